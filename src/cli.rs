@@ -2,6 +2,7 @@ use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
 use crate::lookup::TrustedSource;
+use crate::organize::TransferMode;
 
 #[derive(Parser)]
 #[command(name = "audiobookctl")]
@@ -18,6 +19,15 @@ pub struct Cli {
     /// Suppress non-essential output
     #[arg(short, long, global = true)]
     pub quiet: bool,
+
+    /// Wait for files and libraries locked by another audiobookctl run instead of failing
+    #[arg(long, global = true)]
+    pub wait: bool,
+
+    /// Give hardlinked files their own copy before writing metadata to them
+    /// (otherwise the other links, e.g. a seeding download, change too)
+    #[arg(long, global = true)]
+    pub break_links: bool,
 }
 
 #[derive(Subcommand)]
@@ -38,8 +48,8 @@ pub enum Commands {
 
     /// Edit metadata in $EDITOR with diff preview
     Edit {
-        /// Path to the m4b file
-        file: Option<PathBuf>,
+        /// Path to the m4b file; several files or a directory are edited in one document
+        files: Vec<PathBuf>,
 
         /// Actually apply changes (default: dry-run)
         #[arg(long)]
@@ -62,6 +72,37 @@ pub enum Commands {
         commit_all: bool,
     },
 
+    /// Set fields directly, without an editor
+    Set {
+        /// Paths to m4b files
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Field to set as FIELD=VALUE (repeatable; an empty value clears the field)
+        #[arg(long = "field", value_name = "FIELD=VALUE")]
+        fields: Vec<String>,
+
+        /// Read fields from a JSON object (use - for stdin)
+        #[arg(long, value_name = "PATH")]
+        from_json: Option<PathBuf>,
+
+        /// Read fields from a TOML table (use - for stdin)
+        #[arg(long, value_name = "PATH")]
+        from_toml: Option<PathBuf>,
+
+        /// Actually apply changes (default: dry-run)
+        #[arg(long)]
+        no_dry_run: bool,
+
+        /// Skip confirmation prompt
+        #[arg(long)]
+        yes: bool,
+
+        /// Skip creating backup file
+        #[arg(long = "no-backup-i-void-my-warranty")]
+        no_backup: bool,
+    },
+
     /// Search local audiobook database
     Search {
         /// Free-text search query
@@ -124,6 +165,14 @@ pub enum Commands {
         /// Trust this source and auto-accept its values (skip editor for conflicts)
         #[arg(long, value_enum)]
         trust_source: Option<TrustedSource>,
+
+        /// Only look up these fields (comma-separated, e.g. narrator,series)
+        #[arg(long, value_delimiter = ',', value_name = "FIELDS")]
+        fields: Vec<String>,
+
+        /// Only fill fields that are empty in the file
+        #[arg(long)]
+        only_missing: bool,
     },
 
     /// Look up metadata for all audiobooks in a directory
@@ -150,13 +199,102 @@ pub enum Commands {
         /// Trust this source and auto-accept its values (skip editor for conflicts)
         #[arg(long, value_enum)]
         trust_source: Option<TrustedSource>,
+
+        /// Number of books to query concurrently (uses config default if not specified)
+        #[arg(long, short = 'j')]
+        jobs: Option<usize>,
+
+        /// Resume an interrupted run, keeping stored results and decisions
+        #[arg(long)]
+        resume: bool,
+
+        /// Write all results to a report file (JSON, or CSV with a .csv extension)
+        /// instead of reviewing them; apply later with lookup-apply
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["auto_accept", "resume"])]
+        report: Option<PathBuf>,
+
+        /// Only look up these fields (comma-separated, e.g. narrator,series)
+        #[arg(long, value_delimiter = ',', value_name = "FIELDS")]
+        fields: Vec<String>,
+
+        /// Only fill fields that are empty in the file
+        #[arg(long)]
+        only_missing: bool,
+    },
+
+    /// Apply the decisions recorded in a lookup-all report
+    LookupApply {
+        /// Report file written by lookup-all --report
+        report: PathBuf,
+
+        /// Actually apply changes (default: dry-run, saves pending edits)
+        #[arg(long)]
+        no_dry_run: bool,
+
+        /// Skip confirmation prompt
+        #[arg(long)]
+        yes: bool,
+
+        /// Skip creating backup files
+        #[arg(long = "no-backup-i-void-my-warranty")]
+        no_backup: bool,
+    },
+
+    /// Export metadata to CSV for editing in a spreadsheet
+    ExportCsv {
+        /// Directory to scan for m4b files
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+
+        /// Write to this file instead of stdout
+        #[arg(long, short = 'o', value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// Import a CSV written by export-csv (rows for changed files are rejected)
+    ImportCsv {
+        /// CSV file to import
+        file: PathBuf,
+
+        /// Actually apply changes (default: dry-run, saves pending edits)
+        #[arg(long)]
+        no_dry_run: bool,
+
+        /// Skip confirmation prompt
+        #[arg(long)]
+        yes: bool,
+
+        /// Skip creating backup files
+        #[arg(long = "no-backup-i-void-my-warranty")]
+        no_backup: bool,
+    },
+
+    /// Lock fields so lookups never change them (lists locked fields if none given)
+    Lock {
+        /// Path to the m4b file
+        file: PathBuf,
+
+        /// Fields to lock
+        fields: Vec<String>,
+
+        /// Unlock the given fields instead (all fields if none given)
+        #[arg(long)]
+        unlock: bool,
+
+        /// Actually apply changes (default: dry-run)
+        #[arg(long)]
+        no_dry_run: bool,
+
+        /// Skip creating backup file
+        #[arg(long = "no-backup-i-void-my-warranty")]
+        no_backup: bool,
     },
 
     /// Organize audiobooks into a structured directory format
     Organize {
         /// Source directory containing .m4b files to organize
-        #[arg(long)]
-        source: PathBuf,
+        #[arg(long, required_unless_present_any = ["resume", "rollback"])]
+        source: Option<PathBuf>,
 
         /// Destination directory (uses config default if not specified)
         #[arg(long)]
@@ -166,10 +304,15 @@ pub enum Commands {
         #[arg(long)]
         format: Option<String>,
 
-        /// Actually copy files (default: dry-run)
+        /// Actually copy or move files (default: dry-run)
         #[arg(long)]
         no_dry_run: bool,
 
+        /// How files get into the library: copy, move (removing each source only
+        /// after its destination is verified), hardlink or reflink
+        #[arg(long, value_enum, default_value_t = TransferMode::Copy)]
+        mode: TransferMode,
+
         /// Allow files with missing metadata (placed in __uncategorized__)
         #[arg(long)]
         allow_uncategorized: bool,
@@ -177,6 +320,15 @@ pub enum Commands {
         /// Show source→dest list instead of tree view
         #[arg(long)]
         list: bool,
+
+        /// Continue an interrupted run into the destination
+        #[arg(long, conflicts_with_all = ["source", "rollback"])]
+        resume: bool,
+
+        /// Undo an interrupted run into the destination, removing the files it
+        /// created (or moving them back)
+        #[arg(long, conflicts_with = "source")]
+        rollback: bool,
     },
 
     /// Scan organized library and fix non-compliant paths
@@ -254,6 +406,51 @@ pub enum Commands {
         #[command(subcommand)]
         action: PendingAction,
     },
+
+    /// Show the journal of metadata changes and file moves
+    History {
+        /// Only show entries involving this file
+        file: Option<PathBuf>,
+
+        /// Maximum number of entries to show
+        #[arg(long, short = 'n', default_value_t = 20)]
+        limit: usize,
+
+        /// Show field values and run ids
+        #[arg(long)]
+        details: bool,
+    },
+
+    /// Revert a journal entry (metadata change, move or copy)
+    Undo {
+        /// Journal entry id (see history)
+        id: u64,
+
+        /// Revert every entry of the run the id starts
+        #[arg(long)]
+        run: bool,
+
+        /// Actually revert (default: dry-run)
+        #[arg(long)]
+        no_dry_run: bool,
+
+        /// Skip creating backup files
+        #[arg(long = "no-backup-i-void-my-warranty")]
+        no_backup: bool,
+    },
+
+    /// Show the genre distribution in the library index
+    Genres {
+        /// Library directory (searches upward for the database)
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+
+    /// Manage canonical author/narrator names
+    Names {
+        #[command(subcommand)]
+        action: NamesAction,
+    },
 }
 
 #[derive(Subcommand)]
@@ -278,6 +475,35 @@ pub enum BackupsAction {
         #[arg(long)]
         yes: bool,
     },
+    /// Restore files from their backups
+    Restore {
+        /// File, or directory to restore every backup in (current directory if not specified)
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        /// Backup generation to restore, counting back from 0 (newest); single file only
+        #[arg(long, default_value_t = 0)]
+        generation: usize,
+
+        /// Restore metadata backups whose audio hash is missing or unreadable,
+        /// without checking they belong to the file's audio
+        #[arg(long)]
+        force: bool,
+
+        /// Actually restore (default: dry-run showing the diff)
+        #[arg(long)]
+        no_dry_run: bool,
+
+        /// Skip confirmation prompt
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Check that backups are readable and match their original's audio
+    Verify {
+        /// Directory to scan (current directory if not specified)
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
 }
 
 #[derive(Subcommand)]
@@ -311,4 +537,43 @@ pub enum PendingAction {
         /// Path to specific m4b file (clears all if not specified)
         file: Option<PathBuf>,
     },
+    /// Find edits whose file is gone: re-target them via the library index or remove them
+    Gc {
+        /// Directory inside the indexed library used to find moved files
+        #[arg(default_value = ".")]
+        library: PathBuf,
+
+        /// Actually re-target and remove edits (default: dry-run)
+        #[arg(long)]
+        no_dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum NamesAction {
+    /// Find probable spelling variants of authors and narrators in the index
+    Scan {
+        /// Library directory (searches upward for the database)
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+
+        /// Confirm each suggested group in turn, recording the most common spelling as canonical
+        #[arg(long)]
+        accept: bool,
+    },
+    /// List canonical names and their aliases
+    List,
+    /// Record aliases for a canonical name
+    Merge {
+        /// Canonical name
+        canonical: String,
+
+        /// Variant spellings to map to the canonical name
+        aliases: Vec<String>,
+    },
+    /// Remove a canonical name (with its aliases) or a single alias
+    Remove {
+        /// Name to remove
+        name: String,
+    },
 }
@@ -1,11 +1,17 @@
-//! Backups command - manage .bak files
+//! Backups command - manage .bak and .meta.bak files and the central store
 
 use crate::config::Config;
-use crate::safety::backup::{find_all_backups, format_size};
-use anyhow::{Context, Result};
-use std::fs;
+use crate::editor::{compute_changes, format_diff};
+use crate::hash::audio_sha256;
+use crate::metadata::read_metadata;
+use crate::safety::absolute_path;
+use crate::safety::backup::{
+    find_all_backups, format_size, remove_backup, restore_backup, BackupInfo, BackupKind,
+};
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeSet;
 use std::io::{self, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// List all backup files
 pub fn list(dir: &Path) -> Result<()> {
@@ -22,10 +28,15 @@ pub fn list(dir: &Path) -> Result<()> {
     println!();
 
     for backup in &backups {
+        let kind = match backup.kind {
+            BackupKind::Full => "full",
+            BackupKind::Metadata => "metadata",
+        };
         println!(
-            "  {} ({})",
-            backup.backup_path.display(),
-            format_size(backup.size_bytes)
+            "  {} ({}, {})",
+            label(backup),
+            format_size(backup.size_bytes),
+            kind
         );
     }
 
@@ -76,9 +87,8 @@ pub fn clean(dir: &Path, all: bool, yes: bool) -> Result<()> {
         }
 
         for backup in &backups {
-            fs::remove_file(&backup.backup_path)
-                .with_context(|| format!("Failed to delete {}", backup.backup_path.display()))?;
-            println!("Deleted: {}", backup.backup_path.display());
+            remove_backup(backup).with_context(|| format!("Failed to delete {}", label(backup)))?;
+            println!("Deleted: {}", label(backup));
         }
 
         println!();
@@ -95,7 +105,7 @@ pub fn clean(dir: &Path, all: bool, yes: bool) -> Result<()> {
         for backup in &backups {
             print!(
                 "Delete {} ({})? [y/N/q] ",
-                backup.backup_path.display(),
+                label(backup),
                 format_size(backup.size_bytes)
             );
             io::stdout().flush()?;
@@ -109,7 +119,7 @@ pub fn clean(dir: &Path, all: bool, yes: bool) -> Result<()> {
             }
 
             if input == "y" || input == "yes" {
-                fs::remove_file(&backup.backup_path)?;
+                remove_backup(backup)?;
                 deleted_count += 1;
                 deleted_size += backup.size_bytes;
                 println!("  Deleted.");
@@ -131,6 +141,201 @@ pub fn clean(dir: &Path, all: bool, yes: bool) -> Result<()> {
     Ok(())
 }
 
+/// Restore a file, or every file in a directory, from its most recent backup
+/// (or an older generation of a single file's backup)
+pub fn restore(
+    path: &Path,
+    generation: usize,
+    force: bool,
+    no_dry_run: bool,
+    yes: bool,
+) -> Result<()> {
+    let backups = if path.is_dir() {
+        if generation > 0 {
+            bail!("--generation needs a single file");
+        }
+        let originals: BTreeSet<PathBuf> = find_all_backups(path)?
+            .into_iter()
+            .map(|b| absolute_path(&b.original_path))
+            .collect();
+        let mut backups = Vec::new();
+        for original in &originals {
+            backups.extend(BackupInfo::for_file(original)?);
+        }
+        backups
+    } else {
+        let mut generations = BackupInfo::generations(path)?;
+        if generations.is_empty() {
+            bail!("No backup found for: {}", path.display());
+        }
+        if generation >= generations.len() {
+            bail!(
+                "{} has {} backup generation(s); --generation counts back from 0 (newest)",
+                path.display(),
+                generations.len()
+            );
+        }
+        vec![generations.swap_remove(generation)]
+    };
+
+    if backups.is_empty() {
+        println!("No backup files found in {}", path.display());
+        return Ok(());
+    }
+
+    let mut planned = Vec::new();
+    for backup in backups {
+        let original = &backup.original_path;
+        if !original.exists() {
+            println!("\u{2717} {} (file no longer exists)", original.display());
+            continue;
+        }
+
+        let changes = match (read_metadata(original), backup.metadata()) {
+            (Ok(current), Ok(saved)) => compute_changes(&current, &saved),
+            (Err(e), _) | (_, Err(e)) => {
+                println!("\u{2717} {} ({})", original.display(), e);
+                continue;
+            }
+        };
+
+        if backup.kind == BackupKind::Metadata && !force {
+            if let Err(e) = backup.audio_hash() {
+                println!(
+                    "\u{2717} {} ({}; use --force to restore without checking the audio)",
+                    original.display(),
+                    e
+                );
+                continue;
+            }
+        }
+
+        if changes.is_empty() && backup.kind == BackupKind::Metadata {
+            println!("{}: tags already match the backup", original.display());
+            continue;
+        }
+
+        println!("{}", format_diff(&original.display().to_string(), &changes));
+        if backup.kind == BackupKind::Full {
+            println!("(full backup: the whole file is replaced)");
+        }
+        println!();
+        planned.push(backup);
+    }
+
+    if planned.is_empty() {
+        println!("Nothing to restore.");
+        return Ok(());
+    }
+
+    if !no_dry_run {
+        println!(
+            "Dry run - {} file(s) would be restored. Run with --no-dry-run to restore.",
+            planned.len()
+        );
+        return Ok(());
+    }
+
+    if !yes {
+        print!("Restore {} file(s) from backup? [y/N] ", planned.len());
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+
+        if !input.trim().eq_ignore_ascii_case("y") && !input.trim().eq_ignore_ascii_case("yes") {
+            println!("Aborted.");
+            return Ok(());
+        }
+    }
+
+    let mut restored = 0;
+    let mut failed = 0;
+    for backup in &planned {
+        match restore_backup(backup, force) {
+            Ok(()) => {
+                println!("  \u{2713} {}", backup.original_path.display());
+                restored += 1;
+            }
+            Err(e) => {
+                println!("  \u{2717} {} ({:#})", backup.original_path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+
+    println!();
+    println!("Restored: {}, Failed: {}", restored, failed);
+    println!("Backups were kept; remove them with `audiobookctl backups clean`.");
+
+    Ok(())
+}
+
+/// Check every backup in a directory can be trusted for a restore
+pub fn verify(dir: &Path) -> Result<()> {
+    let backups = find_all_backups(dir)?;
+
+    if backups.is_empty() {
+        println!("No backup files found in {}", dir.display());
+        return Ok(());
+    }
+
+    let mut untrusted = 0;
+    for backup in &backups {
+        match check_backup(backup) {
+            Ok(()) => println!("  \u{2713} {}", label(backup)),
+            Err(e) => {
+                println!("  \u{2717} {} ({:#})", label(backup), e);
+                untrusted += 1;
+            }
+        }
+    }
+
+    println!();
+    println!(
+        "OK: {}, Untrusted: {}",
+        backups.len() - untrusted,
+        untrusted
+    );
+
+    if untrusted > 0 {
+        bail!("{} backup(s) can't be trusted", untrusted);
+    }
+    Ok(())
+}
+
+/// A backup can be trusted if it's readable and holds the same audio as its original
+fn check_backup(backup: &BackupInfo) -> Result<()> {
+    let expected = backup.audio_hash()?;
+
+    if !backup.original_path.exists() {
+        bail!(
+            "original {} no longer exists",
+            backup.original_path.display()
+        );
+    }
+    if audio_sha256(&backup.original_path)? != expected {
+        bail!("audio differs from {}", backup.original_path.display());
+    }
+
+    Ok(())
+}
+
+/// How a backup is shown: its file, or the original and date for store entries
+fn label(backup: &BackupInfo) -> String {
+    match &backup.stored {
+        Some(entry) => format!(
+            "{} [store, {}]",
+            entry.original.display(),
+            entry
+                .created_at
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+        ),
+        None => backup.backup_path.display().to_string(),
+    }
+}
+
 /// Get current backup storage usage
 pub fn current_usage(dir: &Path) -> Result<u64> {
     let backups = find_all_backups(dir)?;
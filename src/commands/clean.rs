@@ -8,6 +8,8 @@ use walkdir::WalkDir;
 
 use crate::config::Config;
 use crate::database::LibraryDb;
+use crate::journal;
+use crate::lockfile;
 
 /// Extensions recognized as auxiliary files (e.g., book.cue for book.m4b)
 const AUXILIARY_EXTENSIONS: &[&str] = &["cue", "pdf", "jpg", "png"];
@@ -40,6 +42,10 @@ pub fn run(dest_override: Option<&PathBuf>, dry_run: bool) -> Result<()> {
         bail!("Not a directory: {:?}", dir);
     }
 
+    let _lock = (!dry_run)
+        .then(|| lockfile::lock_library(&dir))
+        .transpose()?;
+
     println!("Opening database in {:?}...", dir);
     let db = LibraryDb::open(&dir)?;
 
@@ -207,6 +213,7 @@ pub fn run(dest_override: Option<&PathBuf>, dry_run: bool) -> Result<()> {
                 parents_to_check.insert(parent.to_path_buf());
             }
             std::fs::remove_file(path).with_context(|| format!("Failed to remove {:?}", path))?;
+            journal::record_remove(path);
             println!("  {} {}", "Removed".red(), path.display());
             removed += 1;
         }
@@ -217,6 +224,7 @@ pub fn run(dest_override: Option<&PathBuf>, dry_run: bool) -> Result<()> {
                 parents_to_check.insert(parent.to_path_buf());
             }
             std::fs::remove_file(path).with_context(|| format!("Failed to remove {:?}", path))?;
+            journal::record_remove(path);
             println!("  {} {}", "Removed".red(), path.display());
             removed += 1;
         }
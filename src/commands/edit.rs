@@ -1,16 +1,21 @@
-use crate::editor::{compute_changes, format_diff, metadata_to_toml, toml_to_metadata};
+use crate::commands::pending::resolve_edit;
+use crate::editor::{
+    batch_to_toml, compute_changes, format_diff, metadata_to_toml, toml_to_batch, toml_to_metadata,
+};
 use crate::metadata::{read_metadata, write_metadata, AudiobookMetadata};
+use crate::names::NameRegistry;
+use crate::organize::scan_directory;
 use crate::safety::{
-    backup_path_for, create_backup, delete_backup, find_all_backups, format_size, has_backup,
+    create_backup, delete_backup, find_all_backups, format_size, remove_backup, BackupInfo,
     PendingEditsCache,
 };
 use anyhow::{bail, Context, Result};
 use std::io::{self, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 pub fn run(
-    file: Option<&Path>,
+    files: &[PathBuf],
     no_dry_run: bool,
     yes: bool,
     no_backup: bool,
@@ -23,8 +28,12 @@ pub fn run(
     }
 
     // All other operations require a file
-    let file =
-        file.ok_or_else(|| anyhow::anyhow!("No file specified. Use: audiobookctl edit <file>"))?;
+    let file = match files {
+        [] => bail!("No file specified. Use: audiobookctl edit <file>"),
+        [file] if !file.is_dir() => file.as_path(),
+        _ if commit => bail!("--commit takes a single file"),
+        _ => return run_batch(files, no_dry_run, yes, no_backup),
+    };
 
     // Handle --commit for specific file
     if commit {
@@ -38,20 +47,43 @@ pub fn run(
     let original_metadata = read_metadata(file)?;
 
     // Check for pending edit
-    let (edited_toml, from_cache) = if no_dry_run && cache.has_pending(file)? {
-        // Load from cache
-        let pending = cache.load(file)?.unwrap();
+    let pending = if no_dry_run {
+        // Pick up an edit saved before the file was moved
+        cache.adopt_orphan(file)?;
+        cache.load(file)?
+    } else {
+        None
+    };
+    let (edited_toml, mut new_metadata, from_cache) = if let Some(pending) = pending {
+        // Load from cache, merging with any newer changes to the file
         println!("Loading pending edit from cache...");
-        (pending.toml_content, true)
+        let metadata = resolve_edit(&pending, file, !yes)?;
+        (pending.toml_content, metadata, true)
     } else {
         // Open in editor
         let toml = metadata_to_toml(&original_metadata);
         let edited = open_in_editor(&toml)?;
-        (edited, false)
+        let metadata = toml_to_metadata(&edited).context("Failed to parse edited TOML")?;
+        (edited, metadata, false)
     };
 
-    // Parse edited TOML
-    let new_metadata = toml_to_metadata(&edited_toml).context("Failed to parse edited TOML")?;
+    // Use canonical author/narrator names from the registry
+    let edited_metadata = new_metadata.clone();
+    NameRegistry::load()?.apply(&mut new_metadata);
+    for (field, edited, canonical) in [
+        ("author", &edited_metadata.author, &new_metadata.author),
+        (
+            "narrator",
+            &edited_metadata.narrator,
+            &new_metadata.narrator,
+        ),
+    ] {
+        if let (Some(edited), Some(canonical)) = (edited, canonical) {
+            if edited != canonical {
+                println!("Using canonical {}: {} -> {}", field, edited, canonical);
+            }
+        }
+    }
 
     // Compute and display diff
     let changes = compute_changes(&original_metadata, &new_metadata);
@@ -82,6 +114,121 @@ pub fn run(
     Ok(())
 }
 
+/// Edit several files (or every file under a directory) in one document
+fn run_batch(paths: &[PathBuf], no_dry_run: bool, yes: bool, no_backup: bool) -> Result<()> {
+    let mut originals: Vec<(PathBuf, AudiobookMetadata)> = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            for scanned in scan_directory(path)? {
+                originals.push((scanned.path, scanned.metadata));
+            }
+        } else {
+            originals.push((path.clone(), read_metadata(path)?));
+        }
+    }
+    originals.sort_by(|a, b| a.0.cmp(&b.0));
+    originals.dedup_by(|a, b| a.0 == b.0);
+
+    if originals.is_empty() {
+        println!("No .m4b files found.");
+        return Ok(());
+    }
+
+    let edited_toml = open_in_editor(&batch_to_toml(&originals))?;
+    let edited = toml_to_batch(&edited_toml).context("Failed to parse edited TOML")?;
+    let names = NameRegistry::load()?;
+
+    // Pair each edited book with its original; books deleted from the document are left alone
+    let mut planned: Vec<(PathBuf, AudiobookMetadata)> = Vec::new();
+    for (path, mut new_metadata) in edited {
+        let Some((_, original)) = originals.iter().find(|(p, _)| *p == path) else {
+            bail!("{} was not part of this edit", path.display());
+        };
+        names.apply(&mut new_metadata);
+
+        let changes = compute_changes(original, &new_metadata);
+        if changes.is_empty() {
+            continue;
+        }
+        println!("{}", format_diff(&path.display().to_string(), &changes));
+        planned.push((path, new_metadata));
+    }
+
+    println!();
+    println!("{} of {} files changed", planned.len(), originals.len());
+
+    save_or_apply(&planned, no_dry_run, yes, no_backup)
+}
+
+/// Save planned changes as pending edits, or (with `--no-dry-run`) confirm once and write them
+pub fn save_or_apply(
+    planned: &[(PathBuf, AudiobookMetadata)],
+    no_dry_run: bool,
+    yes: bool,
+    no_backup: bool,
+) -> Result<()> {
+    if planned.is_empty() {
+        return Ok(());
+    }
+
+    let cache = PendingEditsCache::new()?;
+
+    if !no_dry_run {
+        for (path, new_metadata) in planned {
+            cache.save(path, &metadata_to_toml(new_metadata))?;
+        }
+        println!();
+        println!("Changes saved to pending cache.");
+        println!("To apply: audiobookctl pending apply (or rerun with --no-dry-run)");
+        return Ok(());
+    }
+
+    // Confirm
+    if !yes {
+        print!("Apply changes to {} file(s)? [y/N] ", planned.len());
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+
+        if !input.trim().eq_ignore_ascii_case("y") && !input.trim().eq_ignore_ascii_case("yes") {
+            println!("Aborted.");
+            return Ok(());
+        }
+    }
+
+    println!();
+    let mut applied = 0;
+    let mut failed = 0;
+
+    for (path, new_metadata) in planned {
+        let result = (|| -> Result<()> {
+            if !no_backup {
+                create_backup(path)?;
+            }
+            write_metadata(path, new_metadata)?;
+            cache.clear(path)?;
+            Ok(())
+        })();
+
+        match result {
+            Ok(()) => {
+                println!("  \u{2713} {}", path.display());
+                applied += 1;
+            }
+            Err(e) => {
+                println!("  \u{2717} {} ({})", path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+
+    println!();
+    println!("Applied: {}, Failed: {}", applied, failed);
+
+    Ok(())
+}
+
 fn open_in_editor(content: &str) -> Result<String> {
     let editor = std::env::var("EDITOR")
         .or_else(|_| std::env::var("VISUAL"))
@@ -152,18 +299,25 @@ fn apply_changes(
 }
 
 fn handle_commit(file: &Path) -> Result<()> {
-    if !has_backup(file) {
+    let backups = BackupInfo::generations(file)?;
+    let Some(backup) = backups.first() else {
         bail!("No backup found for: {}", file.display());
-    }
-
-    let backup = backup_path_for(file);
-    let size = std::fs::metadata(&backup).map(|m| m.len()).unwrap_or(0);
+    };
 
-    print!(
-        "Delete backup {} ({})? [y/N] ",
-        backup.display(),
-        format_size(size)
-    );
+    if backups.len() == 1 {
+        print!(
+            "Delete backup {} ({})? [y/N] ",
+            backup.backup_path.display(),
+            format_size(backup.size_bytes)
+        );
+    } else {
+        print!(
+            "Delete {} backups of {} ({})? [y/N] ",
+            backups.len(),
+            file.display(),
+            format_size(backups.iter().map(|b| b.size_bytes).sum())
+        );
+    }
     io::stdout().flush()?;
 
     let mut input = String::new();
@@ -212,7 +366,7 @@ fn handle_commit_all() -> Result<()> {
 
     if input.trim().eq_ignore_ascii_case("y") || input.trim().eq_ignore_ascii_case("yes") {
         for backup in &backups {
-            std::fs::remove_file(&backup.backup_path)?;
+            remove_backup(backup)?;
         }
         println!("Deleted {} backup(s).", backups.len());
     } else {
@@ -1,6 +1,6 @@
 use anyhow::Result;
 
-use crate::organize::PLACEHOLDERS;
+use crate::organize::{FILTERS, PLACEHOLDERS};
 
 /// Run the fields command - list available format placeholders
 pub fn run() -> Result<()> {
@@ -15,6 +15,18 @@ pub fn run() -> Result<()> {
     println!("Example format: \"{{author}}/{{series}}/{{title}}/{{filename}}\"");
     println!();
     println!("Padding: Use {{series_position:02}} for zero-padded numbers (e.g., 01, 02)");
+    println!(
+        "Groups: {{title}}[ - Book {{series_position}}] drops the bracketed text when a field inside is missing"
+    );
+    println!("        (use \\[ and \\] for literal brackets)");
+
+    println!();
+    println!("Filters (e.g., {{title|strip_leading_article|truncate:60}}):");
+    println!();
+
+    for (name, description) in FILTERS {
+        println!("  {}  - {}", name, description);
+    }
 
     Ok(())
 }
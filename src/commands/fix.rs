@@ -3,7 +3,12 @@ use colored::Colorize;
 use std::path::{Path, PathBuf};
 
 use crate::config::Config;
+use crate::journal;
+use crate::lockfile;
+use crate::names::NameRegistry;
+use crate::organize::transfer::cleanup_empty_dirs;
 use crate::organize::{scan_directory, tree, FixPlan, FormatTemplate};
+use crate::safety::{BackupStore, PendingEditsCache};
 
 /// Run the fix command - scan organized library and fix non-compliant paths
 pub fn run(dest_override: Option<&PathBuf>, no_dry_run: bool, show_all: bool) -> Result<()> {
@@ -21,7 +26,9 @@ pub fn run(dest_override: Option<&PathBuf>, no_dry_run: bool, show_all: bool) ->
         .context("No destination specified. Set [organize] dest in config or use --dest")?;
 
     // Parse format template
-    let template = FormatTemplate::parse(&format_str).context("Failed to parse format string")?;
+    let template = FormatTemplate::parse(&format_str)
+        .context("Failed to parse format string")?
+        .with_names(NameRegistry::load()?);
 
     // Validate destination directory
     if !dest.exists() {
@@ -31,6 +38,11 @@ pub fn run(dest_override: Option<&PathBuf>, no_dry_run: bool, show_all: bool) ->
         bail!("Library path is not a directory: {:?}", dest);
     }
 
+    // Keep other runs out of the library while we move files around
+    let _lock = no_dry_run
+        .then(|| lockfile::lock_library(&dest))
+        .transpose()?;
+
     // Scan library
     println!("Scanning {:?}...", dest);
     let files = scan_directory(&dest).context("Failed to scan library")?;
@@ -75,7 +87,7 @@ pub fn run(dest_override: Option<&PathBuf>, no_dry_run: bool, show_all: bool) ->
     // Execute if --no-dry-run and there are files to fix
     if !plan.needs_fix.is_empty() {
         if no_dry_run {
-            execute_fix(&plan)?;
+            execute_fix(&plan, &dest)?;
         } else {
             println!();
             println!("{}", "Dry run - no files moved.".yellow());
@@ -145,11 +157,15 @@ fn print_results(plan: &FixPlan, show_all: bool) {
     }
 }
 
-fn execute_fix(plan: &FixPlan) -> Result<()> {
+fn execute_fix(plan: &FixPlan, dest: &Path) -> Result<()> {
     println!();
     println!("{}", "Moving files...".green());
 
+    let cache = PendingEditsCache::new()?;
+    let store = BackupStore::open(&Config::load()?.backups)?;
     let mut aux_count = 0;
+    let mut pending_moved = 0;
+    let mut backups_moved = 0;
 
     for op in &plan.needs_fix {
         // Create parent directories
@@ -159,11 +175,17 @@ fn execute_fix(plan: &FixPlan) -> Result<()> {
         }
 
         // Move m4b file (rename)
+        let _file_lock = lockfile::lock_file(&op.source)?;
         std::fs::rename(&op.source, &op.dest)
             .with_context(|| format!("Failed to move {:?} to {:?}", op.source, op.dest))?;
+        journal::record_move(&op.source, &op.dest);
 
         println!("  {} {}", "✓".green(), op.dest.display());
 
+        // Pending edits follow the file
+        pending_moved += cache.relocate(&op.source, &op.dest)?;
+        backups_moved += store.relocate(&op.source, &op.dest)?;
+
         // Move auxiliary files
         for aux in &op.auxiliary {
             // Create parent directories for auxiliary file
@@ -184,6 +206,7 @@ fn execute_fix(plan: &FixPlan) -> Result<()> {
 
             std::fs::rename(&aux.source, &aux.dest)
                 .with_context(|| format!("Failed to move {:?} to {:?}", aux.source, aux.dest))?;
+            journal::record_move(&aux.source, &aux.dest);
 
             println!(
                 "    {} {}",
@@ -194,7 +217,7 @@ fn execute_fix(plan: &FixPlan) -> Result<()> {
         }
 
         // Try to remove empty parent directories
-        cleanup_empty_dirs(&op.source);
+        cleanup_empty_dirs(&op.source, dest);
     }
 
     println!();
@@ -212,19 +235,12 @@ fn execute_fix(plan: &FixPlan) -> Result<()> {
             plan.needs_fix.len()
         );
     }
+    if pending_moved > 0 {
+        println!("{} pending edit(s) moved with their files.", pending_moved);
+    }
+    if backups_moved > 0 {
+        println!("{} stored backup(s) moved with their files.", backups_moved);
+    }
 
     Ok(())
 }
-
-/// Remove empty parent directories after moving a file
-fn cleanup_empty_dirs(file_path: &Path) {
-    let mut current = file_path.parent();
-
-    while let Some(dir) = current {
-        // Try to remove the directory (will fail if not empty)
-        if std::fs::remove_dir(dir).is_err() {
-            break;
-        }
-        current = dir.parent();
-    }
-}
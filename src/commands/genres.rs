@@ -0,0 +1,68 @@
+//! Genres command - genre distribution in the library index
+
+use crate::config::Config;
+use crate::database::LibraryDb;
+use crate::lookup::{map_genres, split_genres};
+use anyhow::Result;
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Show how many books use each genre, and what the `[genres]` mapping would do with it
+pub fn run(dir: &Path) -> Result<()> {
+    let db = LibraryDb::find_from(dir)?.ok_or_else(|| {
+        anyhow::anyhow!("No database found. Run 'audiobookctl index <dir>' first")
+    })?;
+    let config = Config::load().unwrap_or_default();
+
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    let mut untagged = 0;
+    let mut total = 0;
+
+    for (genre, count) in db.genre_counts()? {
+        total += count;
+        let genres = genre
+            .map(|g| split_genres(&g, &config.genres))
+            .unwrap_or_default();
+        if genres.is_empty() {
+            untagged += count;
+        }
+        for genre in genres {
+            *counts.entry(genre).or_default() += count;
+        }
+    }
+
+    if total == 0 {
+        println!("No books in the index.");
+        return Ok(());
+    }
+
+    let mut sorted: Vec<(String, usize)> = counts.into_iter().collect();
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let width = sorted.iter().map(|(g, _)| g.len()).max().unwrap_or(0);
+
+    // Map each genre on its own so multi-genre mappings show in full
+    let mut single = config.genres.clone();
+    single.max = usize::MAX;
+    let mut unfiltered = single.clone();
+    unfiltered.whitelist.clear();
+
+    println!("Genres in library ({} books):", total);
+    println!();
+    for (genre, count) in &sorted {
+        let note = match map_genres(std::slice::from_ref(genre), &single) {
+            Some(mapped) if mapped == *genre => String::new(),
+            Some(mapped) => format!("-> {}", mapped).cyan().to_string(),
+            None if map_genres(std::slice::from_ref(genre), &unfiltered).is_none() => {
+                "-> (dropped)".yellow().to_string()
+            }
+            None => "(not in whitelist)".yellow().to_string(),
+        };
+        println!("  {:<width$}  {:>5}  {}", genre, count, note, width = width);
+    }
+    if untagged > 0 {
+        println!("  {:<width$}  {:>5}", "(none)", untagged, width = width);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! History command - browse the operation journal
+
+use crate::journal::{Journal, JournalEntry, Operation};
+use crate::safety::absolute_path;
+use anyhow::Result;
+use std::path::Path;
+
+/// List journal entries, newest first
+pub fn run(file: Option<&Path>, limit: usize, details: bool) -> Result<()> {
+    let journal = Journal::open()?;
+    let entries = journal.entries()?;
+    let file = file.map(absolute_path);
+
+    let shown: Vec<&JournalEntry> = entries
+        .iter()
+        .rev()
+        .filter(|e| file.as_deref().is_none_or(|f| touches(e, f)))
+        .take(limit)
+        .collect();
+
+    if shown.is_empty() {
+        println!("No history.");
+        return Ok(());
+    }
+
+    for entry in shown {
+        let undone = if Journal::is_undone(&entries, entry.id) {
+            " [undone]"
+        } else {
+            ""
+        };
+        println!(
+            "#{:<5} {}  {:<14} {}{}",
+            entry.id,
+            entry
+                .timestamp
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M"),
+            entry.command,
+            entry.summary(),
+            undone
+        );
+
+        if details {
+            if entry.run != entry.id {
+                println!("       part of run #{}", entry.run);
+            }
+            if let Operation::Metadata { changes, .. } = &entry.operation {
+                for change in changes {
+                    println!(
+                        "       {}: {} -> {}",
+                        change.field,
+                        display(&change.before),
+                        display(&change.after)
+                    );
+                }
+            }
+        }
+    }
+
+    println!();
+    println!("Revert with: audiobookctl undo <id> (or --run <id> for a whole run)");
+
+    Ok(())
+}
+
+/// Whether an entry involves a file
+fn touches(entry: &JournalEntry, file: &Path) -> bool {
+    match &entry.operation {
+        Operation::Metadata { file: f, .. } => f == file,
+        Operation::Move { from, to } | Operation::Copy { from, to, .. } => {
+            from == file || to == file
+        }
+        Operation::Remove { path } => path == file,
+        Operation::Undo { .. } => false,
+    }
+}
+
+fn display(value: &Option<String>) -> String {
+    match value {
+        Some(v) if v.contains('\n') => format!("{:?}", v),
+        Some(v) => format!("\"{}\"", v),
+        None => "(empty)".to_string(),
+    }
+}
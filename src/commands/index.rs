@@ -8,6 +8,7 @@ use walkdir::WalkDir;
 
 use crate::database::LibraryDb;
 use crate::hash::sha256_file;
+use crate::lockfile;
 use crate::metadata::read_metadata;
 
 /// How often to commit during batch indexing
@@ -22,6 +23,8 @@ pub fn run(dir: &Path, full: bool, prune: bool) -> Result<()> {
         anyhow::bail!("Not a directory: {:?}", dir);
     }
 
+    let _lock = lockfile::lock_library(dir)?;
+
     println!("Opening database in {:?}...", dir);
     let mut db = LibraryDb::open(dir)?;
 
@@ -104,6 +104,12 @@ fn prompt_format() -> Result<String> {
     println!("  {}", "{author}/{year} - {title}/{filename}".green());
     println!("    → Andy Weir/2021 - Project Hail Mary/book.m4b");
     println!();
+    println!(
+        "  {}",
+        "{author|first|last_first}/{series|default:\"Standalone\"}/{title}/{filename}".green()
+    );
+    println!("    → Weir, Andy/Standalone/Project Hail Mary/book.m4b");
+    println!();
 
     let default = "{author}/{series}/{title}/{filename}";
     print!("Enter format string [{}]: ", default.green());
@@ -0,0 +1,70 @@
+//! Lock command - protect fields from lookups
+
+use crate::metadata::{read_locked_fields, validate_field_names, write_locked_fields};
+use crate::safety::create_backup;
+use anyhow::Result;
+use std::path::Path;
+
+/// Lock or unlock fields in a file, or list its locked fields
+pub fn run(
+    file: &Path,
+    fields: &[String],
+    unlock: bool,
+    no_dry_run: bool,
+    no_backup: bool,
+) -> Result<()> {
+    validate_field_names(fields)?;
+    let current = read_locked_fields(file)?;
+
+    if fields.is_empty() && !unlock {
+        if current.is_empty() {
+            println!("{}: no locked fields", file.display());
+        } else {
+            println!("{}: locked {}", file.display(), current.join(", "));
+        }
+        return Ok(());
+    }
+
+    let updated: Vec<String> = if unlock {
+        current
+            .iter()
+            .filter(|f| !fields.is_empty() && !fields.contains(f))
+            .cloned()
+            .collect()
+    } else {
+        let mut updated = current.clone();
+        for field in fields {
+            if !updated.contains(field) {
+                updated.push(field.clone());
+            }
+        }
+        updated
+    };
+
+    if updated == current {
+        println!("No changes to apply.");
+        return Ok(());
+    }
+
+    let summary = if updated.is_empty() {
+        "none".to_string()
+    } else {
+        updated.join(", ")
+    };
+
+    if !no_dry_run {
+        println!("{}: locked fields would be {}", file.display(), summary);
+        println!();
+        println!("Run with --no-dry-run to apply.");
+        return Ok(());
+    }
+
+    if !no_backup {
+        let backup = create_backup(file)?;
+        println!("Created backup: {}", backup.display());
+    }
+    write_locked_fields(file, &updated)?;
+    println!("\u{2713} {}: locked fields {}", file.display(), summary);
+
+    Ok(())
+}
@@ -1,40 +1,160 @@
 //! Lookup command - query APIs for audiobook metadata
 
-use crate::editor::{compute_changes, format_diff, toml_to_metadata};
+use crate::config::Config;
+use crate::editor::{compute_changes, format_diff, toml_string_value, toml_to_metadata};
 use crate::lookup::{
-    extract_asin_from_filename, fetch_audible, fetch_audnexus, fetch_openlibrary,
-    has_trusted_source_data, merge_results, resolve_with_trusted_source, FieldValue, LookupResult,
-    MergedMetadata, TrustedSource,
+    best_identifier, discover_identifiers, fetch_audible, fetch_audnexus, fetch_openlibrary,
+    has_trusted_source_data, merge_results_with_options, next_asin, next_isbn, normalize_isbn,
+    read_sidecars, replace_fuzzy_openlibrary, resolve_with_trusted_source, FieldValue, Identifier,
+    IdentifierKind, IdentifierSource, LookupResult, MergeOptions, MergedMetadata, TrustedSource,
 };
-use crate::metadata::{read_metadata, write_metadata, AudiobookMetadata};
+use crate::metadata::{
+    read_locked_fields, read_metadata, validate_field_names, write_metadata, AudiobookMetadata,
+};
+use crate::names::NameRegistry;
 use crate::safety::{create_backup, PendingEditsCache};
 use anyhow::{bail, Context, Result};
 use std::io::{self, Write};
 use std::path::Path;
 use std::process::Command;
+use tracing::debug;
+
+/// Print lookup progress only when running verbosely (single-file lookup)
+macro_rules! progress {
+    ($verbose:expr, $($arg:tt)*) => {
+        if $verbose {
+            print!($($arg)*);
+            let _ = io::stdout().flush();
+        }
+    };
+}
+
+/// Build merge options from `--fields`/`--only-missing`, the config (locked fields,
+/// genre mapping) and the name registry
+pub fn merge_options(
+    fields: &[String],
+    only_missing: bool,
+    config: &Config,
+) -> Result<MergeOptions> {
+    validate_field_names(fields)?;
+    validate_field_names(&config.lookup.locked_fields)
+        .context("Invalid [lookup] locked_fields in config")?;
+
+    Ok(MergeOptions {
+        fields: (!fields.is_empty()).then(|| fields.to_vec()),
+        only_missing,
+        locked: config.lookup.locked_fields.clone(),
+        names: NameRegistry::load()?,
+        genres: config.genres.clone(),
+    })
+}
 
 /// Query APIs and merge with existing metadata
-pub fn query_and_merge(file: &Path) -> Result<(AudiobookMetadata, MergedMetadata, Vec<String>)> {
+pub fn query_and_merge(
+    file: &Path,
+    options: &MergeOptions,
+) -> Result<(AudiobookMetadata, MergedMetadata, Vec<String>)> {
+    let rt = tokio::runtime::Runtime::new().context("Failed to create tokio runtime")?;
+    let client = reqwest::Client::new();
+    rt.block_on(lookup_file(&client, file, true, options))
+}
+
+/// Look up a single file: sidecars, identifiers, API queries, then merge.
+///
+/// Batch lookups share one runtime and client across files and pass
+/// `verbose = false` so per-source progress doesn't interleave.
+pub async fn lookup_file(
+    client: &reqwest::Client,
+    file: &Path,
+    verbose: bool,
+    options: &MergeOptions,
+) -> Result<(AudiobookMetadata, MergedMetadata, Vec<String>)> {
     let original_metadata = read_metadata(file)?;
+    let options = options.with_locked(&read_locked_fields(file)?);
+
+    // Collect ASINs/ISBNs from filename, tags, aux files, comment and directories
+    let mut identifiers = discover_identifiers(
+        file,
+        original_metadata.asin.as_deref(),
+        original_metadata.isbn.as_deref(),
+    );
+
+    // Local sidecar files (metadata.json, .opf, desc.txt, .nfo) come first
+    let mut results = read_sidecars(file);
+    for result in &results {
+        progress!(verbose, "  Found sidecar metadata: {}\n", result.source);
+    }
 
-    // Try to extract ASIN from filename for more accurate lookup
-    let filename_asin = extract_asin_from_filename(file);
-    if let Some(ref asin) = filename_asin {
-        println!("  Found ASIN in filename: {}", asin);
+    // Fill gaps in the search terms from sidecars so untagged files still match
+    let mut search_metadata = original_metadata.clone();
+    for result in &results {
+        fill_if_missing(&mut search_metadata.title, &result.title);
+        fill_if_missing(&mut search_metadata.author, &result.author);
+        add_sidecar_identifiers(&mut identifiers, result);
     }
 
-    let results = query_apis_sync(&original_metadata, filename_asin.as_deref())?;
+    for identifier in &identifiers {
+        progress!(verbose, "  Found {}\n", identifier);
+    }
+
+    results.extend(query_apis(client, &search_metadata, &identifiers, verbose).await?);
 
     if results.is_empty() {
-        anyhow::bail!("No results found from any API");
+        anyhow::bail!("No results found from sidecars or any API");
     }
 
     let sources: Vec<String> = results.iter().map(|r| r.source.clone()).collect();
-    let merged = merge_results(&original_metadata, &results);
+    let merged = merge_results_with_options(&original_metadata, &results, &options);
 
     Ok((original_metadata, merged, sources))
 }
 
+fn fill_if_missing(target: &mut Option<String>, value: &Option<String>) {
+    if target.is_none() {
+        target.clone_from(value);
+    }
+}
+
+/// Add identifiers carried by a sidecar result, unless already known
+fn add_sidecar_identifiers(identifiers: &mut Vec<Identifier>, sidecar: &LookupResult) {
+    let candidates = [
+        (IdentifierKind::Asin, sidecar.asin.clone()),
+        (
+            IdentifierKind::Isbn,
+            sidecar.isbn.as_deref().and_then(normalize_isbn),
+        ),
+    ];
+
+    for (kind, value) in candidates {
+        let Some(value) = value else { continue };
+        if identifiers
+            .iter()
+            .any(|id| id.kind == kind && id.value == value)
+        {
+            continue;
+        }
+        identifiers.push(Identifier {
+            kind,
+            value,
+            source: IdentifierSource::AuxiliaryFile,
+            origin: sidecar.source.clone(),
+        });
+    }
+}
+
+/// What happened to a file after its lookup results were reviewed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LookupOutcome {
+    /// Changes were written to the file
+    Applied,
+    /// Changes were saved to the pending edits cache
+    Deferred,
+    /// Nothing was changed (no edits, or the user declined)
+    Skipped,
+    /// Dry-run of an automatic mode; nothing was decided
+    DryRun,
+}
+
 /// Process a single file lookup (shared by lookup and lookup-all)
 pub fn process_lookup(
     file: &Path,
@@ -43,7 +163,7 @@ pub fn process_lookup(
     no_dry_run: bool,
     yes: bool,
     no_backup: bool,
-) -> Result<bool> {
+) -> Result<LookupOutcome> {
     // Generate TOML
     let toml_content = merged_to_toml(merged);
 
@@ -63,13 +183,16 @@ pub fn process_lookup(
 
     if changes.is_empty() {
         println!("No changes to apply.");
-        return Ok(false);
+        return Ok(LookupOutcome::Skipped);
     }
 
     // Apply changes
     if no_dry_run {
-        apply_changes(file, &new_metadata, yes, no_backup)?;
-        Ok(true)
+        if apply_changes(file, &new_metadata, yes, no_backup)? {
+            Ok(LookupOutcome::Applied)
+        } else {
+            Ok(LookupOutcome::Skipped)
+        }
     } else {
         let cache = PendingEditsCache::new()?;
         let _cache_path = cache.save(file, &edited_toml)?;
@@ -79,7 +202,7 @@ pub fn process_lookup(
             "To apply: audiobookctl edit \"{}\" --no-dry-run",
             file.display()
         );
-        Ok(false)
+        Ok(LookupOutcome::Deferred)
     }
 }
 
@@ -134,10 +257,15 @@ pub fn run(
     yes: bool,
     no_backup: bool,
     trust_source: Option<TrustedSource>,
+    fields: &[String],
+    only_missing: bool,
 ) -> Result<()> {
+    let config = Config::load().unwrap_or_default();
+    let options = merge_options(fields, only_missing, &config)?;
+
     println!("Reading metadata from {}...", file.display());
 
-    let (original, merged, _sources) = query_and_merge(file)?;
+    let (original, merged, _sources) = query_and_merge(file, &options)?;
 
     // Check for early exit
     if let Some(sources) = merged.matches_file() {
@@ -169,104 +297,146 @@ pub fn run(
     Ok(())
 }
 
-/// Synchronous wrapper for async API queries using tokio runtime
-fn query_apis_sync(
-    metadata: &AudiobookMetadata,
-    filename_asin: Option<&str>,
-) -> Result<Vec<LookupResult>> {
-    let rt = tokio::runtime::Runtime::new().context("Failed to create tokio runtime")?;
-    rt.block_on(query_apis(metadata, filename_asin))
+/// Report a provider error: inline when verbose, as a debug log otherwise
+fn report_error(verbose: bool, label: &str, e: &anyhow::Error) {
+    if verbose {
+        eprintln!("{}error - {}", label, e);
+    } else {
+        debug!("{}error - {}", label, e);
+    }
 }
 
 /// Query APIs concurrently
 async fn query_apis(
+    client: &reqwest::Client,
     metadata: &AudiobookMetadata,
-    filename_asin: Option<&str>,
+    identifiers: &[Identifier],
+    verbose: bool,
 ) -> Result<Vec<LookupResult>> {
-    let client = reqwest::Client::new();
-
     // Extract search parameters from existing metadata
     let title = metadata.title.as_deref();
     let author = metadata.author.as_deref();
-    let isbn = metadata.isbn.as_deref();
 
-    // Determine which ASIN to use for Audnexus lookup
-    // Prefer ASIN from filename (authoritative), fall back to metadata
-    let asin_for_lookup = filename_asin.or(metadata.asin.as_deref());
+    // Use the most authoritative identifiers (filename > tag > aux file > comment > directory)
+    let best_asin = best_identifier(identifiers, IdentifierKind::Asin);
+    let isbn = best_identifier(identifiers, IdentifierKind::Isbn).map(|id| id.value.as_str());
 
     let mut results = Vec::new();
 
-    // If we have an ASIN (especially from filename), query Audnexus first
+    // If we have an ASIN, query Audnexus first
     // This is the most accurate source when ASIN is known
-    if let Some(asin) = asin_for_lookup {
-        print!("Querying Audnexus (ASIN: {})... ", asin);
-        io::stdout().flush()?;
+    if let Some(asin) = best_asin {
+        progress!(verbose, "Querying Audnexus ({})... ", asin);
 
-        match fetch_audnexus(&client, title, author, Some(asin)).await {
+        match fetch_audnexus(client, title, author, Some(&asin.value)).await {
             Ok(Some(mut result)) => {
-                // Mark source as "audnexus" or "audnexus (filename)" for clarity
-                if filename_asin.is_some() {
-                    result.source = "audnexus (filename ASIN)".to_string();
+                // Note where the ASIN came from unless it was the file's own tag
+                if asin.source != IdentifierSource::Tag {
+                    result.source = format!("audnexus ({} ASIN)", asin.source.label());
                 }
-                println!("found \"{}\"", result.title.as_deref().unwrap_or("Unknown"));
+                progress!(
+                    verbose,
+                    "found \"{}\"\n",
+                    result.title.as_deref().unwrap_or("Unknown")
+                );
                 results.push(result);
             }
             Ok(None) => {
-                println!("no results");
-            }
-            Err(e) => {
-                eprintln!("error - {}", e);
+                progress!(verbose, "no results\n");
             }
+            Err(e) => report_error(verbose, "", &e),
         }
     }
 
     // Query Audible and Open Library concurrently
-    print!("Querying Audible... ");
-    io::stdout().flush()?;
-
-    let audible_future = fetch_audible(&client, title, author);
+    progress!(verbose, "Querying Audible... ");
+    let audible_future = fetch_audible(client, title, author);
 
-    print!("Querying Open Library... ");
-    io::stdout().flush()?;
-
-    let openlibrary_future = fetch_openlibrary(&client, title, author, isbn);
+    progress!(verbose, "Querying Open Library... ");
+    let openlibrary_future = fetch_openlibrary(client, title, author, isbn);
 
     // Run both concurrently
     let (audible_result, openlibrary_result) = tokio::join!(audible_future, openlibrary_future);
 
-    println!(); // Newline after status messages
+    progress!(verbose, "\n"); // Newline after status messages
 
     // Handle Audible result
     match audible_result {
         Ok(Some(result)) => {
-            println!(
-                "  Audible: found \"{}\"",
+            progress!(
+                verbose,
+                "  Audible: found \"{}\"\n",
                 result.title.as_deref().unwrap_or("Unknown")
             );
             results.push(result);
         }
         Ok(None) => {
-            println!("  Audible: no results");
-        }
-        Err(e) => {
-            eprintln!("  Audible: error - {}", e);
+            progress!(verbose, "  Audible: no results\n");
         }
+        Err(e) => report_error(verbose, "  Audible: ", &e),
     }
 
     // Handle Open Library result
     match openlibrary_result {
         Ok(Some(result)) => {
-            println!(
-                "  Open Library: found \"{}\"",
+            progress!(
+                verbose,
+                "  Open Library: found \"{}\"\n",
                 result.title.as_deref().unwrap_or("Unknown")
             );
             results.push(result);
         }
         Ok(None) => {
-            println!("  Open Library: no results");
+            progress!(verbose, "  Open Library: no results\n");
+        }
+        Err(e) => report_error(verbose, "  Open Library: ", &e),
+    }
+
+    // Second round: exact-ID queries using identifiers reported by the first round
+    if let Some(xref) = next_asin(&results, best_asin.map(|id| id.value.as_str())) {
+        progress!(
+            verbose,
+            "Querying Audnexus (ASIN {} via {})... ",
+            xref.value,
+            xref.via
+        );
+
+        match fetch_audnexus(client, title, author, Some(&xref.value)).await {
+            Ok(Some(mut result)) => {
+                result.source = format!("audnexus ({} ASIN)", xref.via);
+                progress!(
+                    verbose,
+                    "found \"{}\"\n",
+                    result.title.as_deref().unwrap_or("Unknown")
+                );
+                results.push(result);
+            }
+            Ok(None) => progress!(verbose, "no results\n"),
+            Err(e) => report_error(verbose, "", &e),
         }
-        Err(e) => {
-            eprintln!("  Open Library: error - {}", e);
+    }
+
+    if let Some(xref) = next_isbn(&results, isbn) {
+        progress!(
+            verbose,
+            "Querying Open Library (ISBN {} via {})... ",
+            xref.value,
+            xref.via
+        );
+
+        match fetch_openlibrary(client, None, None, Some(&xref.value)).await {
+            Ok(Some(mut result)) => {
+                result.source = format!("openlibrary ({} ISBN)", xref.via);
+                progress!(
+                    verbose,
+                    "found \"{}\"\n",
+                    result.title.as_deref().unwrap_or("Unknown")
+                );
+                // The exact match supersedes the fuzzy title/author result
+                replace_fuzzy_openlibrary(&mut results, result);
+            }
+            Ok(None) => progress!(verbose, "no results\n"),
+            Err(e) => report_error(verbose, "", &e),
         }
     }
 
@@ -287,9 +457,9 @@ pub fn merged_to_toml(merged: &MergedMetadata) -> String {
             FieldValue::Agreed { value: v, sources } => {
                 let source_list = sources.join(", ");
                 lines.push(format!(
-                    "{} = \"{}\"  # [{}]",
+                    "{} = {}  # [{}]",
                     name,
-                    escape_toml_string(v),
+                    toml_string_value(v),
                     source_list
                 ));
             }
@@ -301,20 +471,17 @@ pub fn merged_to_toml(merged: &MergedMetadata) -> String {
                 // Find which group contains the selected value
                 for (sources, alt_value) in alternatives {
                     let source_list = sources.join(", ");
+                    let line = format!(
+                        "{} = {}  # [{}]",
+                        name,
+                        toml_string_value(alt_value),
+                        source_list
+                    );
                     if alt_value == selected {
-                        lines.push(format!(
-                            "{} = \"{}\"  # [{}]",
-                            name,
-                            escape_toml_string(alt_value),
-                            source_list
-                        ));
+                        lines.push(line);
                     } else {
-                        lines.push(format!(
-                            "# {} = \"{}\"  # [{}]",
-                            name,
-                            escape_toml_string(alt_value),
-                            source_list
-                        ));
+                        // Comment out every line of a multi-line value
+                        lines.extend(line.lines().map(|l| format!("# {}", l)));
                     }
                 }
             }
@@ -367,15 +534,6 @@ pub fn merged_to_toml(merged: &MergedMetadata) -> String {
     lines.join("\n")
 }
 
-/// Escape special characters in TOML strings
-fn escape_toml_string(s: &str) -> String {
-    s.replace('\\', "\\\\")
-        .replace('"', "\\\"")
-        .replace('\n', "\\n")
-        .replace('\r', "\\r")
-        .replace('\t', "\\t")
-}
-
 /// Open content in the user's preferred editor
 fn open_in_editor(content: &str) -> Result<String> {
     let editor = std::env::var("EDITOR")
@@ -413,7 +571,7 @@ fn apply_changes(
     new_metadata: &AudiobookMetadata,
     yes: bool,
     no_backup: bool,
-) -> Result<()> {
+) -> Result<bool> {
     // Confirm
     if !yes {
         print!("Apply these changes to {}? [y/N] ", file.display());
@@ -424,7 +582,7 @@ fn apply_changes(
 
         if !input.trim().eq_ignore_ascii_case("y") && !input.trim().eq_ignore_ascii_case("yes") {
             println!("Aborted.");
-            return Ok(());
+            return Ok(false);
         }
     }
 
@@ -440,7 +598,7 @@ fn apply_changes(
     write_metadata(file, new_metadata)?;
     println!("Changes applied successfully.");
 
-    Ok(())
+    Ok(true)
 }
 
 #[cfg(test)]
@@ -531,10 +689,35 @@ mod tests {
     }
 
     #[test]
-    fn test_escape_toml_string() {
-        assert_eq!(escape_toml_string("hello"), "hello");
-        assert_eq!(escape_toml_string("hello\"world"), "hello\\\"world");
-        assert_eq!(escape_toml_string("line1\nline2"), "line1\\nline2");
-        assert_eq!(escape_toml_string("path\\to\\file"), "path\\\\to\\\\file");
+    fn test_merged_to_toml_multiline_description_conflict() {
+        let selected = "Stranded on Mars.\n\nHe has to science the sh*t out of it.";
+        let other = "# A novel\nby Andy Weir";
+        let merged = MergedMetadata {
+            title: FieldValue::Empty,
+            author: FieldValue::Empty,
+            narrator: FieldValue::Empty,
+            series: FieldValue::Empty,
+            series_position: FieldValue::Empty,
+            year: FieldValue::Empty,
+            description: FieldValue::Conflicting {
+                selected: selected.to_string(),
+                alternatives: vec![
+                    (vec!["audible".to_string()], selected.to_string()),
+                    (vec!["audnexus".to_string()], other.to_string()),
+                ],
+            },
+            publisher: FieldValue::Empty,
+            genre: FieldValue::Empty,
+            isbn: FieldValue::Empty,
+            asin: FieldValue::Empty,
+        };
+
+        let toml = merged_to_toml(&merged);
+        assert!(toml.contains("description = \"\"\"\nStranded on Mars.\n\nHe has"));
+        assert!(toml
+            .contains("# description = \"\"\"\n# # A novel\n# by Andy Weir\"\"\"  # [audnexus]"));
+
+        let parsed = toml_to_metadata(&toml).unwrap();
+        assert_eq!(parsed.description.as_deref(), Some(selected));
     }
 }
@@ -1,27 +1,55 @@
 //! Lookup-all command - batch metadata lookup with queue mode
 
 use crate::commands::backups::current_usage;
-use crate::commands::lookup::{merged_to_toml, process_lookup, query_and_merge};
-use crate::config::Config;
+use crate::commands::lookup::{
+    lookup_file, merge_options, merged_to_toml, process_lookup, LookupOutcome,
+};
+use crate::config::{BackupLocation, BackupMode, Config};
 use crate::editor::{compute_changes, toml_to_metadata};
-use crate::lookup::{MergedMetadata, TrustedSource};
-use crate::metadata::{write_metadata, AudiobookMetadata};
-use crate::organize::scanner::scan_directory;
+use crate::lookup::report::{LookupReport, ReportEntry};
+use crate::lookup::session::{EntryStatus, FileFingerprint, LookupSession};
+use crate::lookup::{MergeOptions, MergedMetadata, TrustedSource};
+use crate::metadata::{read_locked_fields, write_metadata, AudiobookMetadata};
+use crate::organize::scanner::{scan_directory, ScannedFile};
 use crate::safety::backup::{create_backup, format_size};
-use anyhow::Result;
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
 use std::fs;
-use std::io::{self, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
 
 /// File with lookup results ready for processing
 struct QueuedFile {
-    path: std::path::PathBuf,
+    path: PathBuf,
     original: AudiobookMetadata,
     merged: MergedMetadata,
     file_size: u64,
 }
 
+/// Result of a background lookup, sent back to the main thread
+struct LookupMessage {
+    path: PathBuf,
+    filename: String,
+    result: Result<(AudiobookMetadata, MergedMetadata, Vec<String>)>,
+    fingerprint: Option<FileFingerprint>,
+}
+
+/// Running totals for the batch summary
+#[derive(Default)]
+struct BatchStats {
+    checked: usize,
+    skipped: usize,
+    errors: usize,
+    processed: usize,
+}
+
+/// How often query results are flushed to the session file
+const SESSION_SAVE_INTERVAL: Duration = Duration::from_secs(2);
+
 /// Run batch lookup on a directory
+#[allow(clippy::too_many_arguments)]
 pub fn run(
     dir: &Path,
     auto_accept: bool,
@@ -29,8 +57,15 @@ pub fn run(
     yes: bool,
     no_backup: bool,
     trust_source: Option<TrustedSource>,
+    jobs: Option<usize>,
+    resume: bool,
+    report: Option<&Path>,
+    fields: &[String],
+    only_missing: bool,
 ) -> Result<()> {
     let config = Config::load().unwrap_or_default();
+    let jobs = jobs.unwrap_or(config.lookup.jobs).max(1);
+    let options = merge_options(fields, only_missing, &config)?;
 
     // Step 1: Scan directory
     println!("Scanning {}...", dir.display());
@@ -42,85 +77,118 @@ pub fn run(
     }
 
     println!("Found {} audiobook files.", files.len());
-    println!();
-
-    // Step 2: Query APIs for each file
-    let mut queued: Vec<QueuedFile> = Vec::new();
-    let mut skipped = 0;
-    let mut errors = 0;
-
-    for (i, file) in files.iter().enumerate() {
-        print!("[{}/{}] Checking {}... ", i + 1, files.len(), file.filename);
-        io::stdout().flush()?;
 
-        match query_and_merge(&file.path) {
-            Ok((original, merged, sources)) => {
-                // Check if trusted source has data
-                if let Some(trusted) = trust_source {
-                    if !crate::lookup::has_trusted_source_data(&merged, trusted) {
-                        println!(
-                            "skipped (trusted source '{}' has no data)",
-                            trusted.as_str()
-                        );
-                        skipped += 1;
-                        continue;
-                    }
-                }
+    if let Some(report_path) = report {
+        return write_report(dir, &files, jobs, &options, trust_source, report_path);
+    }
 
-                if let Some(matched_sources) = merged.matches_file() {
-                    println!("matches [{}] - skipping", matched_sources.join(", "));
-                    skipped += 1;
-                } else {
-                    println!("updates available from [{}]", sources.join(", "));
+    // Step 2: Load or start the session
+    let mut session = open_session(dir, resume)?;
+
+    let total = files.len();
+    let mut stats = BatchStats::default();
+    let mut queue: VecDeque<QueuedFile> = VecDeque::new();
+    let mut to_query = Vec::new();
+
+    for file in &files {
+        match session.entry(&file.path) {
+            Some(entry) if entry.is_current(&file.path) => {
+                stats.checked += 1;
+                if entry.status == EntryStatus::UpToDate {
+                    stats.skipped += 1;
+                } else if !entry.status.is_decided() {
                     let file_size = fs::metadata(&file.path).map(|m| m.len()).unwrap_or(0);
-                    queued.push(QueuedFile {
+                    // Stored results may predate this run's --fields/--only-missing
+                    let locked = read_locked_fields(&file.path).unwrap_or_default();
+                    queue.push_back(QueuedFile {
                         path: file.path.clone(),
-                        original,
-                        merged,
+                        original: entry.original.clone(),
+                        merged: options
+                            .with_locked(&locked)
+                            .apply(&entry.original, &entry.merged),
                         file_size,
                     });
                 }
             }
-            Err(e) => {
-                println!("error: {}", e);
-                errors += 1;
-            }
+            // New, previously failed, or changed since its results were stored or it
+            // was skipped or deferred
+            _ => to_query.push(file),
         }
     }
 
+    if resume {
+        println!(
+            "Resuming: {} decided, {} awaiting review, {} to query.",
+            stats.checked - stats.skipped - queue.len(),
+            queue.len(),
+            to_query.len()
+        );
+    }
+    println!("Querying {} at a time...", jobs);
     println!();
 
-    if queued.is_empty() {
-        println!("All {} files are up to date.", skipped);
-        return Ok(());
-    }
+    // Step 3: Query APIs in the background on one shared runtime and client
+    let rt = tokio::runtime::Runtime::new().context("Failed to create tokio runtime")?;
+    let rx = spawn_lookups(&rt, to_query, jobs, &options, true);
+
+    // Step 4: Review results as they arrive
+    let mut budget = BackupBudget::new(dir, &config, no_backup)?;
+    let mut last_save = Instant::now();
+    let mut completed = true;
+
+    loop {
+        // Block for the next result only when there is nothing to review
+        if queue.is_empty() {
+            match rx.recv() {
+                Ok(message) => handle_message(
+                    message,
+                    total,
+                    trust_source,
+                    &mut queue,
+                    &mut stats,
+                    &mut session,
+                ),
+                Err(_) => break,
+            }
+        }
+        while let Ok(message) = rx.try_recv() {
+            handle_message(
+                message,
+                total,
+                trust_source,
+                &mut queue,
+                &mut stats,
+                &mut session,
+            );
+        }
 
-    // Step 3: Check backup limits
-    let queued = check_backup_limits(dir, queued, &config, no_backup)?;
+        // Query results are cheap to redo, so only flush them periodically
+        if last_save.elapsed() >= SESSION_SAVE_INTERVAL {
+            session.save()?;
+            last_save = Instant::now();
+        }
 
-    if queued.is_empty() {
-        return Ok(());
-    }
+        let Some(item) = queue.pop_front() else {
+            continue;
+        };
 
-    // Step 4: Print summary
-    println!(
-        "Found {} files with available updates ({} already up to date, {} errors)",
-        queued.len(),
-        skipped,
-        errors
-    );
-    println!();
+        if !budget.reserve(item.file_size) {
+            budget.print_limit_reached(stats.processed);
+            completed = false;
+            break;
+        }
 
-    // Step 5: Process queue
-    for (i, item) in queued.iter().enumerate() {
+        stats.processed += 1;
+        println!();
         println!(
-            "[{}/{}] Processing {}",
-            i + 1,
-            queued.len(),
-            item.path.display()
+            "Processing {} ({} checked of {}, {} waiting)",
+            item.path.display(),
+            stats.checked,
+            total,
+            queue.len()
         );
 
-        if let Some(trusted) = trust_source {
+        let outcome = if let Some(trusted) = trust_source {
             // Use trusted source mode
             let resolved = crate::lookup::resolve_with_trusted_source(&item.merged, trusted);
             process_trusted_accept(
@@ -130,7 +198,7 @@ pub fn run(
                 no_dry_run,
                 no_backup,
                 trusted,
-            )?;
+            )
         } else if auto_accept {
             process_auto_accept(
                 &item.path,
@@ -138,7 +206,7 @@ pub fn run(
                 &item.merged,
                 no_dry_run,
                 no_backup,
-            )?;
+            )
         } else {
             process_lookup(
                 &item.path,
@@ -147,68 +215,347 @@ pub fn run(
                 no_dry_run,
                 yes,
                 no_backup,
-            )?;
+            )
+        };
+
+        // Decisions are saved right away; a failed file stays undecided
+        let outcome = match outcome {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                session.save()?;
+                println!();
+                println!("Session saved. Run again with --resume to continue.");
+                return Err(e);
+            }
+        };
+        let status = match outcome {
+            LookupOutcome::Applied => Some(EntryStatus::Applied),
+            LookupOutcome::Deferred => Some(EntryStatus::Deferred),
+            LookupOutcome::Skipped => Some(EntryStatus::Skipped),
+            LookupOutcome::DryRun => None,
+        };
+        if let Some(status) = status {
+            session.set_status(&item.path, status);
         }
+        session.save()?;
+        last_save = Instant::now();
+    }
 
-        println!();
+    // Dropping the runtime cancels lookups still in flight (e.g. backup limit reached)
+    drop(rt);
+
+    if completed {
+        session.remove()?;
+    } else {
+        session.save()?;
+        println!("Session saved. Run again with --resume to continue.");
+    }
+
+    println!();
+    if stats.processed == 0 && stats.errors == 0 {
+        println!("All {} files are up to date.", stats.skipped);
+    } else {
+        println!(
+            "Processed {} files with available updates ({} already up to date, {} errors)",
+            stats.processed, stats.skipped, stats.errors
+        );
     }
 
     Ok(())
 }
 
-/// Check backup storage limits and truncate queue if necessary
-fn check_backup_limits(
-    dir: &Path,
-    mut queued: Vec<QueuedFile>,
-    config: &Config,
-    no_backup: bool,
-) -> Result<Vec<QueuedFile>> {
-    if no_backup {
-        return Ok(queued);
+/// Start background lookups for files, at most `jobs` in flight at once.
+///
+/// Results arrive on the returned channel in completion order; the channel
+/// closes once every file has reported. With `fingerprint`, each file is
+/// also hashed so a resumed session can tell whether it changed.
+fn spawn_lookups(
+    rt: &tokio::runtime::Runtime,
+    files: Vec<&ScannedFile>,
+    jobs: usize,
+    options: &MergeOptions,
+    fingerprint: bool,
+) -> mpsc::Receiver<LookupMessage> {
+    let client = reqwest::Client::new();
+    let options = Arc::new(options.clone());
+    let semaphore = Arc::new(Semaphore::new(jobs));
+    let (tx, rx) = mpsc::channel::<LookupMessage>();
+
+    for file in files {
+        let client = client.clone();
+        let semaphore = Arc::clone(&semaphore);
+        let options = Arc::clone(&options);
+        let tx = tx.clone();
+        let path = file.path.clone();
+        let filename = file.filename.clone();
+
+        rt.spawn(async move {
+            let Ok(_permit) = semaphore.acquire_owned().await else {
+                return;
+            };
+
+            // Hash the file while the APIs are queried
+            let hash_path = path.clone();
+            let hashing = tokio::task::spawn_blocking(move || {
+                fingerprint
+                    .then(|| FileFingerprint::compute(&hash_path).ok())
+                    .flatten()
+            });
+            let result = lookup_file(&client, &path, false, &options).await;
+            let fingerprint = hashing.await.ok().flatten();
+
+            let _ = tx.send(LookupMessage {
+                path,
+                filename,
+                result,
+                fingerprint,
+            });
+        });
     }
 
-    let current = current_usage(dir)?;
-    let limit = config.backups.max_storage_bytes;
-    let queued_size: u64 = queued.iter().map(|f| f.file_size).sum();
+    rx
+}
 
-    if current + queued_size <= limit {
-        return Ok(queued);
-    }
+/// Query every file and write a report instead of reviewing interactively
+fn write_report(
+    dir: &Path,
+    files: &[ScannedFile],
+    jobs: usize,
+    options: &MergeOptions,
+    trust_source: Option<TrustedSource>,
+    report_path: &Path,
+) -> Result<()> {
+    println!("Querying {} at a time...", jobs);
+    println!();
 
-    // Need to truncate
-    let available = limit.saturating_sub(current);
-    let mut allowed_size = 0u64;
-    let mut allowed_count = 0;
+    let rt = tokio::runtime::Runtime::new().context("Failed to create tokio runtime")?;
+    let rx = spawn_lookups(&rt, files.iter().collect(), jobs, options, false);
 
-    for item in &queued {
-        if allowed_size + item.file_size <= available {
-            allowed_size += item.file_size;
-            allowed_count += 1;
-        } else {
-            break;
+    let mut report = LookupReport::new(dir);
+    let mut stats = BatchStats::default();
+    let mut updates = 0;
+
+    for message in rx {
+        stats.checked += 1;
+        print!("[{}/{}] {}: ", stats.checked, files.len(), message.filename);
+
+        match message.result {
+            Ok((original, merged, sources)) => {
+                // Trusted source pre-selects its values for conflicts
+                let merged = match trust_source {
+                    Some(trusted) => crate::lookup::resolve_with_trusted_source(&merged, trusted),
+                    None => merged,
+                };
+                let up_to_date = merged.matches_file().is_some();
+                if up_to_date {
+                    println!("up to date");
+                    stats.skipped += 1;
+                } else {
+                    println!("updates available from [{}]", sources.join(", "));
+                    updates += 1;
+                }
+                report.files.push(ReportEntry::from_merged(
+                    &message.path,
+                    &original,
+                    &merged,
+                    &sources,
+                    up_to_date,
+                ));
+            }
+            Err(e) => {
+                println!("error: {}", e);
+                stats.errors += 1;
+                report
+                    .files
+                    .push(ReportEntry::from_error(&message.path, &e));
+            }
         }
     }
 
+    // Results arrive in completion order; keep the report stable
+    report.files.sort_by(|a, b| a.path.cmp(&b.path));
+    report.write(report_path)?;
+
+    println!();
+    println!(
+        "Wrote report for {} files to {} ({} with updates, {} up to date, {} errors)",
+        report.files.len(),
+        report_path.display(),
+        updates,
+        stats.skipped,
+        stats.errors
+    );
     println!(
-        "Found {} files with updates, but backup limit ({}) allows only {}.",
-        queued.len(),
-        format_size(limit),
-        allowed_count
+        "Review it, then run: audiobookctl lookup-apply \"{}\"",
+        report_path.display()
     );
-    println!("Current backup usage: {}", format_size(current));
-    println!("Run `audiobookctl backups clean` or increase limit in config.");
 
-    if allowed_count == 0 {
-        println!();
-        println!("Cannot process any files - backup limit reached.");
-        return Ok(Vec::new());
+    Ok(())
+}
+
+/// Load the saved session for `--resume`, or start a new one
+fn open_session(dir: &Path, resume: bool) -> Result<LookupSession> {
+    let existing = LookupSession::load(dir)?;
+
+    match existing {
+        Some(session) if resume => {
+            println!(
+                "Resuming session from {} ({} files recorded).",
+                session
+                    .created_at
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M"),
+                session.files.len()
+            );
+            Ok(session)
+        }
+        Some(session) => {
+            println!(
+                "Discarding interrupted session from {} ({} decided). Use --resume to continue it.",
+                session
+                    .created_at
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M"),
+                session.decided_count()
+            );
+            LookupSession::new(dir)
+        }
+        None => {
+            if resume {
+                println!("No session to resume; starting a new one.");
+            }
+            LookupSession::new(dir)
+        }
     }
+}
 
-    println!("Processing first {} files...", allowed_count);
-    println!();
+/// Print a status line for a finished lookup and queue it if it has updates
+fn handle_message(
+    message: LookupMessage,
+    total: usize,
+    trust_source: Option<TrustedSource>,
+    queue: &mut VecDeque<QueuedFile>,
+    stats: &mut BatchStats,
+    session: &mut LookupSession,
+) {
+    stats.checked += 1;
+    print!("[{}/{}] {}: ", stats.checked, total, message.filename);
+
+    match message.result {
+        Ok((original, merged, sources)) => {
+            // Check if trusted source has data
+            let mut up_to_date = false;
+            if let Some(trusted) = trust_source {
+                if !crate::lookup::has_trusted_source_data(&merged, trusted) {
+                    println!(
+                        "skipped (trusted source '{}' has no data)",
+                        trusted.as_str()
+                    );
+                    up_to_date = true;
+                }
+            }
+
+            if !up_to_date {
+                if let Some(matched_sources) = merged.matches_file() {
+                    println!("matches [{}] - skipping", matched_sources.join(", "));
+                    up_to_date = true;
+                }
+            }
+
+            let status = if up_to_date {
+                stats.skipped += 1;
+                EntryStatus::UpToDate
+            } else {
+                println!("updates available from [{}]", sources.join(", "));
+                EntryStatus::Queued
+            };
+            session.record_result(
+                &message.path,
+                status,
+                message.fingerprint,
+                &original,
+                &merged,
+                &sources,
+            );
+
+            if !up_to_date {
+                let file_size = fs::metadata(&message.path).map(|m| m.len()).unwrap_or(0);
+                queue.push_back(QueuedFile {
+                    path: message.path,
+                    original,
+                    merged,
+                    file_size,
+                });
+            }
+        }
+        Err(e) => {
+            println!("error: {}", e);
+            stats.errors += 1;
+        }
+    }
+}
+
+/// Backup storage still available for this run
+struct BackupBudget {
+    /// None when backups are disabled
+    available: Option<u64>,
+    current: u64,
+    limit: u64,
+    mode: BackupMode,
+}
+
+/// Space set aside per file for a metadata backup (tags plus cover art)
+const METADATA_BACKUP_ESTIMATE: u64 = 1024 * 1024;
+
+impl BackupBudget {
+    fn new(dir: &Path, config: &Config, no_backup: bool) -> Result<Self> {
+        let limit = config.backups.max_storage_bytes;
+        let mode = config.backups.mode;
+        // The central store prunes old backups itself to stay within the limit
+        if no_backup || config.backups.location == BackupLocation::Store {
+            return Ok(Self {
+                available: None,
+                current: 0,
+                limit,
+                mode,
+            });
+        }
+
+        let current = current_usage(dir)?;
+        Ok(Self {
+            available: Some(limit.saturating_sub(current)),
+            current,
+            limit,
+            mode,
+        })
+    }
 
-    queued.truncate(allowed_count);
-    Ok(queued)
+    /// Reserve space for backing up one more file, returning false if it doesn't fit
+    fn reserve(&mut self, file_size: u64) -> bool {
+        let size = match self.mode {
+            BackupMode::Full => file_size,
+            BackupMode::Metadata => METADATA_BACKUP_ESTIMATE.min(file_size),
+        };
+        match self.available {
+            None => true,
+            Some(available) if size <= available => {
+                self.available = Some(available - size);
+                true
+            }
+            Some(_) => false,
+        }
+    }
+
+    fn print_limit_reached(&self, processed: usize) {
+        println!();
+        println!(
+            "Backup limit ({}) reached after {} files.",
+            format_size(self.limit),
+            processed
+        );
+        println!("Current backup usage: {}", format_size(self.current));
+        println!("Run `audiobookctl backups clean` or increase limit in config.");
+    }
 }
 
 /// Auto-accept changes when all sources agree
@@ -218,14 +565,14 @@ fn process_auto_accept(
     merged: &MergedMetadata,
     no_dry_run: bool,
     no_backup: bool,
-) -> Result<()> {
+) -> Result<LookupOutcome> {
     // Check if there are any actual conflicts
     let has_conflicts = has_real_conflicts(merged);
 
     if has_conflicts {
         // Fall back to interactive mode for this file
         println!("  Has conflicts - opening editor...");
-        process_lookup(file, original, merged, no_dry_run, false, no_backup)?;
+        process_lookup(file, original, merged, no_dry_run, false, no_backup)
     } else {
         // Auto-apply all agreed values that differ from file
         let toml = merged_to_toml(merged);
@@ -234,7 +581,7 @@ fn process_auto_accept(
 
         if changes.is_empty() {
             println!("  No changes to apply.");
-            return Ok(());
+            return Ok(LookupOutcome::Skipped);
         }
 
         // Show what will be auto-applied
@@ -247,12 +594,12 @@ fn process_auto_accept(
             }
             write_metadata(file, &new_metadata)?;
             println!("  Applied.");
+            Ok(LookupOutcome::Applied)
         } else {
             println!("  (dry-run, use --no-dry-run to apply)");
+            Ok(LookupOutcome::DryRun)
         }
     }
-
-    Ok(())
 }
 
 /// Auto-accept using trusted source values
@@ -263,14 +610,14 @@ fn process_trusted_accept(
     no_dry_run: bool,
     no_backup: bool,
     trusted: TrustedSource,
-) -> Result<()> {
+) -> Result<LookupOutcome> {
     let toml = merged_to_toml(resolved);
     let new_metadata = toml_to_metadata(&toml)?;
     let changes = compute_changes(original, &new_metadata);
 
     if changes.is_empty() {
         println!("  No changes from '{}'.", trusted.as_str());
-        return Ok(());
+        return Ok(LookupOutcome::Skipped);
     }
 
     let fields: Vec<&str> = changes.iter().map(|c| c.field.as_str()).collect();
@@ -282,11 +629,11 @@ fn process_trusted_accept(
         }
         write_metadata(file, &new_metadata)?;
         println!("  Applied.");
+        Ok(LookupOutcome::Applied)
     } else {
         println!("  (dry-run, use --no-dry-run to apply)");
+        Ok(LookupOutcome::DryRun)
     }
-
-    Ok(())
 }
 
 /// Check if merged metadata has any real conflicts (not just empty fields)
@@ -311,3 +658,51 @@ fn has_real_conflicts(merged: &MergedMetadata) -> bool {
         .iter()
         .any(|f| matches!(f, FieldValue::Conflicting { .. }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_budget_reserve() {
+        let mut budget = BackupBudget {
+            available: Some(100),
+            current: 0,
+            limit: 100,
+            mode: BackupMode::Full,
+        };
+
+        assert!(budget.reserve(60));
+        assert!(!budget.reserve(50));
+        assert!(budget.reserve(40));
+        assert!(!budget.reserve(1));
+    }
+
+    #[test]
+    fn test_backup_budget_disabled() {
+        let mut budget = BackupBudget {
+            available: None,
+            current: 0,
+            limit: 0,
+            mode: BackupMode::Full,
+        };
+
+        assert!(budget.reserve(u64::MAX));
+    }
+
+    #[test]
+    fn test_backup_budget_metadata_mode() {
+        let mut budget = BackupBudget {
+            available: Some(3 * METADATA_BACKUP_ESTIMATE),
+            current: 0,
+            limit: 3 * METADATA_BACKUP_ESTIMATE,
+            mode: BackupMode::Metadata,
+        };
+
+        // A 500 MB book only costs its tags
+        assert!(budget.reserve(500 * 1024 * 1024));
+        assert!(budget.reserve(500 * 1024 * 1024));
+        assert!(budget.reserve(500 * 1024 * 1024));
+        assert!(!budget.reserve(500 * 1024 * 1024));
+    }
+}
@@ -0,0 +1,131 @@
+//! Lookup-apply command - apply decisions from a lookup-all report
+
+use crate::editor::{compute_changes, format_diff, metadata_to_toml};
+use crate::lookup::report::{LookupReport, ReportStatus};
+use crate::metadata::{read_metadata, write_metadata, AudiobookMetadata};
+use crate::safety::{create_backup, PendingEditsCache};
+use anyhow::Result;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// A file with decisions that change its metadata
+struct PlannedApply {
+    path: PathBuf,
+    new_metadata: AudiobookMetadata,
+}
+
+/// Apply the decisions recorded in a report
+pub fn run(report_path: &Path, no_dry_run: bool, yes: bool, no_backup: bool) -> Result<()> {
+    let report = LookupReport::read(report_path)?;
+
+    let mut planned = Vec::new();
+    let mut unchanged = 0;
+    let mut rejected = 0;
+
+    for entry in &report.files {
+        if entry.status != ReportStatus::UpdatesAvailable {
+            continue;
+        }
+
+        let current = match read_metadata(&entry.path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                println!("\u{2717} {} ({})", entry.path.display(), e);
+                rejected += 1;
+                continue;
+            }
+        };
+
+        let new_metadata = match entry.apply_to(&current) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                println!("\u{2717} {} ({})", entry.path.display(), e);
+                rejected += 1;
+                continue;
+            }
+        };
+
+        let changes = compute_changes(&current, &new_metadata);
+        if changes.is_empty() {
+            unchanged += 1;
+            continue;
+        }
+
+        println!(
+            "{}",
+            format_diff(&entry.path.display().to_string(), &changes)
+        );
+        planned.push(PlannedApply {
+            path: entry.path.clone(),
+            new_metadata,
+        });
+    }
+
+    println!();
+    println!(
+        "{} files to update, {} without changes, {} rejected",
+        planned.len(),
+        unchanged,
+        rejected
+    );
+
+    if planned.is_empty() {
+        return Ok(());
+    }
+
+    if !no_dry_run {
+        let cache = PendingEditsCache::new()?;
+        for item in &planned {
+            cache.save(&item.path, &metadata_to_toml(&item.new_metadata))?;
+        }
+        println!();
+        println!("Changes saved to pending cache.");
+        println!("To apply: audiobookctl pending apply (or rerun with --no-dry-run)");
+        return Ok(());
+    }
+
+    // Confirm
+    if !yes {
+        print!("Apply changes to {} file(s)? [y/N] ", planned.len());
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+
+        if !input.trim().eq_ignore_ascii_case("y") && !input.trim().eq_ignore_ascii_case("yes") {
+            println!("Aborted.");
+            return Ok(());
+        }
+    }
+
+    println!();
+    let mut applied = 0;
+    let mut failed = 0;
+
+    for item in &planned {
+        let result = apply_one(&item.path, &item.new_metadata, no_backup);
+
+        match result {
+            Ok(()) => {
+                println!("  \u{2713} {}", item.path.display());
+                applied += 1;
+            }
+            Err(e) => {
+                println!("  \u{2717} {} ({})", item.path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+
+    println!();
+    println!("Applied: {}, Failed: {}", applied, failed);
+
+    Ok(())
+}
+
+fn apply_one(file: &Path, new_metadata: &AudiobookMetadata, no_backup: bool) -> Result<()> {
+    if !no_backup {
+        create_backup(file)?;
+    }
+    write_metadata(file, new_metadata)
+}
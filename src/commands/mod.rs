@@ -3,12 +3,20 @@ pub mod clean;
 pub mod edit;
 pub mod fields;
 pub mod fix;
+pub mod genres;
+pub mod history;
 pub mod index;
 pub mod init;
+pub mod lock;
 pub mod lookup;
 pub mod lookup_all;
+pub mod lookup_apply;
+pub mod names;
 pub mod organize;
 pub mod pending;
 pub mod rehash;
 pub mod search;
+pub mod set;
 pub mod show;
+pub mod spreadsheet;
+pub mod undo;
@@ -0,0 +1,123 @@
+//! Names command - manage canonical author/narrator names
+
+use crate::database::LibraryDb;
+use crate::names::{find_variants, NameRegistry};
+use anyhow::{bail, Result};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Find probable name variants in the library index
+pub fn scan(dir: &Path, accept: bool) -> Result<()> {
+    let db = LibraryDb::find_from(dir)?.ok_or_else(|| {
+        anyhow::anyhow!("No database found. Run 'audiobookctl index <dir>' first")
+    })?;
+    let mut registry = NameRegistry::load()?;
+
+    let groups = find_variants(&db.name_counts()?, &registry);
+    if groups.is_empty() {
+        println!("No probable name variants found.");
+        return Ok(());
+    }
+
+    println!("Probable name variants:");
+    println!();
+    for group in &groups {
+        for (i, (name, count)) in group.spellings.iter().enumerate() {
+            let marker = if i == 0 { "*" } else { " " };
+            println!("  {} {} ({} books)", marker, name, count);
+        }
+        println!();
+    }
+
+    if !accept {
+        println!("* = suggested canonical name (most common spelling)");
+        println!("To review the suggestions one by one: audiobookctl names scan --accept");
+        println!("To pick a name: audiobookctl names merge \"<canonical>\" \"<alias>\"...");
+        return Ok(());
+    }
+
+    // Similar names can still be different people, so each group is confirmed
+    let mut recorded = 0;
+    for group in &groups {
+        let aliases: Vec<String> = group.spellings[1..]
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect();
+        print!(
+            "Use \"{}\" for {}? [y/N] ",
+            group.suggested(),
+            aliases
+                .iter()
+                .map(|a| format!("\"{}\"", a))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if input.trim().eq_ignore_ascii_case("y") || input.trim().eq_ignore_ascii_case("yes") {
+            registry.merge(group.suggested(), &aliases)?;
+            recorded += 1;
+        }
+    }
+    if recorded > 0 {
+        registry.save()?;
+    }
+    println!(
+        "\u{2713} Recorded {} of {} name groups",
+        recorded,
+        groups.len()
+    );
+
+    Ok(())
+}
+
+/// List canonical names and their aliases
+pub fn list() -> Result<()> {
+    let registry = NameRegistry::load()?;
+
+    if registry.names.is_empty() {
+        println!("No canonical names registered.");
+        return Ok(());
+    }
+
+    for (canonical, aliases) in &registry.names {
+        println!("{}", canonical);
+        for alias in aliases {
+            println!("  = {}", alias);
+        }
+    }
+
+    Ok(())
+}
+
+/// Record aliases for a canonical name
+pub fn merge(canonical: &str, aliases: &[String]) -> Result<()> {
+    if aliases.is_empty() {
+        bail!("Specify at least one alias to merge into \"{}\"", canonical);
+    }
+
+    let mut registry = NameRegistry::load()?;
+    registry.merge(canonical, aliases)?;
+    registry.save()?;
+    println!(
+        "\u{2713} {} = {}",
+        canonical,
+        registry.names[canonical.trim()].join(", ")
+    );
+
+    Ok(())
+}
+
+/// Remove a canonical name or a single alias
+pub fn remove(name: &str) -> Result<()> {
+    let mut registry = NameRegistry::load()?;
+    if !registry.remove(name) {
+        bail!("\"{}\" is not in the name registry", name);
+    }
+    registry.save()?;
+    println!("\u{2713} Removed {}", name);
+
+    Ok(())
+}
@@ -1,17 +1,25 @@
 use anyhow::{bail, Context, Result};
 use colored::Colorize;
-use std::collections::HashMap;
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 
 use crate::config::Config;
 use crate::database::LibraryDb;
-use crate::hash::{hash_file_path, sha256_file, write_hash_file};
-use crate::metadata::AudiobookMetadata;
+use crate::hash::{get_hash, hash_file_path, sha256_file, write_hash_file};
+use crate::journal;
+use crate::lockfile;
+use crate::metadata::read_metadata;
+use crate::names::NameRegistry;
+use crate::organize::run::{OrganizeRun, RunStep, StepKind, StepStatus};
+use crate::organize::transfer::{
+    cleanup_empty_dirs, copy_file, copy_verified, hardlink_file, move_file, part_path,
+    reflink_file, same_file,
+};
 use crate::organize::{
     scan_directory_with_progress, tree, AlreadyPresent, FormatTemplate, OrganizePlan,
-    PlannedOperation, UncategorizedFile,
+    PlannedOperation, TransferMode, UncategorizedFile,
 };
+use crate::safety::{absolute_path, BackupStore, PendingEditsCache};
 
 /// Run the organize command
 pub fn run(
@@ -19,6 +27,7 @@ pub fn run(
     dest_override: Option<&PathBuf>,
     format_override: Option<&str>,
     no_dry_run: bool,
+    mode: TransferMode,
     allow_uncategorized: bool,
     list_mode: bool,
 ) -> Result<()> {
@@ -36,7 +45,9 @@ pub fn run(
         .context("No destination specified. Set [organize] dest in config or use --dest")?;
 
     // Parse format template
-    let template = FormatTemplate::parse(&format_str).context("Failed to parse format string")?;
+    let template = FormatTemplate::parse(&format_str)
+        .context("Failed to parse format string")?
+        .with_names(NameRegistry::load()?);
 
     // Validate source directory
     if !source.exists() {
@@ -46,6 +57,28 @@ pub fn run(
         bail!("Source is not a directory: {:?}", source);
     }
 
+    // An interrupted run has to be finished or undone first
+    if let Some(interrupted) = OrganizeRun::load(&dest)? {
+        let message = format!(
+            "An interrupted organize run into {:?} has {} of {} file(s) done. Use --resume to finish it or --rollback to undo it.",
+            dest,
+            interrupted.finished_count(),
+            interrupted.steps.len()
+        );
+        if no_dry_run {
+            bail!(message);
+        }
+        eprintln!("{}: {}", "Warning".yellow().bold(), message);
+    }
+
+    // Keep other runs out of the library while we copy into it, and out of
+    // the source while we move files out of it
+    let _locks = if no_dry_run {
+        lock_libraries(source, &dest, mode)?
+    } else {
+        Vec::new()
+    };
+
     // Scan source directory with progress output
     print!("Scanning {:?}... ", source);
     io::stdout().flush().ok();
@@ -72,12 +105,6 @@ pub fn run(
 
     println!("Found {} .m4b file(s)", files.len());
 
-    // Build metadata map for database writes
-    let file_metadata: HashMap<PathBuf, AudiobookMetadata> = files
-        .iter()
-        .map(|f| (f.path.clone(), f.metadata.clone()))
-        .collect();
-
     // Build plan with progress output for hash comparisons
     print!("Planning...");
     io::stdout().flush().ok();
@@ -129,18 +156,19 @@ pub fn run(
 
     // Execute if --no-dry-run
     if no_dry_run {
-        execute_plan(
-            &plan.operations,
-            &plan.already_present,
-            &plan.uncategorized,
-            &dest,
-            allow_uncategorized,
-            &file_metadata,
-        )?;
+        let mut run = OrganizeRun::new(&plan, source, &dest, mode, allow_uncategorized)?;
+        execute_run(&mut run)?;
     } else {
         println!();
-        println!("{}", "Dry run - no files copied.".yellow());
-        println!("Run with {} to copy files.", "--no-dry-run".cyan());
+        println!(
+            "{}",
+            format!("Dry run - no files {}.", mode.done()).yellow()
+        );
+        println!(
+            "Run with {} to {} files.",
+            "--no-dry-run".cyan(),
+            mode.verb()
+        );
     }
 
     Ok(())
@@ -308,111 +336,194 @@ fn print_list_view(
     }
 }
 
-fn execute_plan(
-    operations: &[PlannedOperation],
-    already_present: &[AlreadyPresent],
-    uncategorized: &[UncategorizedFile],
-    dest: &Path,
-    allow_uncategorized: bool,
-    file_metadata: &HashMap<PathBuf, AudiobookMetadata>,
-) -> Result<()> {
-    println!();
-    println!("{}", "Copying files...".green());
-
-    let mut aux_count = 0;
+/// Continue an interrupted organize run
+pub fn resume(dest_override: Option<&PathBuf>, no_dry_run: bool) -> Result<()> {
+    let dest = library_dest(dest_override)?;
+    let mut run = load_run(&dest)?;
+    print_run_status(&run);
 
-    // Copy organized files
-    for op in operations {
-        // Create parent directories
-        if let Some(parent) = op.dest.parent() {
-            std::fs::create_dir_all(parent)
-                .with_context(|| format!("Failed to create directory {:?}", parent))?;
+    let remaining: Vec<&RunStep> = run.steps.iter().filter(|s| !s.is_finished()).collect();
+    if !remaining.is_empty() {
+        println!();
+        println!("Remaining:");
+        for step in remaining {
+            println!("  {} -> {}", step.source.display(), step.dest.display());
         }
+    }
 
-        // Compute source hash before copy
-        let source_hash = sha256_file(&op.source)
-            .with_context(|| format!("Failed to hash source {:?}", op.source))?;
+    if !no_dry_run {
+        println!();
+        println!("{}", "Dry run - nothing resumed.".yellow());
+        println!("Run with {} to continue.", "--resume --no-dry-run".cyan());
+        return Ok(());
+    }
 
-        // Copy m4b file
-        std::fs::copy(&op.source, &op.dest)
-            .with_context(|| format!("Failed to copy {:?} to {:?}", op.source, op.dest))?;
+    let _locks = lock_libraries(&run.source, &run.dest, run.mode)?;
+    execute_run(&mut run)
+}
 
-        // Verify destination hash matches source
-        let dest_hash = sha256_file(&op.dest)
-            .with_context(|| format!("Failed to hash destination {:?}", op.dest))?;
+/// Undo an interrupted organize run: remove the files it put in the library,
+/// or move them back for a move run
+pub fn rollback(dest_override: Option<&PathBuf>, no_dry_run: bool) -> Result<()> {
+    let dest = library_dest(dest_override)?;
+    let mut run = load_run(&dest)?;
+    print_run_status(&run);
 
-        if source_hash != dest_hash {
-            bail!(
-                "Copy verification failed: {:?} -> {:?}\n  Source hash: {}\n  Dest hash:   {}",
-                op.source,
-                op.dest,
-                source_hash,
-                dest_hash
+    let undo: Vec<usize> = (0..run.steps.len())
+        .rev()
+        .filter(|&i| needs_rollback(&run.steps[i]))
+        .collect();
+    println!();
+    if undo.is_empty() {
+        println!("Nothing to roll back.");
+    }
+    for &i in &undo {
+        let step = &run.steps[i];
+        if run.mode == TransferMode::Move && !step.source.exists() {
+            println!(
+                "  move {} -> {}",
+                step.dest.display(),
+                step.source.display()
             );
+        } else {
+            println!("  remove {}", step.dest.display());
         }
+    }
 
-        // Write hash file for the destination
-        write_hash_file(&op.dest, &dest_hash)
-            .with_context(|| format!("Failed to write hash file for {:?}", op.dest))?;
+    if !no_dry_run {
+        println!();
+        println!("{}", "Dry run - nothing rolled back.".yellow());
+        println!(
+            "Run with {} to roll back.",
+            "--rollback --no-dry-run".cyan()
+        );
+        return Ok(());
+    }
 
-        println!("  {} {}", "✓".green(), op.dest.display());
+    let _locks = lock_libraries(&run.source, &run.dest, run.mode)?;
+    let cache = PendingEditsCache::new()?;
+    let store = BackupStore::open(&Config::load()?.backups)?;
+    let db = LibraryDb::find_from(&run.dest)?;
 
-        // Copy auxiliary files
-        for aux in &op.auxiliary {
-            // Create parent directories for auxiliary file
-            if let Some(parent) = aux.dest.parent() {
-                std::fs::create_dir_all(parent)
-                    .with_context(|| format!("Failed to create directory {:?}", parent))?;
+    println!();
+    let mut rolled_back = 0;
+    let mut failed = 0;
+    for i in undo {
+        let step = &run.steps[i];
+        match rollback_step(step, run.mode, &run.dest, &cache, &store) {
+            Ok(()) => {
+                println!("  {} {}", "\u{2713}".green(), step.dest.display());
+                if let Some(db) = db.as_ref().filter(|_| step.is_audiobook()) {
+                    if let Ok(relative) = step.dest.strip_prefix(db.base_path()) {
+                        db.remove_path(&relative.to_string_lossy())?;
+                    }
+                }
+                run.steps[i].status = StepStatus::Pending;
+                run.steps[i].used = None;
+                rolled_back += 1;
             }
-
-            // Skip if destination already exists
-            if aux.dest.exists() {
-                println!(
-                    "    {} {} (skipped, exists)",
-                    "○".yellow(),
-                    aux.dest.file_name().unwrap_or_default().to_string_lossy()
-                );
-                continue;
+            Err(e) => {
+                println!("  {} {}: {:#}", "\u{2717}".red(), step.dest.display(), e);
+                failed += 1;
             }
+        }
+    }
 
-            std::fs::copy(&aux.source, &aux.dest)
-                .with_context(|| format!("Failed to copy {:?} to {:?}", aux.source, aux.dest))?;
-
-            println!(
-                "    {} {}",
-                "+".cyan(),
-                aux.dest.file_name().unwrap_or_default().to_string_lossy()
-            );
-            aux_count += 1;
+    // Copies that never finished
+    for step in &run.steps {
+        let part = part_path(&step.dest);
+        if part.exists() {
+            std::fs::remove_file(&part).with_context(|| format!("Failed to remove {:?}", part))?;
+            cleanup_empty_dirs(&step.dest, &run.dest);
         }
     }
 
-    // Copy uncategorized files
-    if allow_uncategorized && !uncategorized.is_empty() {
-        let uncategorized_dir = dest.join("__uncategorized__");
-        std::fs::create_dir_all(&uncategorized_dir)
-            .with_context(|| format!("Failed to create {:?}", uncategorized_dir))?;
+    println!();
+    println!("Rolled back: {}, Failed: {}", rolled_back, failed);
+    if failed > 0 {
+        run.save()?;
+        bail!(
+            "{} file(s) could not be rolled back; fix them and run --rollback again",
+            failed
+        );
+    }
+    run.remove()
+}
 
-        for file in uncategorized {
-            let filename = file.source.file_name().context("File has no filename")?;
-            let dest_path = uncategorized_dir.join(filename);
+fn library_dest(dest_override: Option<&PathBuf>) -> Result<PathBuf> {
+    let config = Config::load().context("Failed to load config")?;
+    config
+        .dest(dest_override)
+        .context("No destination specified. Set [organize] dest in config or use --dest")
+}
+
+fn load_run(dest: &Path) -> Result<OrganizeRun> {
+    OrganizeRun::load(dest)?.with_context(|| format!("No interrupted organize run into {:?}", dest))
+}
+
+fn print_run_status(run: &OrganizeRun) {
+    println!(
+        "Interrupted {} run from {} into {} (started {}): {} of {} file(s) done.",
+        run.mode.verb(),
+        run.source.display(),
+        run.dest.display(),
+        run.started_at
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M"),
+        run.finished_count(),
+        run.steps.len()
+    );
+}
+
+/// Lock the library, and the source too when moving files out of it
+fn lock_libraries(source: &Path, dest: &Path, mode: TransferMode) -> Result<Vec<lockfile::Lock>> {
+    let mut locks = vec![lockfile::lock_library(dest)?];
+    if mode == TransferMode::Move && !absolute_path(source).starts_with(absolute_path(dest)) {
+        locks.push(lockfile::lock_library(source)?);
+    }
+    Ok(locks)
+}
+
+/// Counts for the summary of one invocation
+#[derive(Default)]
+struct RunStats {
+    pending_moved: usize,
+    backups_moved: usize,
+}
 
-            std::fs::copy(&file.source, &dest_path)
-                .with_context(|| format!("Failed to copy {:?} to {:?}", file.source, dest_path))?;
+/// Carry out a run's remaining steps, then update the database. The run is
+/// saved as each step starts and finishes, and removed once all is done.
+fn execute_run(run: &mut OrganizeRun) -> Result<()> {
+    println!();
+    println!("{}", format!("{} files...", run.mode.doing()).green());
 
-            // Write hash file for uncategorized
-            let hash = sha256_file(&dest_path)?;
-            write_hash_file(&dest_path, &hash)?;
+    let cache = PendingEditsCache::new()?;
+    let store = BackupStore::open(&Config::load()?.backups)?;
+    let mut stats = RunStats::default();
 
-            println!("  {} {} (uncategorized)", "✓".yellow(), dest_path.display());
+    run.save()?;
+    for i in 0..run.steps.len() {
+        if run.steps[i].is_finished() {
+            continue;
+        }
+        if let Err(e) = execute_step(run, i, &cache, &store, &mut stats) {
+            run.save()?;
+            eprintln!();
+            eprintln!(
+                "{} Run saved; use {} to continue it or {} to undo it.",
+                "Interrupted.".red().bold(),
+                "--resume".cyan(),
+                "--rollback".cyan()
+            );
+            return Err(e);
         }
     }
 
     // Write hash files for already-present files (they were compared but not copied)
-    if !already_present.is_empty() {
+    if !run.already_present.is_empty() {
         println!();
         println!("{}", "Writing hash files for existing audiobooks...".cyan());
-        for ap in already_present {
+        for ap in &run.already_present {
             // Only write if hash file doesn't already exist
             let hash_path = hash_file_path(&ap.dest);
             if !hash_path.exists() {
@@ -426,12 +537,18 @@ fn execute_plan(
         }
     }
 
+    let organized: Vec<&RunStep> = run
+        .steps
+        .iter()
+        .filter(|s| s.kind == StepKind::Audiobook && s.status == StepStatus::Done)
+        .collect();
+
     // Post-copy verification: check each destination directory has only the expected m4b
     println!();
     println!("{}", "Verifying copies...".cyan());
-    for op in operations {
-        if let Some(parent) = op.dest.parent() {
-            let expected_filename = op
+    for step in &organized {
+        if let Some(parent) = step.dest.parent() {
+            let expected_filename = step
                 .dest
                 .file_name()
                 .map(|f| f.to_string_lossy().to_string())
@@ -477,53 +594,399 @@ fn execute_plan(
     println!("  Verification complete");
 
     println!();
-    let total_m4b = operations.len()
-        + if allow_uncategorized {
-            uncategorized.len()
-        } else {
-            0
-        };
+    let total_m4b = run
+        .steps
+        .iter()
+        .filter(|s| s.is_audiobook() && s.status == StepStatus::Done)
+        .count();
+    let aux_count = run
+        .steps
+        .iter()
+        .filter(|s| s.kind == StepKind::Auxiliary && s.status == StepStatus::Done)
+        .count();
     if aux_count > 0 {
         println!(
-            "{} {} audiobook(s) + {} auxiliary file(s) copied.",
+            "{} {} audiobook(s) + {} auxiliary file(s) {}.",
             "Done!".green().bold(),
             total_m4b,
-            aux_count
+            aux_count,
+            run.mode.done()
         );
     } else {
-        println!("{} {} file(s) copied.", "Done!".green().bold(), total_m4b);
+        println!(
+            "{} {} file(s) {}.",
+            "Done!".green().bold(),
+            total_m4b,
+            run.mode.done()
+        );
+    }
+    if stats.pending_moved > 0 {
+        println!(
+            "{} pending edit(s) now target the organized files.",
+            stats.pending_moved
+        );
+    }
+    if stats.backups_moved > 0 {
+        println!(
+            "{} stored backup(s) moved with their files.",
+            stats.backups_moved
+        );
+    }
+    if run.mode == TransferMode::Move && !run.already_present.is_empty() {
+        println!(
+            "{} source file(s) already in the library were left in place.",
+            run.already_present.len()
+        );
     }
 
     // Update database
     println!();
     println!("{}", "Updating database...".cyan());
 
-    let mut db = LibraryDb::open(dest)?;
+    let mut db = LibraryDb::open(&run.dest)?;
     let mut db_count = 0;
 
     // Use transaction for batch updates
     db.begin_transaction()?;
 
-    for op in operations {
-        let metadata = file_metadata
-            .get(&op.source)
-            .with_context(|| format!("Missing metadata for {:?}", op.source))?;
-        let relative = op.dest.strip_prefix(dest).unwrap_or(&op.dest);
-        let file_size = std::fs::metadata(&op.dest)?.len() as i64;
-        let hash = sha256_file(&op.dest)?;
-        db.upsert(&relative.to_string_lossy(), file_size, &hash, metadata)?;
+    // Tags are read back from the library (a moved source is gone)
+    for step in &organized {
+        let metadata = read_metadata(&step.dest)?;
+        let relative = step.dest.strip_prefix(&run.dest).unwrap_or(&step.dest);
+        let file_size = std::fs::metadata(&step.dest)?.len() as i64;
+        // Written (from the verified transfer) by the step itself
+        let hash = get_hash(&step.dest, false)?;
+        db.upsert(&relative.to_string_lossy(), file_size, &hash, &metadata)?;
         db_count += 1;
     }
 
     // Touch already-present files to update their indexed_at timestamp
-    for ap in already_present {
-        let relative = ap.dest.strip_prefix(dest).unwrap_or(&ap.dest);
+    for ap in &run.already_present {
+        let relative = ap.dest.strip_prefix(&run.dest).unwrap_or(&ap.dest);
         db.touch(&relative.to_string_lossy())?;
         db_count += 1;
     }
 
+    // Remember which library files share data with their source
+    for step in run.steps.iter().filter(|s| s.is_audiobook()) {
+        if let Some(used @ (TransferMode::Hardlink | TransferMode::Reflink)) = step.used {
+            let relative = step.dest.strip_prefix(&run.dest).unwrap_or(&step.dest);
+            db.record_link(&relative.to_string_lossy(), &step.source, used.verb())?;
+        }
+    }
+
     db.commit()?;
     println!("  {} record(s) updated in database", db_count);
 
+    run.remove()
+}
+
+/// Carry out one step of a run
+fn execute_step(
+    run: &mut OrganizeRun,
+    index: usize,
+    cache: &PendingEditsCache,
+    store: &BackupStore,
+    stats: &mut RunStats,
+) -> Result<()> {
+    let mode = run.mode;
+    let step = run.steps[index].clone();
+    let name = step.dest.file_name().unwrap_or_default().to_string_lossy();
+
+    // Create parent directories
+    if let Some(parent) = step.dest.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory {:?}", parent))?;
+    }
+
+    // Left over from an interrupted copy
+    let part = part_path(&step.dest);
+    if part.exists() {
+        std::fs::remove_file(&part).with_context(|| format!("Failed to remove {:?}", part))?;
+    }
+
+    // An interrupted step whose file already arrived only needs finishing
+    if step.status == StepStatus::Started && landed(&step, mode)? {
+        let used = finish_landed(&step, mode, cache, store, stats)?;
+        println!(
+            "  {} {} (already {})",
+            "✓".green(),
+            step.dest.display(),
+            mode.done()
+        );
+        if mode == TransferMode::Move {
+            cleanup_empty_dirs(&step.source, &run.source);
+        }
+        return finish_step(run, index, StepStatus::Done, used);
+    }
+
+    // Skip auxiliary files that already exist
+    if step.kind == StepKind::Auxiliary && step.status == StepStatus::Pending && step.dest.exists()
+    {
+        println!("    {} {} (skipped, exists)", "○".yellow(), name);
+        return finish_step(run, index, StepStatus::Skipped, None);
+    }
+
+    run.steps[index].status = StepStatus::Started;
+    run.save()?;
+
+    let used = if step.is_audiobook() {
+        let _file_lock = lockfile::lock_file(&step.source)?;
+        let used = transfer_m4b(mode, &step.source, &step.dest)?;
+
+        if step.kind == StepKind::Uncategorized {
+            println!(
+                "  {} {} (uncategorized){}",
+                "✓".yellow(),
+                step.dest.display(),
+                fallback_note(mode, used)
+            );
+        } else {
+            println!(
+                "  {} {}{}",
+                "✓".green(),
+                step.dest.display(),
+                fallback_note(mode, used)
+            );
+        }
+
+        // Pending edits follow the organized file; stored backups follow a move
+        stats.pending_moved += cache.relocate(&step.source, &step.dest)?;
+        if mode == TransferMode::Move {
+            stats.backups_moved += store.relocate(&step.source, &step.dest)?;
+        }
+        used
+    } else {
+        let used = transfer_auxiliary(mode, &step.source, &step.dest)?;
+        println!("    {} {}", "+".cyan(), name);
+        used
+    };
+
+    if mode == TransferMode::Move {
+        cleanup_empty_dirs(&step.source, &run.source);
+    }
+    finish_step(run, index, StepStatus::Done, Some(used))
+}
+
+fn finish_step(
+    run: &mut OrganizeRun,
+    index: usize,
+    status: StepStatus,
+    used: Option<TransferMode>,
+) -> Result<()> {
+    run.steps[index].status = status;
+    run.steps[index].used = used;
+    run.save()
+}
+
+/// Whether an interrupted step's file already reached its destination
+fn landed(step: &RunStep, mode: TransferMode) -> Result<bool> {
+    if !step.dest.exists() {
+        return Ok(false);
+    }
+    if !step.source.exists() {
+        return Ok(mode == TransferMode::Move);
+    }
+    Ok(sha256_file(&step.source)? == sha256_file(&step.dest)?)
+}
+
+/// Do what an interrupted step left undone after its file arrived: remove a
+/// move's source (a copy across filesystems that was verified but not yet
+/// deleted), journal the transfer and write the `.sha256`, and point pending
+/// edits and stored backups at the new path.
+///
+/// Returns the mode used, or `None` when the files can't tell (a reflink
+/// looks like a copy).
+fn finish_landed(
+    step: &RunStep,
+    mode: TransferMode,
+    cache: &PendingEditsCache,
+    store: &BackupStore,
+    stats: &mut RunStats,
+) -> Result<Option<TransferMode>> {
+    let used = landed_mode(step, mode)?;
+
+    if mode == TransferMode::Move && step.source.exists() {
+        std::fs::remove_file(&step.source)
+            .with_context(|| format!("Failed to remove {:?}", step.source))?;
+    }
+
+    if !step.is_audiobook() {
+        match mode {
+            TransferMode::Move => journal::record_move(&step.source, &step.dest),
+            _ => journal::record_copy(&step.source, &step.dest, None),
+        }
+        return Ok(used);
+    }
+
+    // The .sha256 is written after the journal entry, so without it the
+    // transfer wasn't journaled yet
+    if !hash_file_path(&step.dest).exists() {
+        let hash = sha256_file(&step.dest)?;
+        if mode == TransferMode::Move {
+            journal::record_move(&step.source, &step.dest);
+            let stale = hash_file_path(&step.source);
+            if stale.exists() {
+                std::fs::remove_file(&stale)
+                    .with_context(|| format!("Failed to remove {:?}", stale))?;
+            }
+        } else {
+            journal::record_copy(&step.source, &step.dest, Some(&hash));
+        }
+        write_hash_file(&step.dest, &hash)?;
+    }
+
+    stats.pending_moved += cache.relocate(&step.source, &step.dest)?;
+    if mode == TransferMode::Move {
+        stats.backups_moved += store.relocate(&step.source, &step.dest)?;
+    }
+    Ok(used)
+}
+
+/// How a landed step's file got there, as far as the files can tell
+fn landed_mode(step: &RunStep, mode: TransferMode) -> Result<Option<TransferMode>> {
+    Ok(match mode {
+        TransferMode::Copy | TransferMode::Move => Some(mode),
+        TransferMode::Hardlink if same_file(&step.source, &step.dest)? => Some(mode),
+        TransferMode::Hardlink => Some(TransferMode::Copy),
+        TransferMode::Reflink => None,
+    })
+}
+
+/// Whether rolling back has anything to undo for a step
+fn needs_rollback(step: &RunStep) -> bool {
+    match step.status {
+        StepStatus::Done => true,
+        StepStatus::Started => step.dest.exists(),
+        StepStatus::Pending | StepStatus::Skipped => false,
+    }
+}
+
+/// Undo one step: move the file back to its source, or remove the library's
+/// copy (never the last copy of a file)
+fn rollback_step(
+    step: &RunStep,
+    mode: TransferMode,
+    root: &Path,
+    cache: &PendingEditsCache,
+    store: &BackupStore,
+) -> Result<()> {
+    if mode == TransferMode::Move && !step.source.exists() {
+        if let Some(parent) = step.source.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {:?}", parent))?;
+        }
+        move_file(&step.dest, &step.source, None)?;
+        journal::record_move(&step.dest, &step.source);
+        cache.relocate(&step.dest, &step.source)?;
+        store.relocate(&step.dest, &step.source)?;
+    } else if step.dest.exists() {
+        if !step.source.exists() {
+            bail!(
+                "the source {} is gone; keeping the library's copy",
+                step.source.display()
+            );
+        }
+        std::fs::remove_file(&step.dest)
+            .with_context(|| format!("Failed to remove {:?}", step.dest))?;
+        journal::record_remove(&step.dest);
+        cache.relocate(&step.dest, &step.source)?;
+    }
+
+    if step.is_audiobook() {
+        let hash_file = hash_file_path(&step.dest);
+        if hash_file.exists() {
+            std::fs::remove_file(&hash_file)
+                .with_context(|| format!("Failed to remove {:?}", hash_file))?;
+        }
+    }
+    cleanup_empty_dirs(&step.dest, root);
     Ok(())
 }
+
+/// Copy, move or link an auxiliary file. Returns the mode actually used.
+fn transfer_auxiliary(mode: TransferMode, source: &Path, dest: &Path) -> Result<TransferMode> {
+    match mode {
+        TransferMode::Copy => {
+            copy_file(source, dest, None)?;
+            journal::record_copy(source, dest, None);
+            Ok(mode)
+        }
+        TransferMode::Move => {
+            move_file(source, dest, None)?;
+            journal::record_move(source, dest);
+            Ok(mode)
+        }
+        TransferMode::Hardlink => {
+            let used = hardlink_file(source, dest, None)?;
+            journal::record_copy(source, dest, None);
+            Ok(used)
+        }
+        TransferMode::Reflink => {
+            let used = reflink_file(source, dest, None)?;
+            journal::record_copy(source, dest, None);
+            Ok(used)
+        }
+    }
+}
+
+/// Copy, move or link one audiobook into the library and write its
+/// `.sha256`. Returns the mode actually used (links fall back to copying).
+/// Copies are always hash-verified; moves are when they cross filesystems.
+fn transfer_m4b(mode: TransferMode, source: &Path, dest: &Path) -> Result<TransferMode> {
+    let source_hash =
+        || sha256_file(source).with_context(|| format!("Failed to hash source {:?}", source));
+
+    let (used, hash) = match mode {
+        TransferMode::Copy => {
+            let hash = source_hash()?;
+            copy_verified(source, dest, &hash)?;
+            journal::record_copy(source, dest, Some(&hash));
+            (mode, hash)
+        }
+        TransferMode::Move => {
+            // A rename doesn't read the file; it is hashed afterwards for its
+            // .sha256 (a move across filesystems verifies its copy anyway)
+            let hash = match move_file(source, dest, None)? {
+                Some(hash) => hash,
+                None => sha256_file(dest)
+                    .with_context(|| format!("Failed to hash destination {:?}", dest))?,
+            };
+            journal::record_move(source, dest);
+
+            // The source's cached hash would be left behind on its own
+            let stale = hash_file_path(source);
+            if stale.exists() {
+                std::fs::remove_file(&stale)
+                    .with_context(|| format!("Failed to remove {:?}", stale))?;
+            }
+            (mode, hash)
+        }
+        TransferMode::Hardlink | TransferMode::Reflink => {
+            let hash = source_hash()?;
+            let used = if mode == TransferMode::Hardlink {
+                hardlink_file(source, dest, Some(&hash))?
+            } else {
+                reflink_file(source, dest, Some(&hash))?
+            };
+            journal::record_copy(source, dest, Some(&hash));
+            (used, hash)
+        }
+    };
+
+    write_hash_file(dest, &hash)
+        .with_context(|| format!("Failed to write hash file for {:?}", dest))?;
+    Ok(used)
+}
+
+/// Suffix for files that were copied because the requested link wasn't possible
+fn fallback_note(requested: TransferMode, used: TransferMode) -> String {
+    if requested == used {
+        String::new()
+    } else {
+        format!(
+            " {}",
+            format!("(copied, can't {} here)", requested.verb()).yellow()
+        )
+    }
+}
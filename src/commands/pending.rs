@@ -1,9 +1,10 @@
 //! Pending command - manage pending edits
 
-use crate::editor::{compute_changes, format_diff, toml_to_metadata};
+use crate::database::LibraryDb;
+use crate::editor::{compute_changes, format_diff, three_way_merge, toml_to_metadata};
 use crate::metadata::read_metadata;
-use crate::metadata::write_metadata;
-use crate::safety::{create_backup, PendingEditsCache};
+use crate::metadata::{write_metadata, AudiobookMetadata};
+use crate::safety::{create_backup, PendingEdit, PendingEditsCache};
 use anyhow::{bail, Context, Result};
 use std::io::{self, Write};
 use std::path::Path;
@@ -33,6 +34,9 @@ pub fn list(show_diff: bool) -> Result<()> {
             if edit.original_path.exists() {
                 match show_diff_for_edit(&edit.original_path, &edit.toml_content) {
                     Ok(diff) => {
+                        if edit.has_drifted(&edit.original_path).unwrap_or(false) {
+                            println!("    (file changed since the edit was saved)");
+                        }
                         for line in diff.lines() {
                             println!("    {}", line);
                         }
@@ -58,9 +62,13 @@ pub fn list(show_diff: bool) -> Result<()> {
 pub fn show(file: &Path) -> Result<()> {
     let cache = PendingEditsCache::new()?;
 
+    cache.adopt_orphan(file)?;
     let pending = cache.load(file)?;
     match pending {
         Some(edit) => {
+            if edit.has_drifted(&edit.original_path)? {
+                println!("Note: file changed since the edit was saved; applying will merge");
+            }
             let diff = show_diff_for_edit(&edit.original_path, &edit.toml_content)?;
             println!("{}", diff);
         }
@@ -105,6 +113,77 @@ pub fn clear(file: Option<&Path>) -> Result<()> {
     Ok(())
 }
 
+/// Find pending edits whose file is gone; re-target them through the library
+/// index (by content hash) or delete them
+pub fn gc(library: &Path, no_dry_run: bool) -> Result<()> {
+    let cache = PendingEditsCache::new()?;
+    let db = LibraryDb::find_from(library)?;
+
+    let mut relocated = 0;
+    let mut orphaned = 0;
+
+    for edit in cache.entries()? {
+        let has_path = !edit.original_path.as_os_str().is_empty();
+        if has_path && edit.original_path.exists() {
+            continue;
+        }
+
+        let label = if has_path {
+            edit.original_path.display().to_string()
+        } else {
+            edit.cache_file.display().to_string()
+        };
+
+        // The index knows where a file with this content lives now
+        let found = match (&db, &edit.content_hash) {
+            (Some(db), Some(hash)) => db
+                .get_by_hash(hash)?
+                .into_iter()
+                .map(|record| db.base_path().join(&record.file_path))
+                .find(|path| path.exists()),
+            _ => None,
+        };
+
+        match found {
+            Some(path) => {
+                let path = path.canonicalize()?;
+                println!("  \u{2713} {} -> {}", label, path.display());
+                if no_dry_run {
+                    cache.retarget(edit, &path)?;
+                }
+                relocated += 1;
+            }
+            None => {
+                println!("  \u{2717} {} (file not found)", label);
+                if no_dry_run {
+                    cache.remove(&edit)?;
+                }
+                orphaned += 1;
+            }
+        }
+    }
+
+    if relocated == 0 && orphaned == 0 {
+        println!("All pending edits point at existing files.");
+        return Ok(());
+    }
+
+    println!();
+    if no_dry_run {
+        println!("Re-targeted: {}, Removed: {}", relocated, orphaned);
+    } else {
+        println!(
+            "{} edit(s) to re-target, {} to remove (run with --no-dry-run to apply)",
+            relocated, orphaned
+        );
+        if db.is_none() {
+            println!("No library index found; run from inside an indexed library to re-target moved files");
+        }
+    }
+
+    Ok(())
+}
+
 fn show_diff_for_edit(file: &Path, toml_content: &str) -> Result<String> {
     let original_metadata = read_metadata(file)?;
     let new_metadata = toml_to_metadata(toml_content)?;
@@ -113,15 +192,22 @@ fn show_diff_for_edit(file: &Path, toml_content: &str) -> Result<String> {
 }
 
 fn apply_single(cache: &PendingEditsCache, file: &Path, yes: bool, no_backup: bool) -> Result<()> {
+    cache.adopt_orphan(file)?;
     let pending = cache.load(file)?;
     let edit = match pending {
         Some(e) => e,
         None => bail!("No pending edit found for: {}", file.display()),
     };
 
-    // Show diff
-    let diff = show_diff_for_edit(file, &edit.toml_content)?;
-    println!("{}", diff);
+    // Merge with newer changes to the file, then show what will be written
+    let new_metadata = resolve_edit(&edit, file, !yes)?;
+    let changes = compute_changes(&read_metadata(file)?, &new_metadata);
+    println!("{}", format_diff(&file.display().to_string(), &changes));
+
+    if changes.is_empty() {
+        cache.clear(file)?;
+        return Ok(());
+    }
 
     // Confirm
     if !yes {
@@ -137,9 +223,6 @@ fn apply_single(cache: &PendingEditsCache, file: &Path, yes: bool, no_backup: bo
         }
     }
 
-    // Apply
-    let new_metadata = toml_to_metadata(&edit.toml_content)?;
-
     if !no_backup {
         let backup_path = create_backup(file)?;
         println!("Created backup: {}", backup_path.display());
@@ -193,7 +276,7 @@ fn apply_all(cache: &PendingEditsCache, yes: bool, no_backup: bool) -> Result<()
     let mut failed = 0;
 
     for edit in &edits {
-        let result = apply_edit(cache, &edit.original_path, &edit.toml_content, no_backup);
+        let result = apply_edit(cache, edit, !yes, no_backup);
         match result {
             Ok(()) => {
                 println!("  \u{2713} {}", edit.original_path.display());
@@ -214,15 +297,16 @@ fn apply_all(cache: &PendingEditsCache, yes: bool, no_backup: bool) -> Result<()
 
 fn apply_edit(
     cache: &PendingEditsCache,
-    file: &Path,
-    toml_content: &str,
+    edit: &PendingEdit,
+    interactive: bool,
     no_backup: bool,
 ) -> Result<()> {
+    let file = edit.original_path.as_path();
     if !file.exists() {
         bail!("file not found");
     }
 
-    let new_metadata = toml_to_metadata(toml_content).context("invalid TOML")?;
+    let new_metadata = resolve_edit(edit, file, interactive)?;
 
     if !no_backup {
         create_backup(file)?;
@@ -233,3 +317,60 @@ fn apply_edit(
 
     Ok(())
 }
+
+/// The metadata to write for a pending edit.
+///
+/// If the file changed since the edit was saved, the edit is three-way merged
+/// with the file's current tags (base = metadata when the edit was saved).
+/// Conflicting fields are resolved by prompting, or fail when not interactive.
+pub fn resolve_edit(
+    edit: &PendingEdit,
+    file: &Path,
+    interactive: bool,
+) -> Result<AudiobookMetadata> {
+    let ours = toml_to_metadata(&edit.toml_content).context("invalid TOML")?;
+
+    let Some(base) = &edit.original else {
+        return Ok(ours);
+    };
+    if !edit.has_drifted(file)? {
+        return Ok(ours);
+    }
+
+    println!(
+        "{} changed since the edit was saved; merging with its current tags",
+        file.display()
+    );
+    let current = read_metadata(file)?;
+    let mut merge = three_way_merge(base, &ours, &current);
+
+    if merge.conflicts.is_empty() {
+        return Ok(merge.merged);
+    }
+
+    if !interactive {
+        let fields: Vec<&str> = merge.conflicts.iter().map(|c| c.field).collect();
+        bail!(
+            "conflicting changes to {}; resolve with: audiobookctl pending apply \"{}\"",
+            fields.join(", "),
+            file.display()
+        );
+    }
+
+    let display = |value: &Option<String>| value.clone().unwrap_or_else(|| "(empty)".to_string());
+    for conflict in merge.conflicts.clone() {
+        println!("  {} conflict:", conflict.field);
+        println!("    pending: {}", display(&conflict.ours));
+        println!("    file:    {}", display(&conflict.theirs));
+        print!("  Keep [p]ending or [f]ile value? [P/f] ");
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if input.trim().eq_ignore_ascii_case("f") || input.trim().eq_ignore_ascii_case("file") {
+            merge.take_theirs(&conflict);
+        }
+    }
+
+    Ok(merge.merged)
+}
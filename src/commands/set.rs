@@ -0,0 +1,183 @@
+//! Set command - change fields without an editor
+
+use crate::commands::edit::save_or_apply;
+use crate::editor::{compute_changes, format_diff};
+use crate::metadata::{read_metadata, validate_field_names, AudiobookMetadata};
+use crate::names::NameRegistry;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Fields in `show --json` output that can't be set (ignored so it can be piped back in)
+const READ_ONLY_FIELDS: &[&str] = &["duration_seconds", "chapter_count", "cover_info"];
+
+/// Set fields on one or more files
+pub fn run(
+    files: &[PathBuf],
+    assignments: &[String],
+    from_json: Option<&Path>,
+    from_toml: Option<&Path>,
+    no_dry_run: bool,
+    yes: bool,
+    no_backup: bool,
+) -> Result<()> {
+    // Fields from a file come first so --field can override them
+    let mut values: Vec<(String, Option<String>)> = Vec::new();
+    if let Some(path) = from_json {
+        values.extend(fields_from_json(&read_source(path)?)?);
+    }
+    if let Some(path) = from_toml {
+        values.extend(fields_from_toml(&read_source(path)?)?);
+    }
+    for assignment in assignments {
+        values.push(parse_assignment(assignment)?);
+    }
+
+    if values.is_empty() {
+        bail!("Nothing to set. Use --field FIELD=VALUE, --from-json or --from-toml");
+    }
+    let names: Vec<String> = values.iter().map(|(name, _)| name.clone()).collect();
+    validate_field_names(&names)?;
+
+    let registry = NameRegistry::load()?;
+    let mut planned: Vec<(PathBuf, AudiobookMetadata)> = Vec::new();
+
+    for file in files {
+        let original = read_metadata(file)?;
+        let mut new_metadata = original.clone();
+        for (field, value) in &values {
+            new_metadata
+                .set_field(field, value.as_deref())
+                .with_context(|| file.display().to_string())?;
+        }
+        registry.apply(&mut new_metadata);
+
+        let changes = compute_changes(&original, &new_metadata);
+        println!("{}", format_diff(&file.display().to_string(), &changes));
+        if !changes.is_empty() {
+            planned.push((file.clone(), new_metadata));
+        }
+    }
+
+    if files.len() > 1 {
+        println!();
+        println!("{} of {} files changed", planned.len(), files.len());
+    }
+
+    save_or_apply(&planned, no_dry_run, yes, no_backup)
+}
+
+/// Parse `FIELD=VALUE`; an empty value clears the field
+fn parse_assignment(assignment: &str) -> Result<(String, Option<String>)> {
+    let (field, value) = assignment
+        .split_once('=')
+        .ok_or_else(|| anyhow::anyhow!("Expected FIELD=VALUE, got \"{}\"", assignment))?;
+    Ok((field.trim().to_string(), Some(value.to_string())))
+}
+
+/// Read a file, or stdin for "-"
+fn read_source(path: &Path) -> Result<String> {
+    if path == Path::new("-") {
+        let mut content = String::new();
+        io::stdin()
+            .read_to_string(&mut content)
+            .context("Failed to read stdin")?;
+        Ok(content)
+    } else {
+        fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))
+    }
+}
+
+/// Fields from a JSON object; `null` clears a field
+fn fields_from_json(content: &str) -> Result<Vec<(String, Option<String>)>> {
+    let value: serde_json::Value = serde_json::from_str(content).context("Failed to parse JSON")?;
+    let object = value
+        .as_object()
+        .ok_or_else(|| anyhow::anyhow!("JSON input must be an object"))?;
+
+    let mut fields = Vec::new();
+    for (key, value) in object {
+        if READ_ONLY_FIELDS.contains(&key.as_str()) {
+            continue;
+        }
+        let text = match value {
+            serde_json::Value::Null => None,
+            serde_json::Value::String(s) => Some(s.clone()),
+            serde_json::Value::Number(n) => Some(n.to_string()),
+            _ => bail!("{} must be a string, a number or null", key),
+        };
+        fields.push((key.clone(), text));
+    }
+    Ok(fields)
+}
+
+/// Fields from a TOML table (same keys as the `edit` document); `""` clears a field
+fn fields_from_toml(content: &str) -> Result<Vec<(String, Option<String>)>> {
+    let value: toml::Value = toml::from_str(content).context("Failed to parse TOML")?;
+    let table = value
+        .as_table()
+        .ok_or_else(|| anyhow::anyhow!("Invalid TOML structure"))?;
+
+    let mut fields = Vec::new();
+    for (key, value) in table {
+        if READ_ONLY_FIELDS.contains(&key.as_str()) {
+            continue;
+        }
+        let text = match value {
+            toml::Value::String(s) => s.clone(),
+            toml::Value::Integer(n) => n.to_string(),
+            _ => bail!("{} must be a string or a number", key),
+        };
+        fields.push((key.clone(), Some(text)));
+    }
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_assignment() {
+        assert_eq!(
+            parse_assignment("series=The Expanse").unwrap(),
+            ("series".to_string(), Some("The Expanse".to_string()))
+        );
+        assert_eq!(
+            parse_assignment("description=a=b").unwrap(),
+            ("description".to_string(), Some("a=b".to_string()))
+        );
+        assert!(parse_assignment("series").is_err());
+    }
+
+    #[test]
+    fn test_fields_from_json_accepts_show_output() {
+        let metadata = AudiobookMetadata {
+            title: Some("Leviathan Wakes".to_string()),
+            series_position: Some(1),
+            duration_seconds: Some(3600),
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&metadata).unwrap();
+        let fields = fields_from_json(&json).unwrap();
+
+        assert!(fields.contains(&("title".to_string(), Some("Leviathan Wakes".to_string()))));
+        assert!(fields.contains(&("series_position".to_string(), Some("1".to_string()))));
+        assert!(fields.contains(&("narrator".to_string(), None)));
+        assert!(!fields.iter().any(|(k, _)| k == "duration_seconds"));
+    }
+
+    #[test]
+    fn test_fields_from_toml() {
+        let fields = fields_from_toml("series = \"The Expanse\"\nseries_position = 3\n").unwrap();
+        assert_eq!(
+            fields,
+            vec![
+                ("series".to_string(), Some("The Expanse".to_string())),
+                ("series_position".to_string(), Some("3".to_string())),
+            ]
+        );
+        assert!(fields_from_toml("series = true").is_err());
+    }
+}
@@ -0,0 +1,239 @@
+//! CSV export/import - bulk metadata editing in a spreadsheet
+//!
+//! `export-csv` writes one row per audiobook: its path, the SHA256 of the
+//! file at export time, and every editable field. `import-csv` reads the
+//! edited sheet back, rejects rows whose file changed since the export, and
+//! turns the rest into per-file changes.
+
+use crate::commands::edit::save_or_apply;
+use crate::editor::{compute_changes, format_diff};
+use crate::hash::sha256_file;
+use crate::metadata::{read_metadata, AudiobookMetadata, EDITABLE_FIELDS};
+use crate::organize::scan_directory;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const PATH_COLUMN: &str = "path";
+const HASH_COLUMN: &str = "sha256";
+
+/// Export every audiobook under `dir` to CSV (stdout without `output`)
+pub fn export(dir: &Path, output: Option<&Path>) -> Result<()> {
+    let mut files = scan_directory(dir)?;
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let rows = files
+        .iter()
+        .map(|file| Ok((file.path.clone(), sha256_file(&file.path)?, &file.metadata)))
+        .collect::<Result<Vec<_>>>()?;
+
+    match output {
+        Some(path) => {
+            let file =
+                fs::File::create(path).with_context(|| format!("Failed to create {:?}", path))?;
+            write_csv(file, &rows)?;
+            println!("Exported {} audiobooks to {}", rows.len(), path.display());
+        }
+        None => write_csv(io::stdout(), &rows)?,
+    }
+
+    Ok(())
+}
+
+/// Import an edited CSV as pending edits (or apply it with `no_dry_run`)
+pub fn import(csv_path: &Path, no_dry_run: bool, yes: bool, no_backup: bool) -> Result<()> {
+    let content =
+        fs::read_to_string(csv_path).with_context(|| format!("Failed to read {:?}", csv_path))?;
+    let rows = parse_csv(&content).with_context(|| format!("Failed to parse {:?}", csv_path))?;
+
+    let mut planned: Vec<(PathBuf, AudiobookMetadata)> = Vec::new();
+    let mut unchanged = 0;
+    let mut rejected = 0;
+
+    for row in &rows {
+        match plan_row(row) {
+            Ok(Some(new_metadata)) => planned.push((row.path.clone(), new_metadata)),
+            Ok(None) => unchanged += 1,
+            Err(e) => {
+                println!("\u{2717} {} ({})", row.path.display(), e);
+                rejected += 1;
+            }
+        }
+    }
+
+    println!();
+    println!(
+        "{} files to update, {} without changes, {} rejected",
+        planned.len(),
+        unchanged,
+        rejected
+    );
+
+    save_or_apply(&planned, no_dry_run, yes, no_backup)
+}
+
+/// One imported row: the file, its hash at export time and the field cells present
+#[derive(Debug, PartialEq)]
+struct CsvRow {
+    path: PathBuf,
+    sha256: String,
+    fields: Vec<(String, String)>,
+}
+
+/// Check a row against the file and print its diff; `None` if nothing changes
+fn plan_row(row: &CsvRow) -> Result<Option<AudiobookMetadata>> {
+    if row.sha256.is_empty() {
+        bail!("no sha256 recorded");
+    }
+    if sha256_file(&row.path)? != row.sha256 {
+        bail!("file changed since export");
+    }
+
+    let current = read_metadata(&row.path)?;
+    let mut new_metadata = current.clone();
+    for (field, value) in &row.fields {
+        new_metadata.set_field(field, Some(value))?;
+    }
+
+    let changes = compute_changes(&current, &new_metadata);
+    if changes.is_empty() {
+        return Ok(None);
+    }
+    println!("{}", format_diff(&row.path.display().to_string(), &changes));
+    Ok(Some(new_metadata))
+}
+
+fn write_csv<W: io::Write>(out: W, rows: &[(PathBuf, String, &AudiobookMetadata)]) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+
+    let mut header = vec![PATH_COLUMN, HASH_COLUMN];
+    header.extend_from_slice(EDITABLE_FIELDS);
+    writer.write_record(&header)?;
+
+    for (path, hash, metadata) in rows {
+        let mut record = vec![path.to_string_lossy().into_owned(), hash.clone()];
+        record.extend(
+            EDITABLE_FIELDS
+                .iter()
+                .map(|field| metadata.get_field(field).unwrap_or_default()),
+        );
+        writer.write_record(&record)?;
+    }
+
+    writer.flush().context("Failed to write CSV")?;
+    Ok(())
+}
+
+/// Parse an exported sheet; field columns may be removed or reordered
+fn parse_csv(content: &str) -> Result<Vec<CsvRow>> {
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let headers = reader.headers()?.clone();
+
+    let mut path_col = None;
+    let mut hash_col = None;
+    let mut field_cols = Vec::new();
+    for (i, header) in headers.iter().enumerate() {
+        match header.trim() {
+            PATH_COLUMN => path_col = Some(i),
+            HASH_COLUMN => hash_col = Some(i),
+            field if EDITABLE_FIELDS.contains(&field) => field_cols.push((i, field.to_string())),
+            other => bail!(
+                "Unknown column \"{}\" (editable fields: {})",
+                other,
+                EDITABLE_FIELDS.join(", ")
+            ),
+        }
+    }
+    let path_col = path_col.context("Missing column \"path\"")?;
+    let hash_col = hash_col.context("Missing column \"sha256\"")?;
+
+    let mut rows: Vec<CsvRow> = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record?;
+        let get = |col: usize| record.get(col).unwrap_or("").trim();
+
+        let path = get(path_col);
+        if path.is_empty() {
+            bail!("row {}: missing path", i + 2);
+        }
+        let path = PathBuf::from(path);
+        if rows.iter().any(|r| r.path == path) {
+            bail!("row {}: {} appears more than once", i + 2, path.display());
+        }
+
+        rows.push(CsvRow {
+            path,
+            sha256: get(hash_col).to_ascii_lowercase(),
+            fields: field_cols
+                .iter()
+                .map(|(col, field)| (field.clone(), get(*col).to_string()))
+                .collect(),
+        });
+    }
+
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_round_trip() {
+        let metadata = AudiobookMetadata {
+            title: Some("Leviathan Wakes".to_string()),
+            series: Some("The Expanse, Book 1".to_string()),
+            series_position: Some(1),
+            description: Some("Line one\n\"quoted\" line two".to_string()),
+            ..Default::default()
+        };
+        let rows = vec![(PathBuf::from("/lib/a.m4b"), "ABC123".to_string(), &metadata)];
+
+        let mut out = Vec::new();
+        write_csv(&mut out, &rows).unwrap();
+        let content = String::from_utf8(out).unwrap();
+        assert!(content.starts_with("path,sha256,title,author,"));
+
+        let parsed = parse_csv(&content).unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].path, PathBuf::from("/lib/a.m4b"));
+        assert_eq!(parsed[0].sha256, "abc123");
+
+        let mut rebuilt = AudiobookMetadata::default();
+        for (field, value) in &parsed[0].fields {
+            rebuilt.set_field(field, Some(value)).unwrap();
+        }
+        assert_eq!(rebuilt, metadata);
+    }
+
+    #[test]
+    fn test_parse_csv_with_subset_of_columns() {
+        let content = "sha256,path,series\nabc,/lib/a.m4b,The Expanse\n";
+        let rows = parse_csv(content).unwrap();
+        assert_eq!(
+            rows,
+            vec![CsvRow {
+                path: PathBuf::from("/lib/a.m4b"),
+                sha256: "abc".to_string(),
+                fields: vec![("series".to_string(), "The Expanse".to_string())],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_csv_errors() {
+        assert!(parse_csv("path,series\n/lib/a.m4b,x\n")
+            .unwrap_err()
+            .to_string()
+            .contains("sha256"));
+        assert!(parse_csv("path,sha256,narator\n/lib/a.m4b,abc,x\n")
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown column \"narator\""));
+        assert!(parse_csv("path,sha256\n/lib/a.m4b,abc\n/lib/a.m4b,abc\n")
+            .unwrap_err()
+            .to_string()
+            .contains("more than once"));
+    }
+}
@@ -0,0 +1,230 @@
+//! Undo command - revert journaled metadata changes and file moves
+
+use crate::config::Config;
+use crate::hash::{hash_file_path, sha256_file};
+use crate::journal::{self, FieldEdit, Journal, JournalEntry, Operation};
+use crate::metadata::{read_metadata, write_metadata};
+use crate::organize::transfer::move_file;
+use crate::safety::{create_backup, BackupStore, PendingEditsCache};
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::PathBuf;
+
+/// How to revert one journal entry
+#[derive(Debug)]
+enum Revert {
+    /// Restore fields to their earlier values
+    Metadata {
+        file: PathBuf,
+        restore: Vec<FieldEdit>,
+        /// Fields changed again since the entry (left alone)
+        skipped: Vec<String>,
+    },
+    /// Move a file back
+    Move { from: PathBuf, to: PathBuf },
+    /// Delete a copy (the original still exists)
+    RemoveCopy { path: PathBuf, original: PathBuf },
+}
+
+/// Revert an entry, or every entry of a run
+pub fn run(id: u64, whole_run: bool, no_dry_run: bool, no_backup: bool) -> Result<()> {
+    let journal = Journal::open()?;
+    let entries = journal.entries()?;
+
+    let targets: Vec<&JournalEntry> = if whole_run {
+        let run: Vec<&JournalEntry> = entries
+            .iter()
+            .filter(|e| e.run == id && !matches!(e.operation, Operation::Undo { .. }))
+            .rev()
+            .collect();
+        if run.is_empty() {
+            bail!("No journal run #{}", id);
+        }
+        run
+    } else {
+        match entries.iter().find(|e| e.id == id) {
+            Some(entry) => vec![entry],
+            None => bail!("No journal entry #{}", id),
+        }
+    };
+
+    let mut planned = Vec::new();
+    let mut failed = 0;
+
+    for entry in targets {
+        if Journal::is_undone(&entries, entry.id) {
+            println!("#{} already undone", entry.id);
+            continue;
+        }
+        match plan(entry, &entries) {
+            Ok(revert) => {
+                print_revert(entry.id, &revert);
+                planned.push((entry.id, revert));
+            }
+            Err(e) => {
+                println!("\u{2717} #{} {} ({})", entry.id, entry.summary(), e);
+                failed += 1;
+            }
+        }
+    }
+
+    if planned.is_empty() {
+        if failed > 0 {
+            bail!("Nothing could be undone");
+        }
+        return Ok(());
+    }
+
+    if !no_dry_run {
+        println!();
+        println!("Dry run - nothing changed. Run with --no-dry-run to undo.");
+        return Ok(());
+    }
+
+    println!();
+    let mut undone = 0;
+    for (id, revert) in &planned {
+        match apply(revert, no_backup) {
+            Ok(()) => {
+                journal::record(Operation::Undo { undone: *id });
+                println!("  \u{2713} #{}", id);
+                undone += 1;
+            }
+            Err(e) => {
+                println!("  \u{2717} #{} ({})", id, e);
+                failed += 1;
+            }
+        }
+    }
+
+    println!();
+    println!("Undone: {}, Failed: {}", undone, failed);
+
+    Ok(())
+}
+
+/// Work out how to revert an entry, checking the files are where the journal expects
+fn plan(entry: &JournalEntry, entries: &[JournalEntry]) -> Result<Revert> {
+    match &entry.operation {
+        Operation::Metadata { file, changes } => {
+            let file = Journal::current_path(entries, entry.id, file);
+            if !file.exists() {
+                bail!("{} no longer exists", file.display());
+            }
+            let current = read_metadata(&file)?;
+
+            let (restore, changed): (Vec<FieldEdit>, Vec<FieldEdit>) = changes
+                .iter()
+                .cloned()
+                .partition(|c| current.get_field(&c.field) == c.after);
+            if restore.is_empty() {
+                bail!("every field was changed again since");
+            }
+
+            Ok(Revert::Metadata {
+                file,
+                restore,
+                skipped: changed.into_iter().map(|c| c.field).collect(),
+            })
+        }
+        Operation::Move { from, to } => {
+            let current = Journal::current_path(entries, entry.id, to);
+            if !current.exists() {
+                bail!("{} no longer exists", current.display());
+            }
+            if from.exists() {
+                bail!("{} already exists", from.display());
+            }
+            Ok(Revert::Move {
+                from: current,
+                to: from.clone(),
+            })
+        }
+        Operation::Copy { from, to, sha256 } => {
+            let copy = Journal::current_path(entries, entry.id, to);
+            if !copy.exists() {
+                bail!("{} no longer exists", copy.display());
+            }
+            if !from.exists() {
+                bail!("the original {} is gone; keeping the copy", from.display());
+            }
+            if let Some(hash) = sha256 {
+                if sha256_file(&copy)? != *hash {
+                    bail!("{} changed since it was copied", copy.display());
+                }
+            }
+            Ok(Revert::RemoveCopy {
+                path: copy,
+                original: from.clone(),
+            })
+        }
+        Operation::Remove { .. } => bail!("deleted files can't be restored"),
+        Operation::Undo { .. } => bail!("undo markers can't be undone; undo the entries it wrote"),
+    }
+}
+
+fn print_revert(id: u64, revert: &Revert) {
+    match revert {
+        Revert::Metadata {
+            file,
+            restore,
+            skipped,
+        } => {
+            println!("#{} restore tags on {}", id, file.display());
+            for change in restore {
+                println!(
+                    "    {}: {} -> {}",
+                    change.field,
+                    change.after.as_deref().unwrap_or("(empty)"),
+                    change.before.as_deref().unwrap_or("(empty)")
+                );
+            }
+            if !skipped.is_empty() {
+                println!("    (changed since, left alone: {})", skipped.join(", "));
+            }
+        }
+        Revert::Move { from, to } => {
+            println!("#{} move {} -> {}", id, from.display(), to.display());
+        }
+        Revert::RemoveCopy { path, .. } => {
+            println!("#{} remove copy {}", id, path.display());
+        }
+    }
+}
+
+fn apply(revert: &Revert, no_backup: bool) -> Result<()> {
+    match revert {
+        Revert::Metadata { file, restore, .. } => {
+            let mut metadata = read_metadata(file)?;
+            for change in restore {
+                metadata.set_field(&change.field, change.before.as_deref())?;
+            }
+            if !no_backup {
+                create_backup(file)?;
+            }
+            write_metadata(file, &metadata)
+        }
+        Revert::Move { from, to } => {
+            if let Some(parent) = to.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create directory {:?}", parent))?;
+            }
+            move_file(from, to, None)?;
+            journal::record_move(from, to);
+            PendingEditsCache::new()?.relocate(from, to)?;
+            BackupStore::open(&Config::load()?.backups)?.relocate(from, to)?;
+            Ok(())
+        }
+        Revert::RemoveCopy { path, original } => {
+            fs::remove_file(path).with_context(|| format!("Failed to remove {:?}", path))?;
+            journal::record_remove(path);
+            let hash_file = hash_file_path(path);
+            if hash_file.exists() {
+                fs::remove_file(&hash_file)
+                    .with_context(|| format!("Failed to remove {:?}", hash_file))?;
+            }
+            PendingEditsCache::new()?.relocate(path, original)?;
+            Ok(())
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 /// Application configuration loaded from ~/.config/audiobookctl/config.toml
@@ -9,6 +10,10 @@ pub struct Config {
     pub organize: OrganizeConfig,
     #[serde(default)]
     pub backups: BackupsConfig,
+    #[serde(default)]
+    pub lookup: LookupConfig,
+    #[serde(default)]
+    pub genres: GenresConfig,
 }
 
 /// Configuration for the organize and fix commands
@@ -28,16 +33,147 @@ pub struct BackupsConfig {
     /// Maximum storage allowed for backups in bytes (default: 2GB)
     #[serde(default = "default_max_storage")]
     pub max_storage_bytes: u64,
+
+    /// What a backup contains (default: metadata)
+    #[serde(default)]
+    pub mode: BackupMode,
+
+    /// Where backups are kept (default: alongside the file)
+    #[serde(default)]
+    pub location: BackupLocation,
+
+    /// Central store directory (default: ~/.local/share/audiobookctl/backups)
+    pub store_dir: Option<PathBuf>,
+
+    /// Generations kept per file in the central store (default: 5)
+    #[serde(default = "default_keep_generations")]
+    pub keep_generations: usize,
+
+    /// Prune stored backups older than this many days (default: never)
+    pub max_age_days: Option<u64>,
+}
+
+/// Where `create_backup` puts backups
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupLocation {
+    /// Next to the file (`book.m4b.bak` / `book.m4b.meta.bak`)
+    #[default]
+    Alongside,
+    /// In the central content-addressed store, with several generations per file
+    Store,
+}
+
+/// What `create_backup` saves before a file is modified
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupMode {
+    /// Only the tag atoms (`book.m4b.meta.bak`, usually a few KB)
+    #[default]
+    Metadata,
+    /// A copy of the whole file (`book.m4b.bak`)
+    Full,
 }
 
 fn default_max_storage() -> u64 {
     2 * 1024 * 1024 * 1024 // 2GB
 }
 
+fn default_keep_generations() -> usize {
+    5
+}
+
 impl Default for BackupsConfig {
     fn default() -> Self {
         Self {
             max_storage_bytes: default_max_storage(),
+            mode: BackupMode::default(),
+            location: BackupLocation::default(),
+            store_dir: None,
+            keep_generations: default_keep_generations(),
+            max_age_days: None,
+        }
+    }
+}
+
+/// Configuration for the lookup and lookup-all commands
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LookupConfig {
+    /// Number of books queried concurrently by lookup-all (default: 4)
+    #[serde(default = "default_jobs")]
+    pub jobs: usize,
+
+    /// Fields lookups never change in any file (e.g. ["title", "genre"])
+    #[serde(default)]
+    pub locked_fields: Vec<String>,
+}
+
+fn default_jobs() -> usize {
+    4
+}
+
+impl Default for LookupConfig {
+    fn default() -> Self {
+        Self {
+            jobs: default_jobs(),
+            locked_fields: Vec::new(),
+        }
+    }
+}
+
+/// Genre mapping applied to lookup results before merging
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenresConfig {
+    /// Source genre -> one or more genres (an empty list drops the genre)
+    /// Example: "Science Fiction & Fantasy" = ["Science Fiction", "Fantasy"]
+    #[serde(default)]
+    pub map: BTreeMap<String, GenreTarget>,
+
+    /// Only these genres are kept, with this spelling (empty: keep all)
+    #[serde(default)]
+    pub whitelist: Vec<String>,
+
+    /// Maximum number of genres written to the tag (default: 1)
+    #[serde(default = "default_max_genres")]
+    pub max: usize,
+
+    /// Separator between multiple genres in the tag (default: "; ")
+    #[serde(default = "default_genre_separator")]
+    pub separator: String,
+}
+
+/// A mapped genre: a single name or a list
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum GenreTarget {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl GenreTarget {
+    pub fn genres(&self) -> &[String] {
+        match self {
+            GenreTarget::One(genre) => std::slice::from_ref(genre),
+            GenreTarget::Many(genres) => genres,
+        }
+    }
+}
+
+fn default_max_genres() -> usize {
+    1
+}
+
+fn default_genre_separator() -> String {
+    "; ".to_string()
+}
+
+impl Default for GenresConfig {
+    fn default() -> Self {
+        Self {
+            map: BTreeMap::new(),
+            whitelist: Vec::new(),
+            max: default_max_genres(),
+            separator: default_genre_separator(),
         }
     }
 }
@@ -127,6 +263,8 @@ dest = "/home/user/audiobooks"
                 dest: Some(PathBuf::from("/default/path")),
             },
             backups: BackupsConfig::default(),
+            lookup: LookupConfig::default(),
+            genres: GenresConfig::default(),
         };
 
         // CLI override takes precedence
@@ -148,6 +286,10 @@ dest = "/home/user/audiobooks"
     fn test_backups_config_defaults() {
         let config = Config::default();
         assert_eq!(config.backups.max_storage_bytes, 2 * 1024 * 1024 * 1024);
+        assert_eq!(config.backups.mode, BackupMode::Metadata);
+        assert_eq!(config.backups.location, BackupLocation::Alongside);
+        assert_eq!(config.backups.keep_generations, 5);
+        assert_eq!(config.backups.max_age_days, None);
     }
 
     #[test]
@@ -159,11 +301,80 @@ dest = "/home/user/audiobooks"
             r#"
 [backups]
 max_storage_bytes = 1073741824
+mode = "full"
+location = "store"
+store_dir = "/mnt/backups/audiobooks"
+keep_generations = 3
+max_age_days = 90
 "#,
         )
         .unwrap();
 
         let config = Config::load_from(&path).unwrap();
         assert_eq!(config.backups.max_storage_bytes, 1024 * 1024 * 1024); // 1GB
+        assert_eq!(config.backups.mode, BackupMode::Full);
+        assert_eq!(config.backups.location, BackupLocation::Store);
+        assert_eq!(
+            config.backups.store_dir,
+            Some(PathBuf::from("/mnt/backups/audiobooks"))
+        );
+        assert_eq!(config.backups.keep_generations, 3);
+        assert_eq!(config.backups.max_age_days, Some(90));
+    }
+
+    #[test]
+    fn test_load_with_lookup_config() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+[lookup]
+jobs = 8
+locked_fields = ["title", "genre"]
+"#,
+        )
+        .unwrap();
+
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.lookup.jobs, 8);
+        assert_eq!(config.lookup.locked_fields, vec!["title", "genre"]);
+        assert_eq!(Config::default().lookup.jobs, 4);
+        assert!(Config::default().lookup.locked_fields.is_empty());
+    }
+
+    #[test]
+    fn test_load_with_genres_config() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+[genres]
+whitelist = ["Science Fiction", "Fantasy"]
+max = 2
+
+[genres.map]
+"Fiction, science fiction, general" = "Science Fiction"
+"Science Fiction & Fantasy" = ["Science Fiction", "Fantasy"]
+"Accessible book" = []
+"#,
+        )
+        .unwrap();
+
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.genres.max, 2);
+        assert_eq!(config.genres.separator, "; ");
+        assert_eq!(
+            config.genres.map["Fiction, science fiction, general"].genres(),
+            ["Science Fiction".to_string()]
+        );
+        assert_eq!(
+            config.genres.map["Science Fiction & Fantasy"]
+                .genres()
+                .len(),
+            2
+        );
+        assert!(config.genres.map["Accessible book"].genres().is_empty());
     }
 }
@@ -41,6 +41,17 @@ pub struct AudiobookRecord {
     pub chapter_count: Option<i32>,
 }
 
+/// A library file linked to the file it was organized from
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkRecord {
+    pub file_path: String,
+    /// Absolute path of the source (e.g. the seeding download)
+    pub source_path: String,
+    /// "hardlink" or "reflink"
+    pub kind: String,
+    pub linked_at: String,
+}
+
 impl LibraryDb {
     /// Open or create database in the given directory
     pub fn open(dir: &Path) -> Result<Self> {
@@ -127,6 +138,13 @@ impl LibraryDb {
             CREATE INDEX IF NOT EXISTS idx_title ON audiobooks(title);
             CREATE INDEX IF NOT EXISTS idx_series ON audiobooks(series);
             CREATE INDEX IF NOT EXISTS idx_sha256 ON audiobooks(sha256);
+
+            CREATE TABLE IF NOT EXISTS links (
+                file_path TEXT PRIMARY KEY,
+                source_path TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                linked_at TEXT NOT NULL
+            );
             "#,
         )?;
         Ok(())
@@ -306,6 +324,112 @@ impl LibraryDb {
             .context("Failed to query by path")
     }
 
+    /// Get records by content hash
+    pub fn get_by_hash(&self, sha256: &str) -> Result<Vec<AudiobookRecord>> {
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT id, file_path, file_size, sha256, indexed_at,
+                   title, author, narrator, series, series_position,
+                   year, description, publisher, genre, asin, isbn,
+                   duration_seconds, chapter_count
+            FROM audiobooks
+            WHERE sha256 = ?1
+            ORDER BY file_path
+            "#,
+        )?;
+
+        self.collect_records(&mut stmt, params![sha256])
+    }
+
+    /// Record that a library file is linked to its source
+    pub fn record_link(&self, relative_path: &str, source_path: &Path, kind: &str) -> Result<()> {
+        let now = chrono::Utc::now().to_rfc3339();
+        self.conn.execute(
+            r#"
+            INSERT INTO links (file_path, source_path, kind, linked_at)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT(file_path) DO UPDATE SET
+                source_path = excluded.source_path,
+                kind = excluded.kind,
+                linked_at = excluded.linked_at
+            "#,
+            params![relative_path, source_path.to_string_lossy(), kind, now],
+        )?;
+        Ok(())
+    }
+
+    /// Get the link recorded for a library file
+    pub fn get_link(&self, relative_path: &str) -> Result<Option<LinkRecord>> {
+        self.conn
+            .query_row(
+                "SELECT file_path, source_path, kind, linked_at FROM links WHERE file_path = ?1",
+                params![relative_path],
+                |row| {
+                    Ok(LinkRecord {
+                        file_path: row.get(0)?,
+                        source_path: row.get(1)?,
+                        kind: row.get(2)?,
+                        linked_at: row.get(3)?,
+                    })
+                },
+            )
+            .optional()
+            .context("Failed to query link")
+    }
+
+    /// Remove a file's record (and its link)
+    pub fn remove_path(&self, relative_path: &str) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM audiobooks WHERE file_path = ?1",
+            params![relative_path],
+        )?;
+        self.remove_link(relative_path)
+    }
+
+    /// Forget a library file's link (after it gets its own copy)
+    pub fn remove_link(&self, relative_path: &str) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM links WHERE file_path = ?1",
+            params![relative_path],
+        )?;
+        Ok(())
+    }
+
+    /// Distinct author and narrator names with the number of books each appears on
+    pub fn name_counts(&self) -> Result<Vec<(String, usize)>> {
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT name, COUNT(*) FROM (
+                SELECT author AS name FROM audiobooks WHERE author IS NOT NULL
+                UNION ALL
+                SELECT narrator AS name FROM audiobooks WHERE narrator IS NOT NULL
+            )
+            GROUP BY name
+            ORDER BY name
+            "#,
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as usize))
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to count names")
+    }
+
+    /// Distinct genre tags with the number of books using each (`None` = no genre)
+    pub fn genre_counts(&self) -> Result<Vec<(Option<String>, usize)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT genre, COUNT(*) FROM audiobooks GROUP BY genre ORDER BY genre")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, Option<String>>(0)?,
+                row.get::<_, i64>(1)? as usize,
+            ))
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to count genres")
+    }
+
     /// Remove entries for files that no longer exist
     pub fn prune(&self) -> Result<usize> {
         let records = self.list_all()?;
@@ -319,6 +443,10 @@ impl LibraryDb {
                 removed += 1;
             }
         }
+        self.conn.execute(
+            "DELETE FROM links WHERE file_path NOT IN (SELECT file_path FROM audiobooks)",
+            [],
+        )?;
 
         Ok(removed)
     }
@@ -438,4 +566,92 @@ mod tests {
         let record = db.get_by_path("book.m4b").unwrap().unwrap();
         assert_eq!(record.title, Some("Updated".to_string()));
     }
+
+    #[test]
+    fn test_get_by_hash() {
+        let dir = TempDir::new().unwrap();
+        let db = LibraryDb::open(dir.path()).unwrap();
+        let metadata = AudiobookMetadata::default();
+
+        db.upsert("b/book.m4b", 1000, "abc", &metadata).unwrap();
+        db.upsert("a/book.m4b", 1000, "abc", &metadata).unwrap();
+        db.upsert("other.m4b", 1000, "def", &metadata).unwrap();
+
+        let records = db.get_by_hash("abc").unwrap();
+        let paths: Vec<&str> = records.iter().map(|r| r.file_path.as_str()).collect();
+        assert_eq!(paths, vec!["a/book.m4b", "b/book.m4b"]);
+        assert!(db.get_by_hash("missing").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_links() {
+        let dir = TempDir::new().unwrap();
+        let db = LibraryDb::open(dir.path()).unwrap();
+        db.upsert("a/book.m4b", 1000, "abc123", &AudiobookMetadata::default())
+            .unwrap();
+
+        db.record_link("a/book.m4b", Path::new("/seed/book.m4b"), "hardlink")
+            .unwrap();
+        let link = db.get_link("a/book.m4b").unwrap().unwrap();
+        assert_eq!(link.source_path, "/seed/book.m4b");
+        assert_eq!(link.kind, "hardlink");
+
+        db.remove_link("a/book.m4b").unwrap();
+        assert!(db.get_link("a/book.m4b").unwrap().is_none());
+
+        // Pruning drops links of files no longer indexed
+        db.record_link("gone.m4b", Path::new("/seed/gone.m4b"), "reflink")
+            .unwrap();
+        db.prune().unwrap();
+        assert!(db.get_link("gone.m4b").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_name_counts() {
+        let dir = TempDir::new().unwrap();
+        let db = LibraryDb::open(dir.path()).unwrap();
+
+        let metadata = AudiobookMetadata {
+            author: Some("Stephen King".to_string()),
+            narrator: Some("Stephen King".to_string()),
+            ..Default::default()
+        };
+        db.upsert("a.m4b", 1000, "a", &metadata).unwrap();
+        let metadata = AudiobookMetadata {
+            author: Some("King, Stephen".to_string()),
+            ..Default::default()
+        };
+        db.upsert("b.m4b", 1000, "b", &metadata).unwrap();
+
+        assert_eq!(
+            db.name_counts().unwrap(),
+            vec![
+                ("King, Stephen".to_string(), 1),
+                ("Stephen King".to_string(), 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_genre_counts() {
+        let dir = TempDir::new().unwrap();
+        let db = LibraryDb::open(dir.path()).unwrap();
+
+        for (path, genre) in [
+            ("a.m4b", Some("Fantasy")),
+            ("b.m4b", Some("Fantasy")),
+            ("c.m4b", None),
+        ] {
+            let metadata = AudiobookMetadata {
+                genre: genre.map(String::from),
+                ..Default::default()
+            };
+            db.upsert(path, 1000, path, &metadata).unwrap();
+        }
+
+        assert_eq!(
+            db.genre_counts().unwrap(),
+            vec![(None, 1), (Some("Fantasy".to_string()), 2)]
+        );
+    }
 }
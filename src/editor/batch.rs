@@ -0,0 +1,261 @@
+//! Multi-file edit documents
+//!
+//! Several books are edited in one TOML document: one `[[book]]` table per
+//! file, keyed by `path`, plus a `[defaults]` table with values shared by
+//! every book. A field missing from a book takes the default; `""` clears it.
+
+use crate::editor::toml::toml_string_value;
+use crate::metadata::{AudiobookMetadata, EDITABLE_FIELDS};
+use anyhow::{bail, Context, Result};
+use std::path::PathBuf;
+
+const NUMERIC_FIELDS: &[&str] = &["series_position", "year"];
+
+/// Build the batch document for a set of files
+pub fn batch_to_toml(books: &[(PathBuf, AudiobookMetadata)]) -> String {
+    let mut lines = Vec::new();
+
+    lines.push(format!(
+        "# Audiobook Metadata - Batch Edit ({} files)",
+        books.len()
+    ));
+    lines
+        .push("# Values in [defaults] apply to every book that doesn't set the field.".to_string());
+    lines.push("# Set a field to \"\" in a book to clear it.".to_string());
+    lines.push("# Delete a [[book]] table to leave that file unchanged.".to_string());
+    lines.push(String::new());
+
+    // Values shared by every book start out in [defaults]
+    let shared: Vec<(&str, String)> = EDITABLE_FIELDS
+        .iter()
+        .filter_map(|&field| {
+            let first = books.first()?.1.get_field(field)?;
+            books
+                .iter()
+                .all(|(_, m)| m.get_field(field).as_deref() == Some(first.as_str()))
+                .then_some((field, first))
+        })
+        .collect();
+
+    lines.push("[defaults]".to_string());
+    for &field in EDITABLE_FIELDS {
+        match shared.iter().find(|(name, _)| *name == field) {
+            Some((_, value)) => lines.push(format_field(field, value)),
+            None => lines.push(format!("# {} = {}", field, empty_value(field))),
+        }
+    }
+
+    for (path, metadata) in books {
+        lines.push(String::new());
+        lines.push("[[book]]".to_string());
+        lines.push(format!(
+            "path = {}",
+            toml_string_value(&path.to_string_lossy())
+        ));
+        for &field in EDITABLE_FIELDS {
+            if shared.iter().any(|(name, _)| *name == field) {
+                continue;
+            }
+            match metadata.get_field(field) {
+                Some(value) => lines.push(format_field(field, &value)),
+                None => lines.push(format!("# {} = {}", field, empty_value(field))),
+            }
+        }
+    }
+
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// Parse a batch document into the new metadata for each listed file
+pub fn toml_to_batch(toml_str: &str) -> Result<Vec<(PathBuf, AudiobookMetadata)>> {
+    let value: toml::Value = toml::from_str(toml_str)?;
+    let table = value
+        .as_table()
+        .ok_or_else(|| anyhow::anyhow!("Invalid TOML structure"))?;
+
+    let empty = toml::map::Map::new();
+    let defaults = match table.get("defaults") {
+        Some(v) => v
+            .as_table()
+            .ok_or_else(|| anyhow::anyhow!("[defaults] must be a table"))?,
+        None => &empty,
+    };
+    check_fields(defaults, "[defaults]", false)?;
+
+    for key in table.keys() {
+        if key != "defaults" && key != "book" {
+            bail!("Unexpected top-level key \"{}\"", key);
+        }
+    }
+
+    let books = match table.get("book") {
+        Some(v) => v
+            .as_array()
+            .ok_or_else(|| anyhow::anyhow!("book must be an array of [[book]] tables"))?
+            .as_slice(),
+        None => &[],
+    };
+
+    let mut result: Vec<(PathBuf, AudiobookMetadata)> = Vec::new();
+    for (index, book) in books.iter().enumerate() {
+        let book = book
+            .as_table()
+            .ok_or_else(|| anyhow::anyhow!("[[book]] #{} must be a table", index + 1))?;
+        let path = book
+            .get("path")
+            .and_then(|p| p.as_str())
+            .map(PathBuf::from)
+            .ok_or_else(|| anyhow::anyhow!("[[book]] #{} has no path", index + 1))?;
+        let label = path.display().to_string();
+        check_fields(book, &label, true)?;
+
+        if result.iter().any(|(p, _)| *p == path) {
+            bail!("{} appears in more than one [[book]] table", label);
+        }
+
+        let mut metadata = AudiobookMetadata::default();
+        for &field in EDITABLE_FIELDS {
+            let Some(value) = book.get(field).or_else(|| defaults.get(field)) else {
+                continue;
+            };
+            let text = match value {
+                toml::Value::String(s) => s.clone(),
+                toml::Value::Integer(n) => n.to_string(),
+                _ => bail!("{}: {} must be a string or a number", label, field),
+            };
+            metadata
+                .set_field(field, Some(&text))
+                .with_context(|| label.clone())?;
+        }
+
+        result.push((path, metadata));
+    }
+
+    Ok(result)
+}
+
+/// Reject keys that aren't editable fields (typos would otherwise be ignored)
+fn check_fields(
+    table: &toml::map::Map<String, toml::Value>,
+    label: &str,
+    allow_path: bool,
+) -> Result<()> {
+    for key in table.keys() {
+        if allow_path && key == "path" {
+            continue;
+        }
+        if !EDITABLE_FIELDS.contains(&key.as_str()) {
+            bail!(
+                "{}: unknown field \"{}\" (editable fields: {})",
+                label,
+                key,
+                EDITABLE_FIELDS.join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+fn format_field(field: &str, value: &str) -> String {
+    if NUMERIC_FIELDS.contains(&field) {
+        format!("{} = {}", field, value)
+    } else {
+        format!("{} = {}", field, toml_string_value(value))
+    }
+}
+
+fn empty_value(field: &str) -> &'static str {
+    if NUMERIC_FIELDS.contains(&field) {
+        "0"
+    } else {
+        "\"\""
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book(title: &str, position: u32, narrator: Option<&str>) -> AudiobookMetadata {
+        AudiobookMetadata {
+            title: Some(title.to_string()),
+            author: Some("Dennis E. Taylor".to_string()),
+            series: Some("Bobiverse".to_string()),
+            series_position: Some(position),
+            narrator: narrator.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_shared_values_go_to_defaults() {
+        let books = vec![
+            (
+                PathBuf::from("/lib/1.m4b"),
+                book("We Are Legion", 1, Some("Ray Porter")),
+            ),
+            (
+                PathBuf::from("/lib/2.m4b"),
+                book("For We Are Many", 2, None),
+            ),
+        ];
+
+        let toml = batch_to_toml(&books);
+        let defaults = toml.split("\n[[book]]").next().unwrap();
+        assert!(defaults.contains("series = \"Bobiverse\""));
+        assert!(defaults.contains("author = \"Dennis E. Taylor\""));
+        assert!(defaults.contains("# narrator = \"\""));
+        assert!(toml.contains("path = \"/lib/1.m4b\"\ntitle = \"We Are Legion\""));
+        assert!(toml.contains("series_position = 2"));
+        assert_eq!(toml.matches("series = \"Bobiverse\"").count(), 1);
+
+        // Unedited document round-trips to the original metadata
+        assert_eq!(toml_to_batch(&toml).unwrap(), books);
+    }
+
+    #[test]
+    fn test_defaults_and_overrides() {
+        let toml = r#"
+[defaults]
+series = "Bobiverse"
+narrator = "Ray Porter"
+
+[[book]]
+path = "/lib/1.m4b"
+title = "We Are Legion"
+series_position = 1
+
+[[book]]
+path = "/lib/2.m4b"
+title = "For We Are Many"
+series_position = 2
+narrator = ""
+"#;
+
+        let books = toml_to_batch(toml).unwrap();
+        assert_eq!(books.len(), 2);
+        assert_eq!(books[0].1.narrator.as_deref(), Some("Ray Porter"));
+        assert_eq!(books[0].1.series.as_deref(), Some("Bobiverse"));
+        assert_eq!(books[1].1.narrator, None);
+        assert_eq!(books[1].1.series_position, Some(2));
+    }
+
+    #[test]
+    fn test_rejects_unknown_fields_and_duplicates() {
+        let unknown = "[[book]]\npath = \"/lib/1.m4b\"\nnarator = \"Ray Porter\"\n";
+        assert!(toml_to_batch(unknown)
+            .unwrap_err()
+            .to_string()
+            .contains("unknown field \"narator\""));
+
+        let duplicate = "[[book]]\npath = \"/lib/1.m4b\"\n\n[[book]]\npath = \"/lib/1.m4b\"\n";
+        assert!(toml_to_batch(duplicate)
+            .unwrap_err()
+            .to_string()
+            .contains("more than one"));
+
+        let no_path = "[[book]]\ntitle = \"Untitled\"\n";
+        assert!(toml_to_batch(no_path).is_err());
+    }
+}
@@ -1,7 +1,11 @@
 #![allow(dead_code, unused_imports)]
 
+pub mod batch;
 pub mod diff;
+pub mod three_way;
 pub mod toml;
 
+pub use batch::{batch_to_toml, toml_to_batch};
 pub use diff::{compute_changes, format_diff, FieldChange};
-pub use toml::{metadata_to_toml, toml_to_metadata};
+pub use three_way::{three_way_merge, FieldConflict, ThreeWayMerge};
+pub use toml::{metadata_to_toml, toml_string_value, toml_to_metadata};
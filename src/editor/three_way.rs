@@ -0,0 +1,127 @@
+//! Three-way merge of metadata edits
+//!
+//! Used when a file changed after an edit was saved: `base` is the metadata
+//! the edit started from, `ours` is the edit and `theirs` is the file now.
+//! A field changed on only one side takes that side's value; a field
+//! changed differently on both sides is a conflict.
+
+use crate::metadata::{AudiobookMetadata, EDITABLE_FIELDS};
+
+/// A field changed differently by the edit and in the file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldConflict {
+    pub field: &'static str,
+    pub ours: Option<String>,
+    pub theirs: Option<String>,
+}
+
+/// Result of a three-way merge
+#[derive(Debug, Clone)]
+pub struct ThreeWayMerge {
+    /// Merged metadata; conflicting fields hold our value until resolved
+    pub merged: AudiobookMetadata,
+    pub conflicts: Vec<FieldConflict>,
+}
+
+impl ThreeWayMerge {
+    /// Resolve a conflict with the file's value
+    pub fn take_theirs(&mut self, conflict: &FieldConflict) {
+        // Values came from get_field, so they always parse
+        let _ = self
+            .merged
+            .set_field(conflict.field, conflict.theirs.as_deref());
+    }
+}
+
+/// Merge an edit (`ours`) with the file's current metadata (`theirs`)
+pub fn three_way_merge(
+    base: &AudiobookMetadata,
+    ours: &AudiobookMetadata,
+    theirs: &AudiobookMetadata,
+) -> ThreeWayMerge {
+    // Start from the file so read-only fields reflect its current state
+    let mut merged = theirs.clone();
+    let mut conflicts = Vec::new();
+
+    for &field in EDITABLE_FIELDS {
+        let base_value = base.get_field(field);
+        let our_value = ours.get_field(field);
+        let their_value = theirs.get_field(field);
+
+        if our_value == base_value || our_value == their_value {
+            continue;
+        }
+
+        let _ = merged.set_field(field, our_value.as_deref());
+        if their_value != base_value {
+            conflicts.push(FieldConflict {
+                field,
+                ours: our_value,
+                theirs: their_value,
+            });
+        }
+    }
+
+    ThreeWayMerge { merged, conflicts }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(title: &str, series: Option<&str>, narrator: Option<&str>) -> AudiobookMetadata {
+        AudiobookMetadata {
+            title: Some(title.to_string()),
+            series: series.map(String::from),
+            narrator: narrator.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_non_overlapping_changes_merge() {
+        let base = metadata("Leviathan Wakes", None, None);
+        let ours = metadata("Leviathan Wakes", Some("The Expanse"), None);
+        let theirs = metadata("Leviathan Wakes", None, Some("Jefferson Mays"));
+
+        let merge = three_way_merge(&base, &ours, &theirs);
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(merge.merged.series.as_deref(), Some("The Expanse"));
+        assert_eq!(merge.merged.narrator.as_deref(), Some("Jefferson Mays"));
+    }
+
+    #[test]
+    fn test_same_change_on_both_sides_is_not_a_conflict() {
+        let base = metadata("Leviathan Wakes", None, None);
+        let both = metadata("Leviathan Wakes", Some("The Expanse"), None);
+
+        let merge = three_way_merge(&base, &both, &both);
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(merge.merged, both);
+    }
+
+    #[test]
+    fn test_conflicting_changes() {
+        let base = metadata("Leviathan Wakes", None, None);
+        let ours = metadata("Leviathan Wakes (Unabridged)", None, None);
+        let theirs = metadata("Leviathan Wakes: The Expanse 1", None, None);
+
+        let mut merge = three_way_merge(&base, &ours, &theirs);
+        assert_eq!(
+            merge.conflicts,
+            vec![FieldConflict {
+                field: "title",
+                ours: Some("Leviathan Wakes (Unabridged)".to_string()),
+                theirs: Some("Leviathan Wakes: The Expanse 1".to_string()),
+            }]
+        );
+        assert_eq!(
+            merge.merged.title.as_deref(),
+            Some("Leviathan Wakes (Unabridged)")
+        );
+
+        let conflict = merge.conflicts[0].clone();
+        merge.take_theirs(&conflict);
+        assert_eq!(merge.merged.title, theirs.title);
+    }
+}
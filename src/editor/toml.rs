@@ -12,7 +12,7 @@ pub fn metadata_to_toml(metadata: &AudiobookMetadata) -> String {
     // Helper to add field
     fn add_field(lines: &mut Vec<String>, name: &str, value: &Option<String>) {
         match value {
-            Some(v) => lines.push(format!("{} = \"{}\"", name, escape_toml_string(v))),
+            Some(v) => lines.push(format!("{} = {}", name, toml_string_value(v))),
             None => lines.push(format!("# {} = \"\"", name)),
         }
     }
@@ -70,15 +70,9 @@ pub fn metadata_to_toml(metadata: &AudiobookMetadata) -> String {
 
 /// Parse TOML string back to metadata
 pub fn toml_to_metadata(toml_str: &str) -> Result<AudiobookMetadata> {
-    // Filter out comment lines and parse remaining as TOML
-    let filtered: String = toml_str
-        .lines()
-        .filter(|line| !line.trim_start().starts_with('#'))
-        .collect::<Vec<_>>()
-        .join("\n");
-
-    // Parse into a toml::Value first
-    let value: toml::Value = toml::from_str(&filtered)?;
+    // Comment lines are ignored by the TOML parser itself. They must not be
+    // filtered out beforehand: a line of a multi-line description may start with '#'.
+    let value: toml::Value = toml::from_str(toml_str)?;
     let table = value
         .as_table()
         .ok_or_else(|| anyhow::anyhow!("Invalid TOML structure"))?;
@@ -113,6 +107,23 @@ pub fn toml_to_metadata(toml_str: &str) -> Result<AudiobookMetadata> {
     })
 }
 
+/// Format a string as a TOML value.
+///
+/// Values with line breaks (descriptions) become multi-line strings so each
+/// paragraph sits on its own line in the editor.
+pub fn toml_string_value(s: &str) -> String {
+    if !s.contains('\n') {
+        return format!("\"{}\"", escape_toml_string(s));
+    }
+
+    let escaped = s
+        .replace('\r', "")
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\t', "\\t");
+    format!("\"\"\"\n{}\"\"\"", escaped)
+}
+
 /// Escape special characters in TOML strings
 fn escape_toml_string(s: &str) -> String {
     s.replace('\\', "\\\\")
@@ -199,4 +210,30 @@ year = 2023
         assert_eq!(parsed.year, original.year);
         assert_eq!(parsed.isbn, original.isbn);
     }
+
+    #[test]
+    fn test_escape_toml_string() {
+        assert_eq!(escape_toml_string("hello"), "hello");
+        assert_eq!(escape_toml_string("hello\"world"), "hello\\\"world");
+        assert_eq!(escape_toml_string("line1\nline2"), "line1\\nline2");
+        assert_eq!(escape_toml_string("path\\to\\file"), "path\\\\to\\\\file");
+    }
+
+    #[test]
+    fn test_multiline_description_round_trip() {
+        let description = "First paragraph with \"quotes\" and a \\.\n\n# Not a comment\n- item";
+        let metadata = AudiobookMetadata {
+            title: Some("Test Book".to_string()),
+            description: Some(description.to_string()),
+            ..Default::default()
+        };
+
+        let toml = metadata_to_toml(&metadata);
+        assert!(toml.contains("description = \"\"\"\nFirst paragraph"));
+        assert!(toml.contains("\n# Not a comment\n"));
+
+        let parsed = toml_to_metadata(&toml).unwrap();
+        assert_eq!(parsed.description.as_deref(), Some(description));
+        assert_eq!(parsed.title.as_deref(), Some("Test Book"));
+    }
 }
@@ -0,0 +1,137 @@
+//! Metadata writes on hardlinked files
+//!
+//! `organize --mode hardlink` leaves a library file sharing its data with the
+//! file it came from (typically a download that is still seeding), so writing
+//! tags to one changes the other and breaks the torrent. Metadata writes call
+//! `guard` first: it warns, or with `--break-links` gives the file its own copy.
+//!
+//! Breaking links is enabled by `configure` (called once by `main`).
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::database::LibraryDb;
+use crate::organize::transfer::copy_file;
+use crate::safety::absolute_path;
+
+static BREAK_LINKS: AtomicBool = AtomicBool::new(false);
+
+/// Break hardlinks before writing metadata for the rest of the process
+pub fn configure(break_links: bool) {
+    BREAK_LINKS.store(break_links, Ordering::Relaxed);
+}
+
+/// Number of names the file's data has (1 unless hardlinked)
+#[cfg(unix)]
+pub fn link_count(path: &Path) -> Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(fs::metadata(path)
+        .with_context(|| format!("Failed to stat {:?}", path))?
+        .nlink())
+}
+
+#[cfg(not(unix))]
+pub fn link_count(_path: &Path) -> Result<u64> {
+    Ok(1)
+}
+
+/// Called before writing metadata to `path`: warns when the file is
+/// hardlinked, or breaks the link if `--break-links` was given
+pub fn guard(path: &Path) -> Result<()> {
+    let links = link_count(path)?;
+    if links < 2 {
+        return Ok(());
+    }
+
+    if BREAK_LINKS.load(Ordering::Relaxed) {
+        break_link(path)?;
+        forget_link(path)?;
+        println!(
+            "{} {} now has its own copy (hardlink broken)",
+            "\u{2713}".green(),
+            path.display()
+        );
+        return Ok(());
+    }
+
+    let source = linked_source(path)?
+        .map(|s| format!(", including {}", s))
+        .unwrap_or_default();
+    eprintln!(
+        "{} {} is hardlinked to {} other file(s){}; the new metadata will appear there too (use --break-links to give it its own copy)",
+        "⚠".yellow(),
+        path.display(),
+        links - 1,
+        source
+    );
+    Ok(())
+}
+
+/// Replace a hardlinked file with a verified copy of its own
+pub fn break_link(path: &Path) -> Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".unlink.tmp");
+    let temp = Path::new(&temp);
+
+    copy_file(path, temp, None)?;
+    fs::rename(temp, path).with_context(|| format!("Failed to replace {:?}", path))
+}
+
+/// The source recorded for a library file by `organize`, if any
+fn linked_source(path: &Path) -> Result<Option<String>> {
+    with_library_entry(path, |db, relative| {
+        Ok(db.get_link(relative)?.map(|link| link.source_path))
+    })
+    .map(Option::flatten)
+}
+
+fn forget_link(path: &Path) -> Result<()> {
+    with_library_entry(path, |db, relative| db.remove_link(relative)).map(|_| ())
+}
+
+/// Run `f` with the library database containing `path` and the path
+/// relative to it, if the file is in an indexed library
+fn with_library_entry<T>(
+    path: &Path,
+    f: impl FnOnce(&LibraryDb, &str) -> Result<T>,
+) -> Result<Option<T>> {
+    let path = absolute_path(path);
+    let Some(dir) = path.parent() else {
+        return Ok(None);
+    };
+    let Some(db) = LibraryDb::find_from(dir)? else {
+        return Ok(None);
+    };
+    let Ok(relative) = path.strip_prefix(db.base_path()) else {
+        return Ok(None);
+    };
+    f(&db, &relative.to_string_lossy()).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_break_link_gives_file_its_own_copy() {
+        let temp = TempDir::new().unwrap();
+        let seed = temp.path().join("seed.m4b");
+        let book = temp.path().join("book.m4b");
+        fs::write(&seed, "audio").unwrap();
+        fs::hard_link(&seed, &book).unwrap();
+        assert_eq!(link_count(&book).unwrap(), 2);
+
+        break_link(&book).unwrap();
+        assert_eq!(link_count(&book).unwrap(), 1);
+        assert_eq!(link_count(&seed).unwrap(), 1);
+        assert_eq!(fs::read(&book).unwrap(), b"audio");
+
+        fs::write(&book, "tagged").unwrap();
+        assert_eq!(fs::read(&seed).unwrap(), b"audio");
+    }
+}
@@ -1,6 +1,6 @@
 //! SHA256 file hashing utilities
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use sha2::{Digest, Sha256};
 use std::fs::File;
 use std::io::{BufReader, Read, Write};
@@ -26,6 +26,103 @@ pub fn sha256_file(path: &Path) -> Result<String> {
     Ok(hex::encode(hasher.finalize()))
 }
 
+/// Compute SHA256 of a file's audio data (its `mdat` atoms), ignoring tags.
+///
+/// Unchanged by metadata writes, so it identifies the same recording before
+/// and after tagging. Fails if the atom structure is truncated or corrupt.
+pub fn audio_sha256(path: &Path) -> Result<String> {
+    hash_atoms(path, None)
+}
+
+/// Compute the SHA256 of a whole file and of its audio data in one pass
+pub fn file_and_audio_sha256(path: &Path) -> Result<(String, String)> {
+    let mut file_hasher = Sha256::new();
+    let audio = hash_atoms(path, Some(&mut file_hasher))?;
+    Ok((hex::encode(file_hasher.finalize()), audio))
+}
+
+/// Walk a file's top-level atoms hashing the `mdat` bodies, feeding every
+/// byte read to `whole` too
+fn hash_atoms(path: &Path, whole: Option<&mut Sha256>) -> Result<String> {
+    let file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let len = file.metadata()?.len();
+    let mut reader = BufReader::new(HashReader {
+        inner: file,
+        hasher: whole,
+    });
+    let mut hasher = Sha256::new();
+    let mut found = false;
+    let mut pos = 0u64;
+
+    while pos < len {
+        let mut header = [0u8; 8];
+        reader
+            .read_exact(&mut header)
+            .with_context(|| format!("Truncated atom header at offset {} in {:?}", pos, path))?;
+        let mut size = u64::from(u32::from_be_bytes(header[..4].try_into().unwrap()));
+        let mut header_len = 8;
+        if size == 1 {
+            let mut large = [0u8; 8];
+            reader.read_exact(&mut large)?;
+            size = u64::from_be_bytes(large);
+            header_len = 16;
+        } else if size == 0 {
+            size = len - pos;
+        }
+        if size < header_len || pos + size > len {
+            bail!("Invalid atom size {} at offset {} in {:?}", size, pos, path);
+        }
+
+        // Other atoms are read rather than skipped so `whole` sees them
+        let body = size - header_len;
+        let copied = if &header[4..] == b"mdat" {
+            found = true;
+            std::io::copy(&mut (&mut reader).take(body), &mut HashWriter(&mut hasher))?
+        } else {
+            std::io::copy(&mut (&mut reader).take(body), &mut std::io::sink())?
+        };
+        if copied != body {
+            bail!("Truncated atom at offset {} in {:?}", pos, path);
+        }
+        pos += size;
+    }
+
+    if !found {
+        bail!("No audio data (mdat atom) in {:?}", path);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Reader that feeds everything it reads to an optional hasher
+struct HashReader<'a> {
+    inner: File,
+    hasher: Option<&'a mut Sha256>,
+}
+
+impl Read for HashReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.update(&buf[..n]);
+        }
+        Ok(n)
+    }
+}
+
+/// Adapter so `io::copy` can stream into a hasher
+struct HashWriter<'a>(&'a mut Sha256);
+
+impl Write for HashWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
 /// Get the path to the hash file for an m4b file
 pub fn hash_file_path(m4b_path: &Path) -> PathBuf {
     let mut hash_path = m4b_path.as_os_str().to_owned();
@@ -113,6 +210,69 @@ mod tests {
         );
     }
 
+    fn atom(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut atom = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        atom.extend_from_slice(kind);
+        atom.extend_from_slice(body);
+        atom
+    }
+
+    #[test]
+    fn test_audio_sha256_ignores_tags() {
+        let audio = atom(b"mdat", b"audio frames");
+        let mut tagged = atom(b"ftyp", b"M4B ");
+        tagged.extend(atom(b"moov", b"udta with a title"));
+        tagged.extend(&audio);
+        let mut retagged = atom(b"ftyp", b"M4B ");
+        retagged.extend(atom(b"moov", b"udta with a different, longer title"));
+        retagged.extend(&audio);
+
+        let a = NamedTempFile::new().unwrap();
+        let b = NamedTempFile::new().unwrap();
+        std::fs::write(a.path(), &tagged).unwrap();
+        std::fs::write(b.path(), &retagged).unwrap();
+
+        assert_eq!(
+            audio_sha256(a.path()).unwrap(),
+            audio_sha256(b.path()).unwrap()
+        );
+        assert_ne!(
+            sha256_file(a.path()).unwrap(),
+            sha256_file(b.path()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_file_and_audio_sha256_matches_separate_hashes() {
+        let mut data = atom(b"ftyp", b"M4B ");
+        data.extend(atom(b"moov", b"udta with a title"));
+        data.extend(atom(b"mdat", b"audio frames"));
+        let file = NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), &data).unwrap();
+
+        assert_eq!(
+            file_and_audio_sha256(file.path()).unwrap(),
+            (
+                sha256_file(file.path()).unwrap(),
+                audio_sha256(file.path()).unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_audio_sha256_rejects_truncated_file() {
+        let mut data = atom(b"ftyp", b"M4B ");
+        data.extend(atom(b"mdat", b"audio frames"));
+        data.truncate(data.len() - 4);
+
+        let file = NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), &data).unwrap();
+        assert!(audio_sha256(file.path()).is_err());
+
+        std::fs::write(file.path(), atom(b"ftyp", b"M4B ")).unwrap();
+        assert!(audio_sha256(file.path()).is_err());
+    }
+
     #[test]
     fn test_hash_file_path() {
         let path = Path::new("/foo/bar/book.m4b");
@@ -0,0 +1,461 @@
+//! Operation journal
+//!
+//! An append-only JSON Lines log (`~/.local/share/audiobookctl/journal.jsonl`)
+//! of every metadata write and file move, with field-level before/after
+//! values. `history` browses it and `undo` reverts entries from it, so a
+//! change can be undone even after its `.bak` file was committed away.
+//!
+//! Recording is enabled by `start` (called once by `main` with the command
+//! being run); without it, `record` does nothing.
+//!
+//! Appends hold `journal.jsonl.lock`, so runs on different libraries can
+//! share the journal without handing out the same id.
+
+use crate::lockfile;
+use crate::metadata::{AudiobookMetadata, EDITABLE_FIELDS};
+use crate::safety::absolute_path;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::warn;
+
+/// A field's value before and after a write
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldEdit {
+    pub field: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// What an entry did
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    /// Tags written to a file
+    Metadata {
+        file: PathBuf,
+        changes: Vec<FieldEdit>,
+    },
+    /// File moved (renamed)
+    Move { from: PathBuf, to: PathBuf },
+    /// File copied; `sha256` is the copy's hash when known
+    Copy {
+        from: PathBuf,
+        to: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sha256: Option<String>,
+    },
+    /// File deleted
+    Remove { path: PathBuf },
+    /// An earlier entry was reverted
+    Undo { undone: u64 },
+}
+
+/// One journal line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: u64,
+    /// Id of the first entry written by the same invocation
+    pub run: u64,
+    pub timestamp: DateTime<Utc>,
+    pub command: String,
+    #[serde(flatten)]
+    pub operation: Operation,
+}
+
+impl JournalEntry {
+    /// Short description for listings
+    pub fn summary(&self) -> String {
+        match &self.operation {
+            Operation::Metadata { file, changes } => {
+                let fields: Vec<&str> = changes.iter().map(|c| c.field.as_str()).collect();
+                format!("tags {} ({})", file.display(), fields.join(", "))
+            }
+            Operation::Move { from, to } => {
+                format!("move {} -> {}", from.display(), to.display())
+            }
+            Operation::Copy { from, to, .. } => {
+                format!("copy {} -> {}", from.display(), to.display())
+            }
+            Operation::Remove { path } => format!("remove {}", path.display()),
+            Operation::Undo { undone } => format!("undo #{}", undone),
+        }
+    }
+}
+
+/// The journal file
+pub struct Journal {
+    path: PathBuf,
+}
+
+/// Recording state for this process: (journal, command, run id once known,
+/// where the journal ended after our last append)
+struct Recorder {
+    journal: Journal,
+    command: String,
+    run: Option<u64>,
+    tail: Option<Tail>,
+}
+
+/// The last id in the journal and the journal's length at that point, so the
+/// next id can be found without rereading the whole file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Tail {
+    id: u64,
+    len: u64,
+}
+
+static RECORDER: Mutex<Option<Recorder>> = Mutex::new(None);
+
+/// Enable recording for the rest of the process
+pub fn start(command: &str) -> Result<()> {
+    let recorder = Recorder {
+        journal: Journal::open()?,
+        command: command.to_string(),
+        run: None,
+        tail: None,
+    };
+    *RECORDER.lock().unwrap_or_else(|e| e.into_inner()) = Some(recorder);
+    Ok(())
+}
+
+/// Whether operations are being recorded
+pub fn is_recording() -> bool {
+    RECORDER.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+/// Append an operation to the journal (no-op unless `start` was called).
+///
+/// The operation has already happened, so a journal failure is only warned about.
+pub fn record(operation: Operation) {
+    let mut guard = RECORDER.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(recorder) = guard.as_mut() else {
+        return;
+    };
+
+    let Recorder {
+        journal,
+        command,
+        run,
+        tail,
+    } = recorder;
+    let result = journal.append_next(tail, |id| JournalEntry {
+        id,
+        run: *run.get_or_insert(id),
+        timestamp: Utc::now(),
+        command: command.clone(),
+        operation,
+    });
+    if let Err(e) = result {
+        eprintln!("Warning: failed to write journal: {:#}", e);
+    }
+}
+
+/// Record a metadata write, if any editable field changed
+pub fn record_metadata(file: &Path, before: &AudiobookMetadata, after: &AudiobookMetadata) {
+    let changes = field_edits(before, after);
+    if !changes.is_empty() {
+        record(Operation::Metadata {
+            file: absolute_path(file),
+            changes,
+        });
+    }
+}
+
+/// Record a file move
+pub fn record_move(from: &Path, to: &Path) {
+    record(Operation::Move {
+        from: absolute_path(from),
+        to: absolute_path(to),
+    });
+}
+
+/// Record a file copy
+pub fn record_copy(from: &Path, to: &Path, sha256: Option<&str>) {
+    record(Operation::Copy {
+        from: absolute_path(from),
+        to: absolute_path(to),
+        sha256: sha256.map(String::from),
+    });
+}
+
+/// Record a file deletion
+pub fn record_remove(path: &Path) {
+    record(Operation::Remove {
+        path: absolute_path(path),
+    });
+}
+
+/// Editable fields whose value differs between two snapshots
+pub fn field_edits(before: &AudiobookMetadata, after: &AudiobookMetadata) -> Vec<FieldEdit> {
+    EDITABLE_FIELDS
+        .iter()
+        .filter_map(|&field| {
+            let old = before.get_field(field);
+            let new = after.get_field(field);
+            (old != new).then(|| FieldEdit {
+                field: field.to_string(),
+                before: old,
+                after: new,
+            })
+        })
+        .collect()
+}
+
+impl Journal {
+    /// The journal in the user's data directory
+    pub fn open() -> Result<Self> {
+        let dir = dirs::data_dir()
+            .context("Could not determine data directory")?
+            .join("audiobookctl");
+        Ok(Self::at(dir.join("journal.jsonl")))
+    }
+
+    /// A journal at a specific path
+    pub fn at(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// All entries, oldest first
+    pub fn entries(&self) -> Result<Vec<JournalEntry>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read journal {:?}", self.path))?;
+
+        // A torn last line (interrupted write) shouldn't make the journal unusable
+        let mut entries = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => warn!("{:?} line {}: invalid entry: {}", self.path, i + 1, e),
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Whether an entry has been undone
+    pub fn is_undone(entries: &[JournalEntry], id: u64) -> bool {
+        entries
+            .iter()
+            .any(|e| e.operation == Operation::Undo { undone: id })
+    }
+
+    /// Where a file recorded in entry `id` is now, following later moves
+    pub fn current_path(entries: &[JournalEntry], id: u64, path: &Path) -> PathBuf {
+        let mut current = path.to_path_buf();
+        for entry in entries.iter().filter(|e| e.id > id) {
+            if let Operation::Move { from, to } = &entry.operation {
+                if *from == current {
+                    current = to.clone();
+                }
+            }
+        }
+        current
+    }
+
+    /// Append an entry with the next free id.
+    ///
+    /// Holds the journal lock so concurrent runs can't hand out the same id.
+    /// `tail` remembers where the journal ended after the previous append;
+    /// only entries written since (by other runs) are read to find the last id.
+    fn append_next(
+        &self,
+        tail: &mut Option<Tail>,
+        entry: impl FnOnce(u64) -> JournalEntry,
+    ) -> Result<()> {
+        let _lock = lockfile::lock_shared(&self.lock_path())?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open journal {:?}", self.path))?;
+
+        let len = file.metadata()?.len();
+        let last_id = match *tail {
+            Some(t) if t.len == len => t.id,
+            Some(t) if t.len < len => last_id_from(&mut file, t.len)?.unwrap_or(t.id),
+            _ => last_id_from(&mut file, 0)?.unwrap_or(0),
+        };
+
+        let entry = entry(last_id + 1);
+        writeln!(file, "{}", serde_json::to_string(&entry)?)
+            .with_context(|| format!("Failed to write journal {:?}", self.path))?;
+        *tail = Some(Tail {
+            id: entry.id,
+            len: file.metadata()?.len(),
+        });
+        Ok(())
+    }
+
+    fn lock_path(&self) -> PathBuf {
+        self.path.with_extension("jsonl.lock")
+    }
+}
+
+/// Id of the last valid entry at or after byte `offset`
+fn last_id_from(file: &mut File, offset: u64) -> Result<Option<u64>> {
+    let mut content = String::new();
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_to_string(&mut content)?;
+    Ok(content
+        .lines()
+        .rev()
+        .find_map(|line| serde_json::from_str::<JournalEntry>(line).ok())
+        .map(|e| e.id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(id: u64, operation: Operation) -> JournalEntry {
+        JournalEntry {
+            id,
+            run: id,
+            timestamp: Utc::now(),
+            command: "fix".to_string(),
+            operation,
+        }
+    }
+
+    #[test]
+    fn test_append_and_read() {
+        let temp = TempDir::new().unwrap();
+        let journal = Journal::at(temp.path().join("data").join("journal.jsonl"));
+        let mut tail = None;
+
+        let first = entry(
+            1,
+            Operation::Metadata {
+                file: PathBuf::from("/lib/book.m4b"),
+                changes: vec![FieldEdit {
+                    field: "title".to_string(),
+                    before: None,
+                    after: Some("Leviathan Wakes".to_string()),
+                }],
+            },
+        );
+        let second = entry(
+            2,
+            Operation::Copy {
+                from: PathBuf::from("/in/book.m4b"),
+                to: PathBuf::from("/lib/book.m4b"),
+                sha256: None,
+            },
+        );
+        journal.append_next(&mut tail, |_| first.clone()).unwrap();
+        journal
+            .append_next(&mut tail, |id| {
+                assert_eq!(id, 2);
+                second.clone()
+            })
+            .unwrap();
+
+        assert_eq!(journal.entries().unwrap(), vec![first, second]);
+        assert_eq!(tail.map(|t| t.id), Some(2));
+    }
+
+    #[test]
+    fn test_append_next_sees_other_runs() {
+        let temp = TempDir::new().unwrap();
+        let journal = Journal::at(temp.path().join("journal.jsonl"));
+        let remove = |id| {
+            entry(
+                id,
+                Operation::Remove {
+                    path: PathBuf::from("/lib/book.m4b"),
+                },
+            )
+        };
+
+        // Two runs appending in turn never reuse an id
+        let (mut ours, mut theirs) = (None, None);
+        journal.append_next(&mut ours, remove).unwrap();
+        journal.append_next(&mut theirs, remove).unwrap();
+        journal.append_next(&mut ours, remove).unwrap();
+        journal.append_next(&mut ours, remove).unwrap();
+
+        let ids: Vec<u64> = journal.entries().unwrap().iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_current_path_follows_later_moves() {
+        let entries = vec![
+            entry(
+                1,
+                Operation::Metadata {
+                    file: PathBuf::from("/in/a.m4b"),
+                    changes: vec![],
+                },
+            ),
+            entry(
+                2,
+                Operation::Move {
+                    from: PathBuf::from("/in/a.m4b"),
+                    to: PathBuf::from("/lib/a.m4b"),
+                },
+            ),
+            entry(
+                3,
+                Operation::Move {
+                    from: PathBuf::from("/lib/a.m4b"),
+                    to: PathBuf::from("/lib/Author/a.m4b"),
+                },
+            ),
+            entry(4, Operation::Undo { undone: 1 }),
+        ];
+
+        assert_eq!(
+            Journal::current_path(&entries, 1, Path::new("/in/a.m4b")),
+            PathBuf::from("/lib/Author/a.m4b")
+        );
+        assert_eq!(
+            Journal::current_path(&entries, 3, Path::new("/lib/Author/a.m4b")),
+            PathBuf::from("/lib/Author/a.m4b")
+        );
+        assert!(Journal::is_undone(&entries, 1));
+        assert!(!Journal::is_undone(&entries, 2));
+    }
+
+    #[test]
+    fn test_field_edits() {
+        let before = AudiobookMetadata {
+            title: Some("Leviathan Wakes".to_string()),
+            year: Some(2011),
+            ..Default::default()
+        };
+        let after = AudiobookMetadata {
+            title: Some("Leviathan Wakes".to_string()),
+            series: Some("The Expanse".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            field_edits(&before, &after),
+            vec![
+                FieldEdit {
+                    field: "series".to_string(),
+                    before: None,
+                    after: Some("The Expanse".to_string()),
+                },
+                FieldEdit {
+                    field: "year".to_string(),
+                    before: Some("2011".to_string()),
+                    after: None,
+                },
+            ]
+        );
+    }
+}
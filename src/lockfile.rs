@@ -0,0 +1,202 @@
+//! Advisory locks between audiobookctl processes
+//!
+//! Modifying commands lock the library they work on, and metadata writes lock
+//! the file being written, so two runs (say `lookup-all` and `fix --no-dry-run`)
+//! can't touch the same files or `.audiobookctl.db` at once. Lock files live in
+//! `~/.cache/audiobookctl/locks/`, named after the hash of the locked path, and
+//! record the PID and command holding them for error messages.
+//!
+//! Locking is enabled by `configure` (called once by `main`); without it,
+//! locks are no-ops.
+
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Database file marking a library root
+const DB_FILENAME: &str = ".audiobookctl.db";
+
+/// How this process takes locks
+#[derive(Debug, Clone)]
+struct Settings {
+    dir: PathBuf,
+    command: String,
+    wait: bool,
+}
+
+static SETTINGS: Mutex<Option<Settings>> = Mutex::new(None);
+
+/// Enable locking for the rest of the process
+pub fn configure(command: &str, wait: bool) -> Result<()> {
+    let dir = dirs::cache_dir()
+        .context("Could not determine cache directory")?
+        .join("audiobookctl")
+        .join("locks");
+    *SETTINGS.lock().unwrap_or_else(|e| e.into_inner()) = Some(Settings {
+        dir,
+        command: command.to_string(),
+        wait,
+    });
+    Ok(())
+}
+
+/// A held lock, released when dropped
+#[derive(Debug)]
+pub struct Lock {
+    _file: Option<File>,
+}
+
+/// Lock the library containing `dir` (the directory holding its
+/// `.audiobookctl.db`, or `dir` itself if it has none)
+pub fn lock_library(dir: &Path) -> Result<Lock> {
+    let root = library_root(&crate::safety::absolute_path(dir));
+    lock_path("library", &root, &format!("library {}", root.display()))
+}
+
+/// Lock a single file
+pub fn lock_file(path: &Path) -> Result<Lock> {
+    let path = crate::safety::absolute_path(path);
+    lock_path("file", &path, &path.display().to_string())
+}
+
+/// Lock a file shared by all runs (the journal, the backup store index) for a
+/// short read-modify-write. Unlike library and file locks this is always on
+/// and always waits, since it is only held for a moment.
+pub fn lock_shared(lock_file: &Path) -> Result<Lock> {
+    if let Some(parent) = lock_file.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory {:?}", parent))?;
+    }
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_file)
+        .with_context(|| format!("Failed to open lock file {:?}", lock_file))?;
+    file.lock()
+        .with_context(|| format!("Failed to lock {:?}", lock_file))?;
+    Ok(Lock { _file: Some(file) })
+}
+
+fn lock_path(kind: &str, path: &Path, description: &str) -> Result<Lock> {
+    let Some(settings) = SETTINGS.lock().unwrap_or_else(|e| e.into_inner()).clone() else {
+        return Ok(Lock { _file: None });
+    };
+
+    let hash = hex::encode(Sha256::digest(path.as_os_str().as_encoded_bytes()));
+    let lock_file = settings.dir.join(format!("{}-{}.lock", kind, &hash[..16]));
+    acquire(&lock_file, description, &settings)
+}
+
+fn acquire(lock_file: &Path, description: &str, settings: &Settings) -> Result<Lock> {
+    if let Some(parent) = lock_file.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory {:?}", parent))?;
+    }
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_file)
+        .with_context(|| format!("Failed to open lock file {:?}", lock_file))?;
+
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            let holder = holder(&mut file);
+            if !settings.wait {
+                bail!(
+                    "{} is locked by {} (use --wait to wait for it)",
+                    description,
+                    holder
+                );
+            }
+            eprintln!("Waiting for {} (locked by {})...", description, holder);
+            file.lock()
+                .with_context(|| format!("Failed to lock {:?}", lock_file))?;
+        }
+        Err(TryLockError::Error(e)) => {
+            return Err(e).with_context(|| format!("Failed to lock {:?}", lock_file));
+        }
+    }
+
+    // Tell anyone waiting on us who we are
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    writeln!(file, "{}\n{}", std::process::id(), settings.command)?;
+
+    Ok(Lock { _file: Some(file) })
+}
+
+/// Who holds a lock, from the lock file's contents
+fn holder(file: &mut File) -> String {
+    let mut content = String::new();
+    let _ = file
+        .seek(SeekFrom::Start(0))
+        .and_then(|_| file.read_to_string(&mut content));
+    let mut lines = content.lines();
+
+    match (lines.next(), lines.next()) {
+        (Some(pid), Some(command)) => {
+            format!("PID {} running `audiobookctl {}`", pid.trim(), command)
+        }
+        _ => "another audiobookctl process".to_string(),
+    }
+}
+
+/// The nearest directory at or above `dir` with a library database
+fn library_root(dir: &Path) -> PathBuf {
+    dir.ancestors()
+        .find(|d| d.join(DB_FILENAME).exists())
+        .unwrap_or(dir)
+        .to_path_buf()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn settings(dir: &Path, wait: bool) -> Settings {
+        Settings {
+            dir: dir.to_path_buf(),
+            command: "fix".to_string(),
+            wait,
+        }
+    }
+
+    #[test]
+    fn test_second_lock_fails_with_holder() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("library.lock");
+        let settings = settings(temp.path(), false);
+
+        let held = acquire(&path, "library /books", &settings).unwrap();
+        let err = acquire(&path, "library /books", &settings).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "library /books is locked by PID {} running `audiobookctl fix` (use --wait to wait for it)",
+                std::process::id()
+            )
+        );
+
+        drop(held);
+        assert!(acquire(&path, "library /books", &settings).is_ok());
+    }
+
+    #[test]
+    fn test_library_root_finds_database() {
+        let temp = TempDir::new().unwrap();
+        let nested = temp.path().join("Author").join("Book");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(library_root(&nested), nested);
+
+        fs::write(temp.path().join(DB_FILENAME), "").unwrap();
+        assert_eq!(library_root(&nested), temp.path());
+    }
+}
@@ -1,5 +1,6 @@
 //! API clients for Audible, Audnexus, and Open Library
 
+use crate::lookup::html::html_to_text;
 use anyhow::{Context, Result};
 use serde::Deserialize;
 use tracing::warn;
@@ -29,6 +30,7 @@ struct AudibleProduct {
     publisher_name: Option<String>,
     publisher_summary: Option<String>,
     release_date: Option<String>,
+    isbn: Option<String>,
     #[allow(dead_code)]
     runtime_length_min: Option<u32>,
 }
@@ -39,7 +41,7 @@ struct AudiblePerson {
 }
 
 /// Result from a single API source
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct LookupResult {
     pub source: String,
     pub title: Option<String>,
@@ -53,6 +55,8 @@ pub struct LookupResult {
     pub genre: Option<String>,
     pub isbn: Option<String>,
     pub asin: Option<String>,
+    /// Every genre the source reported, most relevant first (see `lookup::genres`)
+    pub genre_candidates: Vec<String>,
 }
 
 // ============================================================================
@@ -75,6 +79,7 @@ struct AudnexusBook {
     #[serde(default)]
     genres: Vec<AudnexusGenre>,
     description: Option<String>,
+    isbn: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -91,6 +96,9 @@ struct AudnexusSeries {
 #[derive(Debug, Deserialize)]
 struct AudnexusGenre {
     name: Option<String>,
+    /// "genre" for top-level categories, "tag" for finer-grained ones
+    #[serde(rename = "type")]
+    kind: Option<String>,
 }
 
 /// Search results from Audnexus (array of books)
@@ -211,8 +219,15 @@ fn audnexus_book_to_result(book: AudnexusBook) -> LookupResult {
         (None, None)
     };
 
-    // Extract first genre
-    let genre = book.genres.first().and_then(|g| g.name.clone());
+    // Genres before tags; the first genre is used unless a mapping is configured
+    let mut genres: Vec<&AudnexusGenre> = book.genres.iter().collect();
+    genres.sort_by_key(|g| match g.kind.as_deref() {
+        Some("genre") => 0,
+        Some("tag") => 1,
+        _ => 2,
+    });
+    let genre_candidates: Vec<String> = genres.iter().filter_map(|g| g.name.clone()).collect();
+    let genre = genre_candidates.first().cloned();
 
     // Extract year from release_date (format: "YYYY-MM-DD" or similar)
     let year = book
@@ -228,11 +243,15 @@ fn audnexus_book_to_result(book: AudnexusBook) -> LookupResult {
         series,
         series_position,
         year,
-        description: book.description,
+        description: book
+            .description
+            .map(|d| html_to_text(&d))
+            .filter(|d| !d.is_empty()),
         publisher: book.publisher_name,
         genre,
-        isbn: None, // Audnexus doesn't provide ISBN
+        isbn: book.isbn.filter(|i| !i.trim().is_empty()),
         asin: book.asin,
+        genre_candidates,
     }
 }
 
@@ -329,8 +348,11 @@ fn audible_product_to_result(product: AudibleProduct) -> LookupResult {
         .as_ref()
         .and_then(|d| d.split('-').next()?.parse().ok());
 
-    // Strip HTML from description
-    let description = product.publisher_summary.map(|s| strip_html_tags(&s));
+    // Description is an HTML fragment
+    let description = product
+        .publisher_summary
+        .map(|s| html_to_text(&s))
+        .filter(|d| !d.is_empty());
 
     LookupResult {
         source: "audible".to_string(),
@@ -343,28 +365,12 @@ fn audible_product_to_result(product: AudibleProduct) -> LookupResult {
         description,
         publisher: product.publisher_name,
         genre: None, // Audible search doesn't return genres
-        isbn: None,
+        isbn: product.isbn.filter(|i| !i.trim().is_empty()),
         asin: product.asin,
+        genre_candidates: Vec::new(),
     }
 }
 
-/// Simple HTML tag stripper
-fn strip_html_tags(html: &str) -> String {
-    let mut result = String::new();
-    let mut in_tag = false;
-
-    for c in html.chars() {
-        match c {
-            '<' => in_tag = true,
-            '>' => in_tag = false,
-            _ if !in_tag => result.push(c),
-            _ => {}
-        }
-    }
-
-    result
-}
-
 /// Fetch metadata from Open Library API
 ///
 /// Searches by title/author or ISBN. Returns first result only.
@@ -438,8 +444,9 @@ fn openlibrary_doc_to_result(doc: OpenLibraryDoc) -> LookupResult {
     // Take first ISBN
     let isbn = doc.isbn.into_iter().next();
 
-    // Take first subject as genre
-    let genre = doc.subject.into_iter().next();
+    // Take first subject as genre; all subjects are candidates for mapping
+    let genre = doc.subject.first().cloned();
+    let genre_candidates = doc.subject;
 
     LookupResult {
         source: "openlibrary".to_string(),
@@ -453,6 +460,7 @@ fn openlibrary_doc_to_result(doc: OpenLibraryDoc) -> LookupResult {
         publisher,
         genre,
         isbn,
-        asin: None, // Open Library doesn't provide ASIN
+        asin: None, // Open Library's Amazon IDs are mostly Kindle editions
+        genre_candidates,
     }
 }
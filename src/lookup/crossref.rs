@@ -0,0 +1,158 @@
+//! Cross-referencing identifiers between providers
+//!
+//! A first round of lookups often reports identifiers we did not have:
+//! Audible's keyword search returns an ASIN, Audnexus returns an ISBN.
+//! Feeding those into a second round of exact-ID queries (Audnexus by
+//! ASIN, Open Library by ISBN) converges on precise matches instead of
+//! relying on keyword search alone.
+
+use crate::lookup::identifiers::{is_valid_asin, normalize_isbn};
+use crate::lookup::trusted::{provider_name, TrustedSource};
+use crate::lookup::LookupResult;
+
+/// An identifier learned from a first-round result
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrossRef {
+    pub value: String,
+    /// Source label of the result that reported it
+    pub via: String,
+}
+
+/// ASIN to look up on Audnexus in the second round.
+///
+/// Only applies when Audnexus was not already queried by ASIN. Audible is
+/// the native home of ASINs, so results are checked in order and the first
+/// valid ASIN wins.
+pub fn next_asin(results: &[LookupResult], queried_asin: Option<&str>) -> Option<CrossRef> {
+    if queried_asin.is_some() {
+        return None;
+    }
+
+    results
+        .iter()
+        .filter(|r| !TrustedSource::Audnexus.matches(&r.source))
+        .find_map(|r| {
+            let asin = r.asin.as_deref()?.trim();
+            is_valid_asin(asin).then(|| CrossRef {
+                value: asin.to_string(),
+                via: provider_name(&r.source).to_string(),
+            })
+        })
+}
+
+/// ISBN to look up on Open Library in the second round.
+///
+/// Only applies when Open Library was not already queried by ISBN. ISBNs
+/// reported by Open Library itself are ignored since they come from the
+/// fuzzy match we are trying to replace.
+pub fn next_isbn(results: &[LookupResult], queried_isbn: Option<&str>) -> Option<CrossRef> {
+    if queried_isbn.is_some() {
+        return None;
+    }
+
+    results
+        .iter()
+        .filter(|r| !TrustedSource::Openlibrary.matches(&r.source))
+        .find_map(|r| {
+            let isbn = normalize_isbn(r.isbn.as_deref()?)?;
+            Some(CrossRef {
+                value: isbn,
+                via: provider_name(&r.source).to_string(),
+            })
+        })
+}
+
+/// Replace fuzzy Open Library results with an exact ISBN match
+pub fn replace_fuzzy_openlibrary(results: &mut Vec<LookupResult>, exact: LookupResult) {
+    results.retain(|r| !TrustedSource::Openlibrary.matches(&r.source));
+    results.push(exact);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_result(source: &str, asin: Option<&str>, isbn: Option<&str>) -> LookupResult {
+        LookupResult {
+            source: source.to_string(),
+            title: Some("The Martian".to_string()),
+            author: None,
+            narrator: None,
+            series: None,
+            series_position: None,
+            year: None,
+            description: None,
+            publisher: None,
+            genre: None,
+            isbn: isbn.map(String::from),
+            asin: asin.map(String::from),
+            genre_candidates: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_next_asin_from_audible() {
+        let results = vec![
+            make_result("audible", Some("B00B5HZGUG"), None),
+            make_result("openlibrary", None, Some("9780553418026")),
+        ];
+
+        assert_eq!(
+            next_asin(&results, None),
+            Some(CrossRef {
+                value: "B00B5HZGUG".to_string(),
+                via: "audible".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_next_asin_skipped_when_already_queried() {
+        let results = vec![make_result("audible", Some("B00B5HZGUG"), None)];
+        assert_eq!(next_asin(&results, Some("B08G9PRS1K")), None);
+    }
+
+    #[test]
+    fn test_next_asin_ignores_invalid() {
+        let results = vec![make_result("audible", Some("not-an-asin"), None)];
+        assert_eq!(next_asin(&results, None), None);
+    }
+
+    #[test]
+    fn test_next_isbn_ignores_openlibrary() {
+        let results = vec![
+            make_result("openlibrary", None, Some("9780553418026")),
+            make_result("audnexus (audible ASIN)", None, Some("978-0-8041-3902-1")),
+        ];
+
+        assert_eq!(
+            next_isbn(&results, None),
+            Some(CrossRef {
+                value: "9780804139021".to_string(),
+                via: "audnexus".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_next_isbn_skipped_when_already_queried() {
+        let results = vec![make_result("audnexus", None, Some("9780804139021"))];
+        assert_eq!(next_isbn(&results, Some("9780553418026")), None);
+    }
+
+    #[test]
+    fn test_replace_fuzzy_openlibrary() {
+        let mut results = vec![
+            make_result("audible", None, None),
+            make_result("openlibrary", None, Some("0000000000")),
+        ];
+
+        replace_fuzzy_openlibrary(
+            &mut results,
+            make_result("openlibrary (audnexus ISBN)", None, Some("9780804139021")),
+        );
+
+        let sources: Vec<&str> = results.iter().map(|r| r.source.as_str()).collect();
+        assert_eq!(sources, vec!["audible", "openlibrary (audnexus ISBN)"]);
+    }
+}
@@ -0,0 +1,178 @@
+//! Genre taxonomy mapping
+//!
+//! Sources report genres in their own vocabulary: Open Library subjects
+//! ("Fiction, science fiction, general"), Audnexus genres and tags. The
+//! `[genres]` config maps them onto the library's own list before merging.
+
+use crate::config::GenresConfig;
+use crate::lookup::LookupResult;
+
+/// Map genre candidates (most relevant first) through the configured taxonomy.
+///
+/// Each candidate is mapped (case-insensitively) to zero or more genres,
+/// filtered by the whitelist, deduplicated and capped at `max`. Returns
+/// `None` when nothing survives.
+pub fn map_genres(candidates: &[String], config: &GenresConfig) -> Option<String> {
+    let mut genres: Vec<String> = Vec::new();
+
+    for candidate in candidates
+        .iter()
+        .map(|c| c.trim())
+        .filter(|c| !c.is_empty())
+    {
+        let mapped: Vec<&str> = match lookup_map(candidate, config) {
+            Some(targets) => targets.iter().map(String::as_str).collect(),
+            None => vec![candidate],
+        };
+
+        for genre in mapped {
+            let Some(genre) = whitelisted(genre, config) else {
+                continue;
+            };
+            if !genres.iter().any(|g| g.eq_ignore_ascii_case(&genre)) {
+                genres.push(genre);
+            }
+        }
+    }
+
+    genres.truncate(config.max.max(1));
+    (!genres.is_empty()).then(|| genres.join(&config.separator))
+}
+
+/// Replace a result's genre with its mapped genre candidates
+pub fn apply_genre_mapping(result: &mut LookupResult, config: &GenresConfig) {
+    let candidates = if result.genre_candidates.is_empty() {
+        result.genre.iter().cloned().collect()
+    } else {
+        result.genre_candidates.clone()
+    };
+    result.genre = map_genres(&candidates, config);
+}
+
+/// Split a stored genre tag into its genres
+pub fn split_genres(value: &str, config: &GenresConfig) -> Vec<String> {
+    let separator = config.separator.trim();
+    let parts: Vec<&str> = if separator.is_empty() {
+        vec![value]
+    } else {
+        value.split(separator).collect()
+    };
+    parts
+        .into_iter()
+        .map(str::trim)
+        .filter(|g| !g.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn lookup_map<'a>(genre: &str, config: &'a GenresConfig) -> Option<&'a [String]> {
+    config
+        .map
+        .iter()
+        .find(|(source, _)| source.trim().eq_ignore_ascii_case(genre))
+        .map(|(_, target)| target.genres())
+}
+
+/// The whitelist spelling of a genre, or the genre itself without a whitelist
+fn whitelisted(genre: &str, config: &GenresConfig) -> Option<String> {
+    let genre = genre.trim();
+    if genre.is_empty() {
+        return None;
+    }
+    if config.whitelist.is_empty() {
+        return Some(genre.to_string());
+    }
+    config
+        .whitelist
+        .iter()
+        .find(|w| w.trim().eq_ignore_ascii_case(genre))
+        .map(|w| w.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GenreTarget;
+
+    fn config() -> GenresConfig {
+        let mut config = GenresConfig {
+            whitelist: vec![
+                "Science Fiction".to_string(),
+                "Fantasy".to_string(),
+                "Thriller".to_string(),
+            ],
+            max: 2,
+            ..Default::default()
+        };
+        config.map.insert(
+            "Fiction, science fiction, general".to_string(),
+            GenreTarget::One("Science Fiction".to_string()),
+        );
+        config.map.insert(
+            "Science Fiction & Fantasy".to_string(),
+            GenreTarget::Many(vec!["Science Fiction".to_string(), "Fantasy".to_string()]),
+        );
+        config
+            .map
+            .insert("Accessible book".to_string(), GenreTarget::Many(vec![]));
+        config
+    }
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_default_config_keeps_first_candidate() {
+        let config = GenresConfig::default();
+        assert_eq!(
+            map_genres(&strings(&["Accessible book", "Fiction"]), &config),
+            Some("Accessible book".to_string())
+        );
+        assert_eq!(map_genres(&[], &config), None);
+    }
+
+    #[test]
+    fn test_map_and_whitelist() {
+        let config = config();
+        assert_eq!(
+            map_genres(
+                &strings(&["Accessible book", "Fiction, science fiction, general"]),
+                &config
+            ),
+            Some("Science Fiction".to_string())
+        );
+        assert_eq!(
+            map_genres(
+                &strings(&["science fiction & fantasy", "thriller"]),
+                &config
+            ),
+            Some("Science Fiction; Fantasy".to_string())
+        );
+        assert_eq!(
+            map_genres(&strings(&["Accessible book", "Romance"]), &config),
+            None
+        );
+    }
+
+    #[test]
+    fn test_apply_genre_mapping_uses_genre_without_candidates() {
+        let mut result = LookupResult {
+            source: "sidecar:json".to_string(),
+            genre: Some("fantasy".to_string()),
+            ..Default::default()
+        };
+        apply_genre_mapping(&mut result, &config());
+        assert_eq!(result.genre.as_deref(), Some("Fantasy"));
+    }
+
+    #[test]
+    fn test_split_genres() {
+        let config = GenresConfig::default();
+        assert_eq!(
+            split_genres("Science Fiction; Fantasy", &config),
+            strings(&["Science Fiction", "Fantasy"])
+        );
+        assert_eq!(split_genres("Fantasy", &config), strings(&["Fantasy"]));
+    }
+}
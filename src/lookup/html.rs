@@ -0,0 +1,241 @@
+//! HTML-to-text conversion for descriptions
+//!
+//! Audible and Audnexus descriptions are HTML fragments. Paragraphs and line
+//! breaks become blank lines/newlines, list items become "- " lines, and
+//! character entities are decoded. A `<` that doesn't start a tag is kept.
+
+/// Convert an HTML fragment to plain text
+pub(crate) fn html_to_text(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut text = String::new();
+    let mut rest = html;
+
+    while let Some(lt) = rest.find('<') {
+        text.push_str(&rest[..lt]);
+        let candidate = &rest[lt..];
+
+        match parse_tag(candidate) {
+            Some(tag) => {
+                push_text(&mut out, &text);
+                text.clear();
+                rest = &candidate[tag.len..];
+
+                match tag.name.as_str() {
+                    "br" => out.push('\n'),
+                    "li" if !tag.closing => {
+                        if !out.ends_with('\n') {
+                            out.push('\n');
+                        }
+                        out.push_str("- ");
+                    }
+                    "li" => out.push('\n'),
+                    "p" | "div" | "ul" | "ol" | "blockquote" | "section" | "table" | "tr"
+                    | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => out.push_str("\n\n"),
+                    "script" | "style" if !tag.closing => {
+                        // Drop the element's content entirely
+                        let end = format!("</{}", tag.name);
+                        rest = match rest.to_ascii_lowercase().find(&end) {
+                            Some(pos) => &rest[pos..],
+                            None => "",
+                        };
+                    }
+                    _ => {}
+                }
+            }
+            None => {
+                text.push('<');
+                rest = &candidate[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    push_text(&mut out, &text);
+
+    tidy_lines(&out)
+}
+
+/// A parsed tag: lowercase name, whether it is a closing tag, and its length
+struct Tag {
+    name: String,
+    closing: bool,
+    len: usize,
+}
+
+/// Parse a tag or comment at the start of `s` (which starts with '<')
+fn parse_tag(s: &str) -> Option<Tag> {
+    if let Some(comment) = s.strip_prefix("<!--") {
+        let end = comment.find("-->")?;
+        return Some(Tag {
+            name: String::new(),
+            closing: false,
+            len: 4 + end + 3,
+        });
+    }
+
+    let after = &s[1..];
+    let (closing, name_start) = match after.strip_prefix('/') {
+        Some(stripped) => (true, stripped),
+        None => (false, after),
+    };
+
+    // A tag name must follow immediately ("a < b" is text)
+    let first = name_start.chars().next()?;
+    if !first.is_ascii_alphabetic() && first != '!' {
+        return None;
+    }
+
+    let end = s.find('>')?;
+    let name: String = name_start
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_ascii_lowercase();
+
+    Some(Tag {
+        name,
+        closing,
+        len: end + 1,
+    })
+}
+
+/// Append text with HTML whitespace collapsed and entities decoded
+fn push_text(out: &mut String, text: &str) {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut last_space = out.is_empty() || out.ends_with([' ', '\n']);
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !last_space {
+                collapsed.push(' ');
+            }
+            last_space = true;
+        } else {
+            collapsed.push(c);
+            last_space = false;
+        }
+    }
+    out.push_str(&decode_entities(&collapsed));
+}
+
+/// Trim each line and allow at most one blank line between paragraphs
+fn tidy_lines(text: &str) -> String {
+    let mut lines: Vec<&str> = Vec::new();
+    for line in text.lines().map(str::trim) {
+        let line = if line == "-" { "" } else { line };
+        if line.is_empty() && lines.last().is_none_or(|l| l.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/// Decode named and numeric character references (`&amp;`, `&#8217;`, `&#x2019;`)
+pub(crate) fn decode_entities(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(amp) = rest.find('&') {
+        result.push_str(&rest[..amp]);
+        let after = &rest[amp + 1..];
+        let decoded = after
+            .find(';')
+            .filter(|&semi| semi <= 10)
+            .and_then(|semi| decode_entity(&after[..semi]).map(|c| (c, semi)));
+
+        match decoded {
+            Some((c, semi)) => {
+                result.push(c);
+                rest = &after[semi + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = after;
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    let c = match entity {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "lsquo" => '\u{2018}',
+        "rsquo" => '\u{2019}',
+        "ldquo" => '\u{201C}',
+        "rdquo" => '\u{201D}',
+        "ndash" => '\u{2013}',
+        "mdash" => '\u{2014}',
+        "hellip" => '\u{2026}',
+        "copy" => '\u{A9}',
+        "reg" => '\u{AE}',
+        "trade" => '\u{2122}',
+        _ => {
+            return entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|d| d.parse().ok()))
+                .and_then(char::from_u32);
+        }
+    };
+    Some(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paragraphs_and_breaks() {
+        let html = "<p>First paragraph.</p><p>Second<br>line two<br/>line three</p>";
+        assert_eq!(
+            html_to_text(html),
+            "First paragraph.\n\nSecond\nline two\nline three"
+        );
+    }
+
+    #[test]
+    fn test_list_items() {
+        let html = "<p>Includes:</p><ul><li>Book one</li>\n<li> Book two </li></ul><p>Enjoy!</p>";
+        assert_eq!(
+            html_to_text(html),
+            "Includes:\n\n- Book one\n- Book two\n\nEnjoy!"
+        );
+    }
+
+    #[test]
+    fn test_entities_and_literal_angle_brackets() {
+        assert_eq!(
+            html_to_text("<b>Rock &amp; roll</b> isn&#8217;t dead &mdash; 3 < 5 &unknown; AT&T"),
+            "Rock & roll isn\u{2019}t dead \u{2014} 3 < 5 &unknown; AT&T"
+        );
+    }
+
+    #[test]
+    fn test_whitespace_comments_and_scripts() {
+        let html = "  <!-- note -->Some\n   text<script>var x = '<p>';</script>  here ";
+        assert_eq!(html_to_text(html), "Some text here");
+    }
+
+    #[test]
+    fn test_plain_text_unchanged() {
+        assert_eq!(html_to_text("Just a description."), "Just a description.");
+    }
+
+    #[test]
+    fn test_decode_entities() {
+        assert_eq!(decode_entities("a &amp; b"), "a & b");
+        assert_eq!(decode_entities("&#8217;&#x2019;"), "\u{2019}\u{2019}");
+        assert_eq!(decode_entities("AT&T"), "AT&T");
+    }
+}
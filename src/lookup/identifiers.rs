@@ -0,0 +1,479 @@
+//! Identifier discovery (ASIN / ISBN)
+//!
+//! Audible audiobook files often carry their ASIN in the filename, the
+//! parent directory name, a `.cue`/`.nfo` file or the comment atom. Each
+//! discovered identifier records where it came from so lookups can prefer
+//! the most authoritative one and explain why a match was made.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Kind of identifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IdentifierKind {
+    Asin,
+    Isbn,
+}
+
+impl fmt::Display for IdentifierKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdentifierKind::Asin => write!(f, "ASIN"),
+            IdentifierKind::Isbn => write!(f, "ISBN"),
+        }
+    }
+}
+
+/// Where an identifier was found, ordered from most to least authoritative
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IdentifierSource {
+    /// The m4b's own filename
+    Filename,
+    /// An embedded ASIN/ISBN tag
+    Tag,
+    /// A `.cue` or `.nfo` file next to the m4b
+    AuxiliaryFile,
+    /// The embedded comment atom
+    Comment,
+    /// A parent directory name
+    Directory,
+}
+
+impl IdentifierSource {
+    /// Short label used in lookup source names, e.g. "audnexus (filename ASIN)"
+    pub fn label(&self) -> &'static str {
+        match self {
+            IdentifierSource::Filename => "filename",
+            IdentifierSource::Tag => "tag",
+            IdentifierSource::AuxiliaryFile => "aux file",
+            IdentifierSource::Comment => "comment",
+            IdentifierSource::Directory => "directory",
+        }
+    }
+}
+
+/// An identifier together with its provenance
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identifier {
+    pub kind: IdentifierKind,
+    pub value: String,
+    pub source: IdentifierSource,
+    /// Human-readable origin, e.g. `filename` or `book.cue`
+    pub origin: String,
+}
+
+impl fmt::Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} (from {})", self.kind, self.value, self.origin)
+    }
+}
+
+/// Pick the most authoritative identifier of a kind
+pub fn best_identifier(identifiers: &[Identifier], kind: IdentifierKind) -> Option<&Identifier> {
+    identifiers
+        .iter()
+        .filter(|id| id.kind == kind)
+        .min_by_key(|id| id.source)
+}
+
+/// Discover all identifiers for an audiobook file.
+///
+/// `tag_asin`/`tag_isbn` are the values already read from the file's tags.
+/// Results are sorted by authority and deduplicated (the most authoritative
+/// origin of each value wins).
+pub fn discover_identifiers(
+    path: &Path,
+    tag_asin: Option<&str>,
+    tag_isbn: Option<&str>,
+) -> Vec<Identifier> {
+    let mut found = Vec::new();
+
+    // Filename
+    if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+        push_from_text(&mut found, stem, IdentifierSource::Filename, "filename");
+    }
+
+    // Embedded tags (trusted as written, including numeric ASINs)
+    if let Some(asin) = tag_asin.map(str::trim).filter(|a| is_plausible_tag_asin(a)) {
+        found.push(Identifier {
+            kind: IdentifierKind::Asin,
+            value: asin.to_string(),
+            source: IdentifierSource::Tag,
+            origin: "ASIN tag".to_string(),
+        });
+    }
+    if let Some(isbn) = tag_isbn.and_then(normalize_isbn) {
+        found.push(Identifier {
+            kind: IdentifierKind::Isbn,
+            value: isbn,
+            source: IdentifierSource::Tag,
+            origin: "ISBN tag".to_string(),
+        });
+    }
+
+    // .cue / .nfo files next to the m4b
+    for aux in auxiliary_files(path) {
+        if let Ok(bytes) = fs::read(&aux) {
+            let name = aux
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let text = String::from_utf8_lossy(&bytes);
+            push_from_text(&mut found, &text, IdentifierSource::AuxiliaryFile, &name);
+        }
+    }
+
+    // Comment atom
+    if let Ok(tag) = mp4ameta::Tag::read_from_path(path) {
+        for comment in tag.comments() {
+            push_from_text(&mut found, comment, IdentifierSource::Comment, "comment");
+        }
+    }
+
+    // Parent directories (nearest first, at most three levels)
+    let mut dir = path.parent();
+    for _ in 0..3 {
+        let Some(d) = dir else { break };
+        if let Some(name) = d.file_name().and_then(|n| n.to_str()) {
+            let origin = format!("directory \"{}\"", name);
+            push_from_text(&mut found, name, IdentifierSource::Directory, &origin);
+        }
+        dir = d.parent();
+    }
+
+    // Stable sort keeps discovery order within a source
+    found.sort_by_key(|id| id.source);
+    let mut seen = std::collections::HashSet::new();
+    found.retain(|id| seen.insert((id.kind, id.value.clone())));
+    found
+}
+
+/// `.cue`/`.nfo` files that belong to this m4b.
+///
+/// Files sharing the m4b's stem always count; other ones only when the
+/// m4b is the sole audiobook in its directory.
+fn auxiliary_files(path: &Path) -> Vec<std::path::PathBuf> {
+    let Some(dir) = path.parent() else {
+        return Vec::new();
+    };
+    let stem = path.file_stem().map(|s| s.to_os_string());
+
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let entries: Vec<_> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+
+    let is_ext = |p: &Path, ext: &str| {
+        p.extension()
+            .is_some_and(|e| e.to_string_lossy().eq_ignore_ascii_case(ext))
+    };
+    let sole_book = entries.iter().filter(|p| is_ext(p, "m4b")).count() <= 1;
+
+    let mut files: Vec<_> = entries
+        .into_iter()
+        .filter(|p| p.is_file() && (is_ext(p, "cue") || is_ext(p, "nfo")))
+        .filter(|p| sole_book || p.file_stem().map(|s| s.to_os_string()) == stem)
+        .collect();
+    files.sort();
+    files
+}
+
+/// Scan free text for ASINs and ISBNs
+fn push_from_text(found: &mut Vec<Identifier>, text: &str, source: IdentifierSource, origin: &str) {
+    for asin in find_asins(text) {
+        found.push(Identifier {
+            kind: IdentifierKind::Asin,
+            value: asin,
+            source,
+            origin: origin.to_string(),
+        });
+    }
+    for isbn in find_isbns(text) {
+        found.push(Identifier {
+            kind: IdentifierKind::Isbn,
+            value: isbn,
+            source,
+            origin: origin.to_string(),
+        });
+    }
+}
+
+/// Extract ASIN from a filename if present.
+///
+/// The ASIN may appear anywhere in the name as its own token, e.g.
+/// `B08G9PRS1K_name.m4b`, `[B08G9PRS1K] name.m4b` or `name-B08G9PRS1K.m4b`.
+pub fn extract_asin_from_filename(path: &Path) -> Option<String> {
+    let filename = path.file_stem()?.to_str()?;
+    find_asins(filename).into_iter().next()
+}
+
+/// Find all ASIN-shaped tokens in text
+fn find_asins(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|token| is_valid_asin(token))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Check if a string is a valid ASIN
+///
+/// Audible ASINs are 10 uppercase alphanumeric characters starting with "B".
+/// At least one digit is required so that ordinary words are not matched.
+/// (Numeric ASINs are ISBN-10s and are picked up as ISBNs instead.)
+pub fn is_valid_asin(s: &str) -> bool {
+    s.len() == 10
+        && s.starts_with('B')
+        && s.chars()
+            .all(|c| c.is_ascii_digit() || c.is_ascii_uppercase())
+        && s.chars().any(|c| c.is_ascii_digit())
+}
+
+/// Check if an ASIN stored in a tag is usable
+///
+/// Unlike tokens found in paths, a tag value was put there deliberately, so
+/// any 10 alphanumeric characters are accepted (older ASINs are ISBN-10s).
+fn is_plausible_tag_asin(s: &str) -> bool {
+    s.len() == 10 && s.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Find all checksum-valid ISBNs in text, normalized to bare digits
+fn find_isbns(text: &str) -> Vec<String> {
+    let mut isbns = Vec::new();
+
+    for chunk in text.split(|c: char| !(c.is_ascii_digit() || c == '-' || c == 'X' || c == 'x')) {
+        // Try the whole hyphenated chunk first, then its parts
+        let whole = chunk.trim_matches('-');
+        if let Some(isbn) = normalize_isbn(whole) {
+            isbns.push(isbn);
+            continue;
+        }
+        for part in whole.split('-') {
+            if let Some(isbn) = normalize_isbn(part) {
+                isbns.push(isbn);
+            }
+        }
+    }
+
+    isbns
+}
+
+/// Strip hyphens/spaces and validate an ISBN-10 or ISBN-13 checksum
+pub fn normalize_isbn(s: &str) -> Option<String> {
+    let cleaned: String = s
+        .chars()
+        .filter(|c| !matches!(c, '-' | ' '))
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+
+    let valid = match cleaned.len() {
+        10 => is_valid_isbn10(&cleaned),
+        13 => is_valid_isbn13(&cleaned),
+        _ => false,
+    };
+    valid.then_some(cleaned)
+}
+
+fn is_valid_isbn10(s: &str) -> bool {
+    let mut sum = 0;
+    for (i, c) in s.chars().enumerate() {
+        let value = match c {
+            '0'..='9' => c as u32 - '0' as u32,
+            'X' if i == 9 => 10,
+            _ => return false,
+        };
+        sum += value * (10 - i as u32);
+    }
+    sum % 11 == 0
+}
+
+fn is_valid_isbn13(s: &str) -> bool {
+    if !(s.starts_with("978") || s.starts_with("979")) {
+        return false;
+    }
+    let mut sum = 0;
+    for (i, c) in s.chars().enumerate() {
+        let Some(digit) = c.to_digit(10) else {
+            return false;
+        };
+        sum += if i % 2 == 0 { digit } else { digit * 3 };
+    }
+    sum % 10 == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_extract_asin_prefix_underscore() {
+        let path = PathBuf::from("B08G9PRS1K_The_Martian.m4b");
+        assert_eq!(
+            extract_asin_from_filename(&path),
+            Some("B08G9PRS1K".to_string())
+        );
+    }
+
+    #[test]
+    fn test_extract_asin_brackets() {
+        let path = PathBuf::from("[B08G9PRS1K] The Martian.m4b");
+        assert_eq!(
+            extract_asin_from_filename(&path),
+            Some("B08G9PRS1K".to_string())
+        );
+    }
+
+    #[test]
+    fn test_extract_asin_suffix_hyphen() {
+        let path = PathBuf::from("The Martian-B08G9PRS1K.m4b");
+        assert_eq!(
+            extract_asin_from_filename(&path),
+            Some("B08G9PRS1K".to_string())
+        );
+    }
+
+    #[test]
+    fn test_extract_asin_middle_of_name() {
+        let path = PathBuf::from("Andy Weir - B08G9PRS1K - The Martian.m4b");
+        assert_eq!(
+            extract_asin_from_filename(&path),
+            Some("B08G9PRS1K".to_string())
+        );
+    }
+
+    #[test]
+    fn test_no_asin_in_filename() {
+        let path = PathBuf::from("The Martian.m4b");
+        assert_eq!(extract_asin_from_filename(&path), None);
+    }
+
+    #[test]
+    fn test_invalid_asin_wrong_length() {
+        let path = PathBuf::from("B08G9_The_Martian.m4b");
+        assert_eq!(extract_asin_from_filename(&path), None);
+    }
+
+    #[test]
+    fn test_invalid_asin_wrong_prefix() {
+        let path = PathBuf::from("A08G9PRS1K_The_Martian.m4b");
+        assert_eq!(extract_asin_from_filename(&path), None);
+    }
+
+    #[test]
+    fn test_is_valid_asin() {
+        assert!(is_valid_asin("B08G9PRS1K"));
+        assert!(is_valid_asin("B00DEKJ8QM"));
+        assert!(is_valid_asin("B1AB2CD3EF")); // newer non-B0 ASINs
+        assert!(!is_valid_asin("12345")); // too short
+        assert!(!is_valid_asin("A08G9PRS1K")); // wrong prefix
+        assert!(!is_valid_asin("B08G9PRS1K!")); // non-alphanumeric
+        assert!(!is_valid_asin("BLACKSMITH")); // plain word
+        assert!(!is_valid_asin("b08g9prs1k")); // lowercase
+    }
+
+    #[test]
+    fn test_normalize_isbn() {
+        assert_eq!(
+            normalize_isbn("978-0-553-41802-6"),
+            Some("9780553418026".to_string())
+        );
+        assert_eq!(normalize_isbn("0553418025"), Some("0553418025".to_string()));
+        assert_eq!(normalize_isbn("080442957X"), Some("080442957X".to_string()));
+        assert_eq!(normalize_isbn("9780553418027"), None); // bad checksum
+        assert_eq!(normalize_isbn("0553418026"), None); // bad checksum
+        assert_eq!(normalize_isbn("1234567890123"), None); // wrong prefix
+    }
+
+    #[test]
+    fn test_find_isbns_in_text() {
+        let text = "The Martian (2014) ISBN 978-0-553-41802-6, track 0000000001";
+        assert_eq!(find_isbns(text), vec!["9780553418026".to_string()]);
+    }
+
+    #[test]
+    fn test_discover_identifiers_sources_and_priority() {
+        let temp = TempDir::new().unwrap();
+        let book_dir = temp.path().join("The Martian [B00B5HZGUG]");
+        fs::create_dir(&book_dir).unwrap();
+        let m4b = book_dir.join("The Martian - B08G9PRS1K.m4b");
+        fs::write(&m4b, b"").unwrap();
+        fs::write(
+            book_dir.join("The Martian.cue"),
+            "REM ASIN B017V4IM1G\nREM ISBN 9780553418026\n",
+        )
+        .unwrap();
+
+        let ids = discover_identifiers(&m4b, None, None);
+
+        let asin = best_identifier(&ids, IdentifierKind::Asin).unwrap();
+        assert_eq!(asin.value, "B08G9PRS1K");
+        assert_eq!(asin.source, IdentifierSource::Filename);
+
+        let isbn = best_identifier(&ids, IdentifierKind::Isbn).unwrap();
+        assert_eq!(isbn.value, "9780553418026");
+        assert_eq!(isbn.source, IdentifierSource::AuxiliaryFile);
+        assert_eq!(isbn.origin, "The Martian.cue");
+
+        assert!(ids
+            .iter()
+            .any(|id| id.value == "B00B5HZGUG" && id.source == IdentifierSource::Directory));
+        assert!(ids
+            .iter()
+            .any(|id| id.value == "B017V4IM1G" && id.source == IdentifierSource::AuxiliaryFile));
+    }
+
+    #[test]
+    fn test_discover_identifiers_dedupes_keeping_most_authoritative() {
+        let temp = TempDir::new().unwrap();
+        let book_dir = temp.path().join("B08G9PRS1K");
+        fs::create_dir(&book_dir).unwrap();
+        let m4b = book_dir.join("book.m4b");
+        fs::write(&m4b, b"").unwrap();
+
+        let ids = discover_identifiers(&m4b, Some("B08G9PRS1K"), None);
+        let matching: Vec<_> = ids.iter().filter(|id| id.value == "B08G9PRS1K").collect();
+        assert_eq!(matching.len(), 1);
+        assert_eq!(matching[0].source, IdentifierSource::Tag);
+    }
+
+    #[test]
+    fn test_discover_identifiers_keeps_numeric_tag_asin() {
+        let temp = TempDir::new().unwrap();
+        let m4b = temp.path().join("book.m4b");
+        fs::write(&m4b, b"").unwrap();
+
+        let ids = discover_identifiers(&m4b, Some("0553418025"), None);
+        let asin = best_identifier(&ids, IdentifierKind::Asin).unwrap();
+        assert_eq!(asin.value, "0553418025");
+        assert_eq!(asin.source, IdentifierSource::Tag);
+
+        // Garbage in the tag is still ignored
+        let ids = discover_identifiers(&m4b, Some("not an asin"), None);
+        assert!(best_identifier(&ids, IdentifierKind::Asin).is_none());
+    }
+
+    #[test]
+    fn test_auxiliary_files_only_matching_stem_when_shared_dir() {
+        let temp = TempDir::new().unwrap();
+        let a = temp.path().join("a.m4b");
+        fs::write(&a, b"").unwrap();
+        fs::write(temp.path().join("b.m4b"), b"").unwrap();
+        fs::write(temp.path().join("a.nfo"), "").unwrap();
+        fs::write(temp.path().join("b.cue"), "").unwrap();
+
+        assert_eq!(auxiliary_files(&a), vec![temp.path().join("a.nfo")]);
+    }
+
+    #[test]
+    fn test_identifier_display() {
+        let id = Identifier {
+            kind: IdentifierKind::Asin,
+            value: "B08G9PRS1K".to_string(),
+            source: IdentifierSource::Filename,
+            origin: "filename".to_string(),
+        };
+        assert_eq!(id.to_string(), "ASIN B08G9PRS1K (from filename)");
+    }
+}
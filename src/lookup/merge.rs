@@ -1,11 +1,14 @@
 //! Merge logic for combining API results
 
-use crate::lookup::LookupResult;
+use crate::config::GenresConfig;
 use crate::lookup::TrustedSource;
-use crate::metadata::AudiobookMetadata;
+use crate::lookup::{apply_genre_mapping, LookupResult};
+use crate::metadata::{AudiobookMetadata, EDITABLE_FIELDS};
+use crate::names::NameRegistry;
+use serde::{Deserialize, Serialize};
 
 /// Represents a field's merged state
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum FieldValue {
     /// All sources agree on this value
     Agreed { value: String, sources: Vec<String> },
@@ -19,7 +22,7 @@ pub enum FieldValue {
 }
 
 /// Merged metadata with conflict information
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MergedMetadata {
     pub title: FieldValue,
     pub author: FieldValue,
@@ -35,6 +38,41 @@ pub struct MergedMetadata {
 }
 
 impl MergedMetadata {
+    /// Get a field by name (see `metadata::EDITABLE_FIELDS`)
+    pub fn field(&self, name: &str) -> Option<&FieldValue> {
+        match name {
+            "title" => Some(&self.title),
+            "author" => Some(&self.author),
+            "narrator" => Some(&self.narrator),
+            "series" => Some(&self.series),
+            "series_position" => Some(&self.series_position),
+            "year" => Some(&self.year),
+            "description" => Some(&self.description),
+            "publisher" => Some(&self.publisher),
+            "genre" => Some(&self.genre),
+            "isbn" => Some(&self.isbn),
+            "asin" => Some(&self.asin),
+            _ => None,
+        }
+    }
+
+    fn field_mut(&mut self, name: &str) -> Option<&mut FieldValue> {
+        match name {
+            "title" => Some(&mut self.title),
+            "author" => Some(&mut self.author),
+            "narrator" => Some(&mut self.narrator),
+            "series" => Some(&mut self.series),
+            "series_position" => Some(&mut self.series_position),
+            "year" => Some(&mut self.year),
+            "description" => Some(&mut self.description),
+            "publisher" => Some(&mut self.publisher),
+            "genre" => Some(&mut self.genre),
+            "isbn" => Some(&mut self.isbn),
+            "asin" => Some(&mut self.asin),
+            _ => None,
+        }
+    }
+
     /// Check if all fields either match the file or are empty
     /// Returns the sources that were checked if no changes needed
     pub fn matches_file(&self) -> Option<Vec<String>> {
@@ -86,6 +124,71 @@ impl MergedMetadata {
     }
 }
 
+/// Restrictions on which fields a lookup may change
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MergeOptions {
+    /// Only look up these fields (`None` means all fields)
+    pub fields: Option<Vec<String>>,
+    /// Only fill fields the file has no value for
+    pub only_missing: bool,
+    /// Fields that are never changed (config plus the file's own lock list)
+    pub locked: Vec<String>,
+    /// Canonical author/narrator names
+    pub names: NameRegistry,
+    /// Genre mapping applied to each source's genres
+    pub genres: GenresConfig,
+}
+
+impl MergeOptions {
+    /// Copy of these options with additional locked fields
+    pub fn with_locked(&self, locked: &[String]) -> Self {
+        let mut options = self.clone();
+        for field in locked {
+            if !options.locked.contains(field) {
+                options.locked.push(field.clone());
+            }
+        }
+        options
+    }
+
+    /// Whether a field must keep the file's value
+    fn is_protected(&self, field: &str, existing: &AudiobookMetadata) -> bool {
+        if self.locked.iter().any(|f| f == field) {
+            return true;
+        }
+        if let Some(ref fields) = self.fields {
+            if !fields.iter().any(|f| f == field) {
+                return true;
+            }
+        }
+        self.only_missing && existing.get_field(field).is_some()
+    }
+
+    /// Pin every protected field to the file's current value
+    ///
+    /// Works on stored results too, so a resumed session honours the
+    /// options of the current run.
+    pub fn apply(&self, existing: &AudiobookMetadata, merged: &MergedMetadata) -> MergedMetadata {
+        let mut merged = merged.clone();
+        for &name in EDITABLE_FIELDS {
+            if !self.is_protected(name, existing) {
+                continue;
+            }
+            let pinned = match existing.get_field(name) {
+                Some(value) => FieldValue::Agreed {
+                    value,
+                    sources: vec!["file".to_string()],
+                },
+                None => FieldValue::Empty,
+            };
+            if let Some(field) = merged.field_mut(name) {
+                *field = pinned;
+            }
+        }
+        merged
+    }
+}
+
 /// Merge a single string field from multiple sources
 ///
 /// Existing metadata is treated as a source ("file") and included in conflict detection.
@@ -253,13 +356,73 @@ pub fn merge_results(existing: &AudiobookMetadata, results: &[LookupResult]) ->
     }
 }
 
+/// Merge results with genre mapping and canonical names applied, keeping
+/// protected fields (see `MergeOptions`) at the file's value
+///
+/// Source author/narrator values are replaced by their canonical names. When
+/// the file itself uses a variant spelling, a "names" source proposes the
+/// canonical one and is pre-selected in the conflict.
+pub fn merge_results_with_options(
+    existing: &AudiobookMetadata,
+    results: &[LookupResult],
+    options: &MergeOptions,
+) -> MergedMetadata {
+    let names = &options.names;
+    let mut results: Vec<LookupResult> = results.to_vec();
+    for result in &mut results {
+        apply_genre_mapping(result, &options.genres);
+        result.author = result.author.as_deref().map(|a| names.canonicalize(a));
+        result.narrator = result.narrator.as_deref().map(|n| names.canonicalize(n));
+    }
+
+    let mut canonical = existing.clone();
+    names.apply(&mut canonical);
+    let renamed = |file: &Option<String>, canonical: Option<String>| {
+        canonical.filter(|c| file.as_ref() != Some(c))
+    };
+    let names_result = LookupResult {
+        source: "names".to_string(),
+        author: renamed(&existing.author, canonical.author),
+        narrator: renamed(&existing.narrator, canonical.narrator),
+        ..Default::default()
+    };
+    let has_renames = names_result.author.is_some() || names_result.narrator.is_some();
+    if has_renames {
+        results.push(names_result);
+    }
+
+    let mut merged = merge_results(existing, &results);
+    if has_renames {
+        select_source(&mut merged.author, "names");
+        select_source(&mut merged.narrator, "names");
+    }
+
+    options.apply(existing, &merged)
+}
+
+/// Select the value offered by a source in a conflict, if it offers one
+fn select_source(field: &mut FieldValue, source: &str) {
+    if let FieldValue::Conflicting {
+        selected,
+        alternatives,
+    } = field
+    {
+        if let Some((_, value)) = alternatives
+            .iter()
+            .find(|(sources, _)| sources.iter().any(|s| s == source))
+        {
+            *selected = value.clone();
+        }
+    }
+}
+
 /// Resolve a single field using trusted source
-fn resolve_field_with_trusted(field: &FieldValue, trusted: &str) -> FieldValue {
+fn resolve_field_with_trusted(field: &FieldValue, trusted: TrustedSource) -> FieldValue {
     match field {
         FieldValue::Conflicting { alternatives, .. } => {
             // Find the trusted source's value
             for (sources, value) in alternatives {
-                if sources.iter().any(|s| s == trusted) {
+                if sources.iter().any(|s| trusted.matches(s)) {
                     return FieldValue::Agreed {
                         value: value.clone(),
                         sources: sources.clone(),
@@ -282,20 +445,18 @@ pub fn resolve_with_trusted_source(
     merged: &MergedMetadata,
     trusted: TrustedSource,
 ) -> MergedMetadata {
-    let trusted_str = trusted.as_str();
-
     MergedMetadata {
-        title: resolve_field_with_trusted(&merged.title, trusted_str),
-        author: resolve_field_with_trusted(&merged.author, trusted_str),
-        narrator: resolve_field_with_trusted(&merged.narrator, trusted_str),
-        series: resolve_field_with_trusted(&merged.series, trusted_str),
-        series_position: resolve_field_with_trusted(&merged.series_position, trusted_str),
-        year: resolve_field_with_trusted(&merged.year, trusted_str),
-        description: resolve_field_with_trusted(&merged.description, trusted_str),
-        publisher: resolve_field_with_trusted(&merged.publisher, trusted_str),
-        genre: resolve_field_with_trusted(&merged.genre, trusted_str),
-        isbn: resolve_field_with_trusted(&merged.isbn, trusted_str),
-        asin: resolve_field_with_trusted(&merged.asin, trusted_str),
+        title: resolve_field_with_trusted(&merged.title, trusted),
+        author: resolve_field_with_trusted(&merged.author, trusted),
+        narrator: resolve_field_with_trusted(&merged.narrator, trusted),
+        series: resolve_field_with_trusted(&merged.series, trusted),
+        series_position: resolve_field_with_trusted(&merged.series_position, trusted),
+        year: resolve_field_with_trusted(&merged.year, trusted),
+        description: resolve_field_with_trusted(&merged.description, trusted),
+        publisher: resolve_field_with_trusted(&merged.publisher, trusted),
+        genre: resolve_field_with_trusted(&merged.genre, trusted),
+        isbn: resolve_field_with_trusted(&merged.isbn, trusted),
+        asin: resolve_field_with_trusted(&merged.asin, trusted),
     }
 }
 
@@ -304,29 +465,27 @@ pub fn resolve_with_trusted_source(
 /// Returns true if the trusted source appears in any field's sources.
 /// Used to skip files when trusted source returned no results.
 pub fn has_trusted_source_data(merged: &MergedMetadata, trusted: TrustedSource) -> bool {
-    let trusted_str = trusted.as_str();
-
-    fn field_has_source(field: &FieldValue, source: &str) -> bool {
+    fn field_has_source(field: &FieldValue, trusted: TrustedSource) -> bool {
         match field {
-            FieldValue::Agreed { sources, .. } => sources.iter().any(|s| s == source),
+            FieldValue::Agreed { sources, .. } => sources.iter().any(|s| trusted.matches(s)),
             FieldValue::Conflicting { alternatives, .. } => alternatives
                 .iter()
-                .any(|(sources, _)| sources.iter().any(|s| s == source)),
+                .any(|(sources, _)| sources.iter().any(|s| trusted.matches(s))),
             FieldValue::Empty => false,
         }
     }
 
-    field_has_source(&merged.title, trusted_str)
-        || field_has_source(&merged.author, trusted_str)
-        || field_has_source(&merged.narrator, trusted_str)
-        || field_has_source(&merged.series, trusted_str)
-        || field_has_source(&merged.series_position, trusted_str)
-        || field_has_source(&merged.year, trusted_str)
-        || field_has_source(&merged.description, trusted_str)
-        || field_has_source(&merged.publisher, trusted_str)
-        || field_has_source(&merged.genre, trusted_str)
-        || field_has_source(&merged.isbn, trusted_str)
-        || field_has_source(&merged.asin, trusted_str)
+    field_has_source(&merged.title, trusted)
+        || field_has_source(&merged.author, trusted)
+        || field_has_source(&merged.narrator, trusted)
+        || field_has_source(&merged.series, trusted)
+        || field_has_source(&merged.series_position, trusted)
+        || field_has_source(&merged.year, trusted)
+        || field_has_source(&merged.description, trusted)
+        || field_has_source(&merged.publisher, trusted)
+        || field_has_source(&merged.genre, trusted)
+        || field_has_source(&merged.isbn, trusted)
+        || field_has_source(&merged.asin, trusted)
 }
 
 #[cfg(test)]
@@ -408,6 +567,7 @@ mod tests {
             genre: None,
             isbn: None,
             asin: None,
+            genre_candidates: Vec::new(),
         }
     }
 
@@ -929,4 +1089,111 @@ mod tests {
 
         assert!(!has_trusted_source_data(&merged, TrustedSource::Audible));
     }
+
+    #[test]
+    fn test_merge_options_restrict_fields() {
+        let existing = AudiobookMetadata {
+            title: Some("Curated Title".to_string()),
+            ..Default::default()
+        };
+        let mut audible = make_lookup_result("audible");
+        audible.title = Some("Store Title".to_string());
+        audible.narrator = Some("R.C. Bray".to_string());
+        audible.genre = Some("Science Fiction".to_string());
+
+        let options = MergeOptions {
+            fields: Some(vec!["narrator".to_string()]),
+            ..Default::default()
+        };
+        let merged = merge_results_with_options(&existing, &[audible], &options);
+
+        assert_eq!(
+            merged.title,
+            FieldValue::Agreed {
+                value: "Curated Title".to_string(),
+                sources: vec!["file".to_string()],
+            }
+        );
+        assert_eq!(
+            merged.narrator,
+            FieldValue::Agreed {
+                value: "R.C. Bray".to_string(),
+                sources: vec!["audible".to_string()],
+            }
+        );
+        assert_eq!(merged.genre, FieldValue::Empty);
+    }
+
+    #[test]
+    fn test_merge_options_only_missing_and_locked() {
+        let existing = AudiobookMetadata {
+            title: Some("Curated Title".to_string()),
+            ..Default::default()
+        };
+        let mut audible = make_lookup_result("audible");
+        audible.title = Some("Store Title".to_string());
+        audible.narrator = Some("R.C. Bray".to_string());
+        audible.genre = Some("Science Fiction".to_string());
+
+        let options = MergeOptions {
+            only_missing: true,
+            ..Default::default()
+        }
+        .with_locked(&["genre".to_string()]);
+        let merged = merge_results_with_options(&existing, &[audible], &options);
+
+        // Existing value kept, missing value filled, locked field untouched
+        assert!(
+            matches!(merged.title, FieldValue::Agreed { ref value, .. } if value == "Curated Title")
+        );
+        assert!(
+            matches!(merged.narrator, FieldValue::Agreed { ref value, .. } if value == "R.C. Bray")
+        );
+        assert_eq!(merged.genre, FieldValue::Empty);
+    }
+
+    #[test]
+    fn test_merge_options_canonical_names() {
+        let existing = AudiobookMetadata {
+            author: Some("King, Stephen".to_string()),
+            ..Default::default()
+        };
+        let mut audible = make_lookup_result("audible");
+        audible.author = Some("Richard Bachman".to_string());
+        audible.narrator = Some("RC Bray".to_string());
+
+        let mut names = NameRegistry::default();
+        names
+            .merge(
+                "Stephen King",
+                &["King, Stephen".to_string(), "Richard Bachman".to_string()],
+            )
+            .unwrap();
+        names.merge("R.C. Bray", &["RC Bray".to_string()]).unwrap();
+        let options = MergeOptions {
+            names,
+            ..Default::default()
+        };
+        let merged = merge_results_with_options(&existing, &[audible], &options);
+
+        match merged.author {
+            FieldValue::Conflicting {
+                selected,
+                alternatives,
+            } => {
+                assert_eq!(selected, "Stephen King");
+                assert_eq!(
+                    alternatives[1],
+                    (
+                        vec!["audible".to_string(), "names".to_string()],
+                        "Stephen King".to_string()
+                    )
+                );
+            }
+            other => panic!("Expected Conflicting, got {:?}", other),
+        }
+        assert!(
+            matches!(merged.narrator, FieldValue::Agreed { ref value, .. } if value == "R.C. Bray")
+        );
+    }
 }
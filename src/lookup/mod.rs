@@ -1,13 +1,26 @@
 #![allow(dead_code, unused_imports)]
 
 pub mod api;
-mod asin;
+mod crossref;
+mod genres;
+mod html;
+mod identifiers;
 pub mod merge;
+pub mod report;
+pub mod session;
+mod sidecar;
 mod trusted;
 
 pub use api::{fetch_audible, fetch_audnexus, fetch_openlibrary, LookupResult};
-pub use asin::extract_asin_from_filename;
+pub use crossref::{next_asin, next_isbn, replace_fuzzy_openlibrary, CrossRef};
+pub use genres::{apply_genre_mapping, map_genres, split_genres};
+pub use identifiers::{
+    best_identifier, discover_identifiers, extract_asin_from_filename, normalize_isbn, Identifier,
+    IdentifierKind, IdentifierSource,
+};
 pub use merge::{
-    has_trusted_source_data, merge_results, resolve_with_trusted_source, FieldValue, MergedMetadata,
+    has_trusted_source_data, merge_results, merge_results_with_options,
+    resolve_with_trusted_source, FieldValue, MergeOptions, MergedMetadata,
 };
+pub use sidecar::read_sidecars;
 pub use trusted::TrustedSource;
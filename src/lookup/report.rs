@@ -0,0 +1,549 @@
+//! Non-interactive lookup reports
+//!
+//! `lookup-all --report <file>` writes every file's merged lookup results
+//! for review outside the editor. Each field records the current value, the
+//! proposed value, per-source alternatives and an `apply` flag. After
+//! editing `proposed`/`apply`, `lookup-apply <file>` applies the decisions.
+//!
+//! The format follows the file extension: `.csv` writes one row per field,
+//! anything else writes JSON.
+
+use crate::lookup::{FieldValue, MergedMetadata};
+use crate::metadata::{AudiobookMetadata, EDITABLE_FIELDS};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Outcome of looking up a single file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportStatus {
+    UpdatesAvailable,
+    UpToDate,
+    Error,
+}
+
+impl ReportStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ReportStatus::UpdatesAvailable => "updates_available",
+            ReportStatus::UpToDate => "up_to_date",
+            ReportStatus::Error => "error",
+        }
+    }
+
+    fn parse(s: &str) -> Result<Self> {
+        match s {
+            "updates_available" => Ok(ReportStatus::UpdatesAvailable),
+            "up_to_date" => Ok(ReportStatus::UpToDate),
+            "error" => Ok(ReportStatus::Error),
+            _ => bail!("Unknown status \"{}\"", s),
+        }
+    }
+}
+
+/// Merge state of a field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldState {
+    Agreed,
+    Conflict,
+}
+
+impl FieldState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            FieldState::Agreed => "agreed",
+            FieldState::Conflict => "conflict",
+        }
+    }
+
+    fn parse(s: &str) -> Result<Self> {
+        match s {
+            "agreed" => Ok(FieldState::Agreed),
+            "conflict" => Ok(FieldState::Conflict),
+            _ => bail!("Unknown field state \"{}\"", s),
+        }
+    }
+}
+
+/// One source's value for a field
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportAlternative {
+    pub sources: Vec<String>,
+    pub value: String,
+}
+
+/// A single field of a report entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportField {
+    pub field: String,
+    pub state: FieldState,
+    /// Value in the file when the report was written
+    pub current: Option<String>,
+    /// Value to write; edit this to pick an alternative
+    pub proposed: Option<String>,
+    #[serde(default)]
+    pub alternatives: Vec<ReportAlternative>,
+    /// Whether `lookup-apply` should write `proposed`
+    pub apply: bool,
+}
+
+/// Lookup results for one file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportEntry {
+    pub path: PathBuf,
+    pub status: ReportStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub sources: Vec<String>,
+    #[serde(default)]
+    pub fields: Vec<ReportField>,
+}
+
+impl ReportEntry {
+    /// Build an entry from merged lookup results
+    pub fn from_merged(
+        path: &Path,
+        original: &AudiobookMetadata,
+        merged: &MergedMetadata,
+        sources: &[String],
+        up_to_date: bool,
+    ) -> Self {
+        let fields = EDITABLE_FIELDS
+            .iter()
+            .filter_map(|name| {
+                let current = original.get_field(name);
+                let (state, proposed, alternatives) = match merged.field(name)? {
+                    FieldValue::Agreed { value, sources } => (
+                        FieldState::Agreed,
+                        value.clone(),
+                        vec![ReportAlternative {
+                            sources: sources.clone(),
+                            value: value.clone(),
+                        }],
+                    ),
+                    FieldValue::Conflicting {
+                        selected,
+                        alternatives,
+                    } => (
+                        FieldState::Conflict,
+                        selected.clone(),
+                        alternatives
+                            .iter()
+                            .map(|(sources, value)| ReportAlternative {
+                                sources: sources.clone(),
+                                value: value.clone(),
+                            })
+                            .collect(),
+                    ),
+                    FieldValue::Empty => return None,
+                };
+
+                let apply = current.as_deref() != Some(proposed.as_str());
+                Some(ReportField {
+                    field: name.to_string(),
+                    state,
+                    current,
+                    proposed: Some(proposed),
+                    alternatives,
+                    apply,
+                })
+            })
+            .collect();
+
+        Self {
+            path: path.to_path_buf(),
+            status: if up_to_date {
+                ReportStatus::UpToDate
+            } else {
+                ReportStatus::UpdatesAvailable
+            },
+            error: None,
+            sources: sources.to_vec(),
+            fields,
+        }
+    }
+
+    /// Build an entry for a file whose lookup failed
+    pub fn from_error(path: &Path, error: &anyhow::Error) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            status: ReportStatus::Error,
+            error: Some(error.to_string()),
+            sources: Vec::new(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Apply the entry's decisions on top of the file's current metadata.
+    ///
+    /// Fails if a field marked for apply no longer has the value recorded
+    /// in the report (the file was retagged since).
+    pub fn apply_to(&self, current: &AudiobookMetadata) -> Result<AudiobookMetadata> {
+        let mut new = current.clone();
+
+        for field in self.fields.iter().filter(|f| f.apply) {
+            let now = current.get_field(&field.field);
+            if now != field.current {
+                bail!(
+                    "{} changed since the report was written (was \"{}\", now \"{}\")",
+                    field.field,
+                    field.current.as_deref().unwrap_or(""),
+                    now.as_deref().unwrap_or("")
+                );
+            }
+            new.set_field(&field.field, field.proposed.as_deref())?;
+        }
+
+        Ok(new)
+    }
+}
+
+/// A full lookup report
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LookupReport {
+    pub generated_at: chrono::DateTime<chrono::Utc>,
+    pub dir: PathBuf,
+    pub files: Vec<ReportEntry>,
+}
+
+impl LookupReport {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            generated_at: chrono::Utc::now(),
+            dir: dir.to_path_buf(),
+            files: Vec::new(),
+        }
+    }
+
+    /// Write the report, choosing JSON or CSV from the file extension
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let content = if is_csv(path) {
+            self.to_csv()?
+        } else {
+            serde_json::to_string_pretty(self).context("Failed to serialize report")?
+        };
+        fs::write(path, content).with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    /// Read a report, choosing JSON or CSV from the file extension
+    pub fn read(path: &Path) -> Result<Self> {
+        let content =
+            fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        if is_csv(path) {
+            Self::from_csv(&content).with_context(|| format!("Failed to parse {:?}", path))
+        } else {
+            serde_json::from_str(&content).with_context(|| format!("Failed to parse {:?}", path))
+        }
+    }
+
+    /// One row per field (or one row per file without fields)
+    fn to_csv(&self) -> Result<String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(CSV_HEADER)?;
+
+        for entry in &self.files {
+            let path = entry.path.to_string_lossy();
+            let sources = entry.sources.join(", ");
+
+            if entry.fields.is_empty() {
+                writer.write_record([
+                    path.as_ref(),
+                    entry.status.as_str(),
+                    entry.error.as_deref().unwrap_or(""),
+                    "",
+                    "",
+                    "",
+                    "",
+                    "",
+                    "",
+                    &sources,
+                ])?;
+                continue;
+            }
+
+            for field in &entry.fields {
+                let alternatives = field
+                    .alternatives
+                    .iter()
+                    .map(|a| format!("[{}] {}", a.sources.join(", "), a.value))
+                    .collect::<Vec<_>>()
+                    .join(" | ");
+                writer.write_record([
+                    path.as_ref(),
+                    entry.status.as_str(),
+                    "",
+                    &field.field,
+                    field.state.as_str(),
+                    field.current.as_deref().unwrap_or(""),
+                    field.proposed.as_deref().unwrap_or(""),
+                    if field.apply { "yes" } else { "no" },
+                    &alternatives,
+                    &sources,
+                ])?;
+            }
+        }
+
+        let bytes = writer.into_inner().context("Failed to write CSV")?;
+        String::from_utf8(bytes).context("CSV output is not UTF-8")
+    }
+
+    /// Parse the CSV layout written by `to_csv`.
+    ///
+    /// Alternatives are informational only and are not read back.
+    fn from_csv(content: &str) -> Result<Self> {
+        let mut reader = csv::Reader::from_reader(content.as_bytes());
+        let headers = reader.headers()?.clone();
+        let column = |name: &str| -> Result<usize> {
+            headers
+                .iter()
+                .position(|h| h == name)
+                .with_context(|| format!("Missing column \"{}\"", name))
+        };
+        let path_col = column("path")?;
+        let status_col = column("status")?;
+        let error_col = column("error")?;
+        let field_col = column("field")?;
+        let state_col = column("state")?;
+        let current_col = column("current")?;
+        let proposed_col = column("proposed")?;
+        let apply_col = column("apply")?;
+        let sources_col = column("sources")?;
+
+        // Rows are grouped by path, so a CSV sorted by another column still
+        // gives one entry per file (in the order files first appear)
+        let mut files: Vec<ReportEntry> = Vec::new();
+        let mut index: HashMap<PathBuf, usize> = HashMap::new();
+        for (i, record) in reader.records().enumerate() {
+            let record = record?;
+            let get = |col: usize| record.get(col).unwrap_or("").trim();
+            let optional = |col: usize| Some(get(col).to_string()).filter(|v| !v.is_empty());
+            let path = PathBuf::from(get(path_col));
+
+            let entry = match index.entry(path) {
+                Entry::Occupied(entry) => &mut files[*entry.get()],
+                Entry::Vacant(entry) => {
+                    files.push(ReportEntry {
+                        path: entry.key().clone(),
+                        status: ReportStatus::parse(get(status_col))
+                            .with_context(|| format!("row {}", i + 2))?,
+                        error: optional(error_col),
+                        sources: get(sources_col)
+                            .split(',')
+                            .map(|s| s.trim().to_string())
+                            .filter(|s| !s.is_empty())
+                            .collect(),
+                        fields: Vec::new(),
+                    });
+                    entry.insert(files.len() - 1);
+                    files.last_mut().expect("entry pushed above")
+                }
+            };
+
+            let field = get(field_col);
+            if field.is_empty() {
+                continue;
+            }
+            let apply = match get(apply_col).to_lowercase().as_str() {
+                "yes" | "y" | "true" | "1" | "x" => true,
+                "no" | "n" | "false" | "0" | "" => false,
+                other => bail!("row {}: apply must be yes or no, got \"{}\"", i + 2, other),
+            };
+
+            entry.fields.push(ReportField {
+                field: field.to_string(),
+                state: FieldState::parse(get(state_col))
+                    .with_context(|| format!("row {}", i + 2))?,
+                current: optional(current_col),
+                proposed: optional(proposed_col),
+                alternatives: Vec::new(),
+                apply,
+            });
+        }
+
+        Ok(Self {
+            generated_at: chrono::Utc::now(),
+            dir: PathBuf::new(),
+            files,
+        })
+    }
+}
+
+const CSV_HEADER: [&str; 10] = [
+    "path",
+    "status",
+    "error",
+    "field",
+    "state",
+    "current",
+    "proposed",
+    "apply",
+    "alternatives",
+    "sources",
+];
+
+fn is_csv(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.to_string_lossy().eq_ignore_ascii_case("csv"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn make_merged() -> MergedMetadata {
+        MergedMetadata {
+            title: FieldValue::Agreed {
+                value: "The Martian".to_string(),
+                sources: vec!["file".to_string(), "audible".to_string()],
+            },
+            author: FieldValue::Conflicting {
+                selected: "Andy Weir".to_string(),
+                alternatives: vec![
+                    (vec!["file".to_string()], "Andy Weir".to_string()),
+                    (vec!["openlibrary".to_string()], "Weir, Andy".to_string()),
+                ],
+            },
+            narrator: FieldValue::Agreed {
+                value: "R. C. Bray".to_string(),
+                sources: vec!["audible".to_string()],
+            },
+            series: FieldValue::Empty,
+            series_position: FieldValue::Empty,
+            year: FieldValue::Agreed {
+                value: "2014".to_string(),
+                sources: vec!["audible".to_string()],
+            },
+            description: FieldValue::Empty,
+            publisher: FieldValue::Empty,
+            genre: FieldValue::Empty,
+            isbn: FieldValue::Empty,
+            asin: FieldValue::Empty,
+        }
+    }
+
+    fn make_original() -> AudiobookMetadata {
+        AudiobookMetadata {
+            title: Some("The Martian".to_string()),
+            author: Some("Andy Weir".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn make_report() -> LookupReport {
+        let mut report = LookupReport::new(Path::new("/books"));
+        report.files.push(ReportEntry::from_merged(
+            Path::new("/books/martian.m4b"),
+            &make_original(),
+            &make_merged(),
+            &["audible".to_string(), "openlibrary".to_string()],
+            false,
+        ));
+        report.files.push(ReportEntry::from_error(
+            Path::new("/books/broken.m4b"),
+            &anyhow::anyhow!("No results found"),
+        ));
+        report
+    }
+
+    #[test]
+    fn test_from_merged_fields() {
+        let entry = &make_report().files[0];
+        let names: Vec<&str> = entry.fields.iter().map(|f| f.field.as_str()).collect();
+        assert_eq!(names, vec!["title", "author", "narrator", "year"]);
+
+        // Unchanged and conflicting-but-kept fields are not applied by default
+        assert!(!entry.fields[0].apply);
+        assert!(!entry.fields[1].apply);
+        assert_eq!(entry.fields[1].state, FieldState::Conflict);
+        assert_eq!(entry.fields[1].alternatives.len(), 2);
+
+        // New values are
+        assert!(entry.fields[2].apply);
+        assert_eq!(entry.fields[2].current, None);
+        assert_eq!(entry.fields[2].proposed.as_deref(), Some("R. C. Bray"));
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("report.json");
+        let report = make_report();
+
+        report.write(&path).unwrap();
+        assert_eq!(LookupReport::read(&path).unwrap(), report);
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("report.csv");
+        let report = make_report();
+
+        report.write(&path).unwrap();
+        let loaded = LookupReport::read(&path).unwrap();
+
+        assert_eq!(loaded.files.len(), 2);
+        assert_eq!(loaded.files[1].status, ReportStatus::Error);
+        assert_eq!(loaded.files[1].error.as_deref(), Some("No results found"));
+        for (a, b) in loaded.files[0].fields.iter().zip(&report.files[0].fields) {
+            assert_eq!(a.field, b.field);
+            assert_eq!(a.state, b.state);
+            assert_eq!(a.current, b.current);
+            assert_eq!(a.proposed, b.proposed);
+            assert_eq!(a.apply, b.apply);
+        }
+    }
+
+    #[test]
+    fn test_csv_rows_grouped_by_path() {
+        let report = make_report();
+        let csv = report.to_csv().unwrap();
+
+        // As if sorted by field in a spreadsheet: the martian rows are split up
+        let mut lines: Vec<&str> = csv.lines().collect();
+        let header = lines.remove(0);
+        let broken = lines.pop().unwrap();
+        lines.insert(2, broken);
+        let shuffled = format!("{}\n{}\n", header, lines.join("\n"));
+
+        let loaded = LookupReport::from_csv(&shuffled).unwrap();
+        assert_eq!(loaded.files.len(), 2);
+        let martian = loaded
+            .files
+            .iter()
+            .find(|e| e.path == Path::new("/books/martian.m4b"))
+            .unwrap();
+        assert_eq!(martian.fields.len(), report.files[0].fields.len());
+    }
+
+    #[test]
+    fn test_apply_to_uses_edited_decisions() {
+        let mut entry = make_report().files.remove(0);
+        // Reviewer picks the alternative author and rejects the year
+        entry.fields[1].proposed = Some("Weir, Andy".to_string());
+        entry.fields[1].apply = true;
+        entry.fields[3].apply = false;
+
+        let new = entry.apply_to(&make_original()).unwrap();
+        assert_eq!(new.author.as_deref(), Some("Weir, Andy"));
+        assert_eq!(new.narrator.as_deref(), Some("R. C. Bray"));
+        assert_eq!(new.year, None);
+    }
+
+    #[test]
+    fn test_apply_to_rejects_drift() {
+        let mut entry = make_report().files.remove(0);
+        entry.fields[1].apply = true;
+
+        let mut retagged = make_original();
+        retagged.author = Some("Someone Else".to_string());
+        assert!(entry.apply_to(&retagged).is_err());
+    }
+}
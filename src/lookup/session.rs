@@ -0,0 +1,370 @@
+//! Persistent lookup-all sessions
+//!
+//! A session records every file's lookup results and the decision made for
+//! it, so an interrupted `lookup-all` can be resumed with `--resume`.
+//! Sessions live in `~/.cache/audiobookctl/sessions/<dir-hash>.json` and are
+//! removed once a run completes.
+
+use crate::hash::sha256_file;
+use crate::lookup::MergedMetadata;
+use crate::metadata::AudiobookMetadata;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Where a file stands within a session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryStatus {
+    /// Results stored, waiting for a decision
+    Queued,
+    /// Sources already match the file
+    UpToDate,
+    /// Changes were written to the file
+    Applied,
+    /// Reviewed and left unchanged
+    Skipped,
+    /// Saved to the pending edits cache
+    Deferred,
+}
+
+impl EntryStatus {
+    /// Whether a decision has been made (the file won't be revisited on resume
+    /// unless it changed since, or the decision was to apply)
+    pub fn is_decided(&self) -> bool {
+        matches!(
+            self,
+            EntryStatus::Applied | EntryStatus::Skipped | EntryStatus::Deferred
+        )
+    }
+}
+
+/// Identity of a file's contents at the time its results were stored
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileFingerprint {
+    pub size: u64,
+    pub modified_secs: u64,
+    pub sha256: String,
+}
+
+impl FileFingerprint {
+    /// Compute the fingerprint of a file (hashes the whole file)
+    pub fn compute(path: &Path) -> Result<Self> {
+        let (size, modified_secs) = size_and_mtime(path)?;
+        Ok(Self {
+            size,
+            modified_secs,
+            sha256: sha256_file(path)?,
+        })
+    }
+
+    /// Check whether the file's content still matches.
+    ///
+    /// Size and modification time are compared first; the file is only
+    /// re-hashed when they differ (e.g. touched but not modified).
+    pub fn matches(&self, path: &Path) -> bool {
+        let Ok((size, modified_secs)) = size_and_mtime(path) else {
+            return false;
+        };
+        if size != self.size {
+            return false;
+        }
+        if modified_secs == self.modified_secs {
+            return true;
+        }
+        sha256_file(path).is_ok_and(|hash| hash == self.sha256)
+    }
+}
+
+fn size_and_mtime(path: &Path) -> Result<(u64, u64)> {
+    let meta = fs::metadata(path).with_context(|| format!("Failed to stat {:?}", path))?;
+    let modified_secs = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    Ok((meta.len(), modified_secs))
+}
+
+/// Stored state for one file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionEntry {
+    pub status: EntryStatus,
+    pub fingerprint: Option<FileFingerprint>,
+    pub original: AudiobookMetadata,
+    pub merged: MergedMetadata,
+    pub sources: Vec<String>,
+}
+
+impl SessionEntry {
+    /// Whether the stored state still applies to the file on resume
+    ///
+    /// Applied files changed because we wrote them; anything else is only
+    /// current while the file matches its fingerprint.
+    pub fn is_current(&self, path: &Path) -> bool {
+        self.status == EntryStatus::Applied
+            || self.fingerprint.as_ref().is_some_and(|f| f.matches(path))
+    }
+}
+
+/// A lookup-all session for one directory
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LookupSession {
+    pub dir: PathBuf,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub files: BTreeMap<PathBuf, SessionEntry>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl LookupSession {
+    /// Start a new, empty session for a directory
+    pub fn new(dir: &Path) -> Result<Self> {
+        let (dir, path) = Self::locate(dir)?;
+        Ok(Self {
+            dir,
+            created_at: chrono::Utc::now(),
+            files: BTreeMap::new(),
+            path,
+        })
+    }
+
+    /// Load the saved session for a directory, if any
+    pub fn load(dir: &Path) -> Result<Option<Self>> {
+        let (_, path) = Self::locate(dir)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read session: {}", path.display()))?;
+        let mut session: Self = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse session: {}", path.display()))?;
+        session.path = path;
+        Ok(Some(session))
+    }
+
+    /// Write the session to disk (via a temp file so a crash can't truncate it)
+    pub fn save(&self) -> Result<()> {
+        let content = serde_json::to_string(self).context("Failed to serialize session")?;
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, content)
+            .with_context(|| format!("Failed to write session: {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Failed to write session: {}", self.path.display()))?;
+        Ok(())
+    }
+
+    /// Delete the session file
+    pub fn remove(&self) -> Result<()> {
+        if self.path.exists() {
+            fs::remove_file(&self.path)
+                .with_context(|| format!("Failed to remove session: {}", self.path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Stored state for a file, if any
+    pub fn entry(&self, path: &Path) -> Option<&SessionEntry> {
+        self.files.get(&Self::key(path))
+    }
+
+    /// Record a file's lookup results
+    pub fn record_result(
+        &mut self,
+        path: &Path,
+        status: EntryStatus,
+        fingerprint: Option<FileFingerprint>,
+        original: &AudiobookMetadata,
+        merged: &MergedMetadata,
+        sources: &[String],
+    ) {
+        self.files.insert(
+            Self::key(path),
+            SessionEntry {
+                status,
+                fingerprint,
+                original: original.clone(),
+                merged: merged.clone(),
+                sources: sources.to_vec(),
+            },
+        );
+    }
+
+    /// Update the status of a file already in the session
+    pub fn set_status(&mut self, path: &Path, status: EntryStatus) {
+        if let Some(entry) = self.files.get_mut(&Self::key(path)) {
+            entry.status = status;
+        }
+    }
+
+    /// Number of files with a decision
+    pub fn decided_count(&self) -> usize {
+        self.files
+            .values()
+            .filter(|e| e.status.is_decided())
+            .count()
+    }
+
+    /// Files are keyed by absolute path so `--resume` works from any directory
+    fn key(path: &Path) -> PathBuf {
+        path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+    }
+
+    /// Canonical directory and session file path
+    fn locate(dir: &Path) -> Result<(PathBuf, PathBuf)> {
+        let abs_dir = dir
+            .canonicalize()
+            .with_context(|| format!("Failed to get absolute path for: {}", dir.display()))?;
+
+        let sessions_dir = dirs::cache_dir()
+            .context("Could not determine cache directory")?
+            .join("audiobookctl")
+            .join("sessions");
+        fs::create_dir_all(&sessions_dir).with_context(|| {
+            format!(
+                "Failed to create sessions directory: {}",
+                sessions_dir.display()
+            )
+        })?;
+
+        let mut hasher = Sha256::new();
+        hasher.update(abs_dir.to_string_lossy().as_bytes());
+        let hash = hex::encode(&hasher.finalize()[..8]);
+
+        Ok((abs_dir, sessions_dir.join(format!("{}.json", hash))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lookup::FieldValue;
+    use tempfile::TempDir;
+
+    fn empty_merged() -> MergedMetadata {
+        MergedMetadata {
+            title: FieldValue::Agreed {
+                value: "The Martian".to_string(),
+                sources: vec!["audible".to_string()],
+            },
+            author: FieldValue::Conflicting {
+                selected: "Andy Weir".to_string(),
+                alternatives: vec![
+                    (vec!["file".to_string()], "Andy Weir".to_string()),
+                    (vec!["audible".to_string()], "Weir, Andy".to_string()),
+                ],
+            },
+            narrator: FieldValue::Empty,
+            series: FieldValue::Empty,
+            series_position: FieldValue::Empty,
+            year: FieldValue::Empty,
+            description: FieldValue::Empty,
+            publisher: FieldValue::Empty,
+            genre: FieldValue::Empty,
+            isbn: FieldValue::Empty,
+            asin: FieldValue::Empty,
+        }
+    }
+
+    #[test]
+    fn test_session_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let session_file = temp.path().join("session.json");
+
+        let mut session = LookupSession {
+            dir: temp.path().to_path_buf(),
+            created_at: chrono::Utc::now(),
+            files: BTreeMap::new(),
+            path: session_file.clone(),
+        };
+        let book = temp.path().join("book.m4b");
+        session.record_result(
+            &book,
+            EntryStatus::Queued,
+            None,
+            &AudiobookMetadata::default(),
+            &empty_merged(),
+            &["audible".to_string()],
+        );
+        session.set_status(&book, EntryStatus::Deferred);
+        session.save().unwrap();
+
+        let content = fs::read_to_string(&session_file).unwrap();
+        let loaded: LookupSession = serde_json::from_str(&content).unwrap();
+        let entry = loaded.entry(&book).unwrap();
+        assert_eq!(entry.status, EntryStatus::Deferred);
+        assert_eq!(entry.merged, empty_merged());
+        assert_eq!(loaded.decided_count(), 1);
+    }
+
+    #[test]
+    fn test_changed_files_are_not_current() {
+        let temp = TempDir::new().unwrap();
+        let book = temp.path().join("book.m4b");
+        fs::write(&book, b"original content").unwrap();
+        let mut entry = SessionEntry {
+            status: EntryStatus::Skipped,
+            fingerprint: Some(FileFingerprint::compute(&book).unwrap()),
+            original: AudiobookMetadata::default(),
+            merged: empty_merged(),
+            sources: Vec::new(),
+        };
+        assert!(entry.is_current(&book));
+
+        fs::write(&book, b"retagged, longer content").unwrap();
+        for status in [
+            EntryStatus::Skipped,
+            EntryStatus::Deferred,
+            EntryStatus::Queued,
+        ] {
+            entry.status = status;
+            assert!(!entry.is_current(&book));
+        }
+        entry.status = EntryStatus::Applied;
+        assert!(entry.is_current(&book));
+    }
+
+    #[test]
+    fn test_fingerprint_detects_changes() {
+        let temp = TempDir::new().unwrap();
+        let book = temp.path().join("book.m4b");
+        fs::write(&book, b"original content").unwrap();
+
+        let fingerprint = FileFingerprint::compute(&book).unwrap();
+        assert!(fingerprint.matches(&book));
+
+        fs::write(&book, b"changed content!").unwrap();
+        let mut stale = fingerprint.clone();
+        stale.modified_secs = 0; // force a re-hash
+        assert!(!stale.matches(&book));
+
+        fs::write(&book, b"shorter").unwrap();
+        assert!(!fingerprint.matches(&book));
+    }
+
+    #[test]
+    fn test_fingerprint_touched_but_unchanged() {
+        let temp = TempDir::new().unwrap();
+        let book = temp.path().join("book.m4b");
+        fs::write(&book, b"content").unwrap();
+
+        let mut fingerprint = FileFingerprint::compute(&book).unwrap();
+        fingerprint.modified_secs = 0; // as if the file had been touched since
+        assert!(fingerprint.matches(&book));
+    }
+
+    #[test]
+    fn test_entry_status_is_decided() {
+        assert!(!EntryStatus::Queued.is_decided());
+        assert!(!EntryStatus::UpToDate.is_decided());
+        assert!(EntryStatus::Applied.is_decided());
+        assert!(EntryStatus::Skipped.is_decided());
+        assert!(EntryStatus::Deferred.is_decided());
+    }
+}
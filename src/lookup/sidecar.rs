@@ -0,0 +1,658 @@
+//! Local sidecar files as a lookup source
+//!
+//! Downloads often ship with metadata next to the m4b: Audiobookshelf's
+//! `metadata.json`, Calibre-style `.opf` files, `desc.txt`/`reader.txt`,
+//! or release `.nfo` files. Each format that is found contributes one
+//! `LookupResult` so offline books show up in the same conflict view as
+//! online sources.
+
+use crate::lookup::html::{decode_entities, html_to_text};
+use crate::lookup::LookupResult;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Read all recognised sidecar files next to an m4b file.
+///
+/// Returns one result per format found, in the order json, opf, txt, nfo.
+/// Unreadable or malformed sidecars are skipped with a warning.
+pub fn read_sidecars(m4b_path: &Path) -> Vec<LookupResult> {
+    let mut results = Vec::new();
+
+    let Some(dir) = m4b_path.parent() else {
+        return results;
+    };
+    let stem = m4b_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    if let Some(path) = find_sidecar(dir, &stem, "json", &["metadata.json"]) {
+        match fs::read_to_string(&path).map(|c| parse_abs_json(&c)) {
+            Ok(Some(result)) => results.push(result),
+            Ok(None) => warn!("Could not parse sidecar {}", path.display()),
+            Err(e) => warn!("Could not read sidecar {}: {}", path.display(), e),
+        }
+    }
+
+    if let Some(path) = find_sidecar(dir, &stem, "opf", &["metadata.opf"]) {
+        match fs::read_to_string(&path) {
+            Ok(content) => results.push(parse_opf(&content)),
+            Err(e) => warn!("Could not read sidecar {}: {}", path.display(), e),
+        }
+    }
+
+    let desc = read_text_file(&dir.join("desc.txt"));
+    let reader = read_text_file(&dir.join("reader.txt"));
+    if desc.is_some() || reader.is_some() {
+        let mut result = empty_result("sidecar:txt");
+        result.description = desc;
+        result.narrator = reader;
+        results.push(result);
+    }
+
+    if let Some(path) = find_sidecar(dir, &stem, "nfo", &[]) {
+        // NFO files are frequently Latin-1, so decode lossily
+        match fs::read(&path) {
+            Ok(bytes) => results.push(parse_nfo(&String::from_utf8_lossy(&bytes))),
+            Err(e) => warn!("Could not read sidecar {}: {}", path.display(), e),
+        }
+    }
+
+    // A sidecar that yields no fields is noise in the conflict view
+    results.retain(has_any_field);
+    results
+}
+
+/// Locate a sidecar with the given extension.
+///
+/// Prefers `<stem>.<ext>`, then any of the well-known names, then the only
+/// file with that extension in the directory (if there is exactly one).
+fn find_sidecar(dir: &Path, stem: &str, ext: &str, known_names: &[&str]) -> Option<PathBuf> {
+    let same_stem = dir.join(format!("{}.{}", stem, ext));
+    if same_stem.is_file() {
+        return Some(same_stem);
+    }
+
+    for name in known_names {
+        let path = dir.join(name);
+        if path.is_file() {
+            return Some(path);
+        }
+    }
+
+    let mut matches: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.is_file()
+                && p.extension()
+                    .is_some_and(|e| e.to_string_lossy().eq_ignore_ascii_case(ext))
+        })
+        .collect();
+
+    if matches.len() == 1 {
+        matches.pop()
+    } else {
+        None
+    }
+}
+
+/// Read a small text file, returning None if missing or empty
+fn read_text_file(path: &Path) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    non_empty(String::from_utf8_lossy(&bytes).trim())
+}
+
+fn empty_result(source: &str) -> LookupResult {
+    LookupResult {
+        source: source.to_string(),
+        title: None,
+        author: None,
+        narrator: None,
+        series: None,
+        series_position: None,
+        year: None,
+        description: None,
+        publisher: None,
+        genre: None,
+        isbn: None,
+        asin: None,
+        genre_candidates: Vec::new(),
+    }
+}
+
+fn has_any_field(r: &LookupResult) -> bool {
+    r.title.is_some()
+        || r.author.is_some()
+        || r.narrator.is_some()
+        || r.series.is_some()
+        || r.series_position.is_some()
+        || r.year.is_some()
+        || r.description.is_some()
+        || r.publisher.is_some()
+        || r.genre.is_some()
+        || r.isbn.is_some()
+        || r.asin.is_some()
+}
+
+fn non_empty(s: &str) -> Option<String> {
+    let trimmed = s.trim();
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(trimmed.to_string())
+    }
+}
+
+/// Parse a series position like "1", "1.5" or "Book 3" (truncated like Audnexus)
+fn parse_position(s: &str) -> Option<u32> {
+    let digits: String = s
+        .trim()
+        .trim_start_matches(|c: char| !c.is_ascii_digit())
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    digits.parse::<f32>().ok().map(|f| f as u32)
+}
+
+/// Extract the first four-digit year from a date-like string
+fn parse_year(s: &str) -> Option<u32> {
+    let chars: Vec<char> = s.chars().collect();
+    chars
+        .windows(4)
+        .enumerate()
+        .find(|(i, w)| {
+            w.iter().all(|c| c.is_ascii_digit())
+                && (*i == 0 || !chars[i - 1].is_ascii_digit())
+                && chars.get(i + 4).is_none_or(|c| !c.is_ascii_digit())
+        })
+        .and_then(|(_, w)| w.iter().collect::<String>().parse().ok())
+}
+
+// ============================================================================
+// Audiobookshelf metadata.json
+// ============================================================================
+
+/// Audiobookshelf `metadata.json` (only the fields we use)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AbsMetadata {
+    title: Option<String>,
+    #[serde(default)]
+    authors: Vec<String>,
+    #[serde(default)]
+    narrators: Vec<String>,
+    #[serde(default)]
+    series: Vec<String>,
+    #[serde(default)]
+    genres: Vec<String>,
+    published_year: Option<String>,
+    published_date: Option<String>,
+    publisher: Option<String>,
+    description: Option<String>,
+    isbn: Option<String>,
+    asin: Option<String>,
+}
+
+fn parse_abs_json(content: &str) -> Option<LookupResult> {
+    let abs: AbsMetadata = serde_json::from_str(content).ok()?;
+
+    // Series entries look like "Mistborn #1"
+    let (series, series_position) = match abs.series.first() {
+        Some(entry) => match entry.rsplit_once(" #") {
+            Some((name, pos)) => (non_empty(name), parse_position(pos)),
+            None => (non_empty(entry), None),
+        },
+        None => (None, None),
+    };
+
+    let join = |names: &[String]| -> Option<String> {
+        let names: Vec<&str> = names
+            .iter()
+            .map(|n| n.trim())
+            .filter(|n| !n.is_empty())
+            .collect();
+        if names.is_empty() {
+            None
+        } else {
+            Some(names.join(", "))
+        }
+    };
+
+    let mut result = empty_result("sidecar:json");
+    result.title = abs.title.as_deref().and_then(non_empty);
+    result.author = join(&abs.authors);
+    result.narrator = join(&abs.narrators);
+    result.series = series;
+    result.series_position = series_position;
+    result.year = abs
+        .published_year
+        .as_deref()
+        .or(abs.published_date.as_deref())
+        .and_then(parse_year);
+    result.description = abs
+        .description
+        .as_deref()
+        .map(html_to_text)
+        .and_then(|d| non_empty(&d));
+    result.publisher = abs.publisher.as_deref().and_then(non_empty);
+    result.genre_candidates = abs.genres.iter().filter_map(|g| non_empty(g)).collect();
+    result.genre = result.genre_candidates.first().cloned();
+    result.isbn = abs.isbn.as_deref().and_then(non_empty);
+    result.asin = abs.asin.as_deref().and_then(non_empty);
+    Some(result)
+}
+
+// ============================================================================
+// OPF (Calibre / EPUB package metadata)
+// ============================================================================
+
+fn parse_opf(content: &str) -> LookupResult {
+    let mut result = empty_result("sidecar:opf");
+
+    result.title = xml_elements(content, "title")
+        .into_iter()
+        .find_map(|e| non_empty(&e.text));
+
+    // Creators without a role are treated as authors
+    let mut authors = Vec::new();
+    let mut narrators = Vec::new();
+    for creator in xml_elements(content, "creator") {
+        let Some(name) = non_empty(&creator.text) else {
+            continue;
+        };
+        match creator.attr("role").as_deref() {
+            Some("nrt") => narrators.push(name),
+            Some("aut") | None => authors.push(name),
+            Some(_) => {}
+        }
+    }
+    if !authors.is_empty() {
+        result.author = Some(authors.join(", "));
+    }
+    if !narrators.is_empty() {
+        result.narrator = Some(narrators.join(", "));
+    }
+
+    result.publisher = xml_elements(content, "publisher")
+        .into_iter()
+        .find_map(|e| non_empty(&e.text));
+    result.year = xml_elements(content, "date")
+        .into_iter()
+        .find_map(|e| parse_year(&e.text));
+    result.description = xml_elements(content, "description")
+        .into_iter()
+        .find_map(|e| non_empty(&html_to_text(&e.text)));
+    result.genre_candidates = xml_elements(content, "subject")
+        .into_iter()
+        .filter_map(|e| non_empty(&e.text))
+        .collect();
+    result.genre = result.genre_candidates.first().cloned();
+
+    for identifier in xml_elements(content, "identifier") {
+        let scheme = identifier.attr("scheme").unwrap_or_default().to_uppercase();
+        let value = identifier.text.trim();
+        // Some writers use "urn:isbn:..." instead of a scheme attribute
+        let (scheme, value) = match value.to_lowercase().strip_prefix("urn:isbn:") {
+            Some(_) => ("ISBN".to_string(), &value["urn:isbn:".len()..]),
+            None => (scheme, value),
+        };
+        match scheme.as_str() {
+            "ISBN" if result.isbn.is_none() => result.isbn = non_empty(value),
+            "ASIN" | "AUDIBLE_ASIN" | "MOBI-ASIN" if result.asin.is_none() => {
+                result.asin = non_empty(value)
+            }
+            _ => {}
+        }
+    }
+
+    for meta in xml_elements(content, "meta") {
+        let value = meta.attr("content").unwrap_or_default();
+        match meta.attr("name").as_deref() {
+            Some("calibre:series") => result.series = non_empty(&value),
+            Some("calibre:series_index") => result.series_position = parse_position(&value),
+            _ => {}
+        }
+    }
+
+    result
+}
+
+/// A matched XML element: raw attribute text and decoded inner text
+struct XmlElement {
+    attrs: String,
+    text: String,
+}
+
+impl XmlElement {
+    /// Get an attribute value by local name (ignoring any namespace prefix)
+    fn attr(&self, name: &str) -> Option<String> {
+        let mut rest = self.attrs.as_str();
+        while let Some(eq) = rest.find('=') {
+            let key = rest[..eq].trim();
+            let key = key.rsplit(char::is_whitespace).next().unwrap_or(key);
+            let after = rest[eq + 1..].trim_start();
+            let quote = after.chars().next()?;
+            if quote != '"' && quote != '\'' {
+                return None;
+            }
+            let end = after[1..].find(quote)?;
+            let value = &after[1..1 + end];
+            let local = key.rsplit(':').next().unwrap_or(key);
+            if local == name {
+                return Some(decode_entities(value));
+            }
+            rest = &after[end + 2..];
+        }
+        None
+    }
+}
+
+/// Find all elements whose local name matches `name` (e.g. `dc:title` for "title").
+///
+/// This is a deliberately small scanner for the flat structure of OPF
+/// metadata, not a general XML parser: nested elements of the same name
+/// and CDATA sections are not handled.
+fn xml_elements(content: &str, name: &str) -> Vec<XmlElement> {
+    let mut elements = Vec::new();
+    let mut pos = 0;
+
+    while let Some(offset) = content[pos..].find('<') {
+        let start = pos + offset + 1;
+        let Some(tag_end) = content[start..].find('>').map(|i| start + i) else {
+            break;
+        };
+        let tag = &content[start..tag_end];
+        pos = tag_end + 1;
+
+        if tag.starts_with('/') || tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+
+        let qualified = tag
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("");
+        let local = qualified.rsplit(':').next().unwrap_or(qualified);
+        if local != name {
+            continue;
+        }
+
+        let self_closing = tag.ends_with('/');
+        let attrs = tag[qualified.len()..]
+            .trim_end_matches('/')
+            .trim()
+            .to_string();
+
+        let text = if self_closing {
+            String::new()
+        } else {
+            let close = format!("</{}>", qualified);
+            match content[pos..].find(&close) {
+                Some(i) => {
+                    let inner = &content[pos..pos + i];
+                    pos += i + close.len();
+                    decode_entities(inner)
+                }
+                None => String::new(),
+            }
+        };
+
+        elements.push(XmlElement { attrs, text });
+    }
+
+    elements
+}
+
+// ============================================================================
+// NFO release files
+// ============================================================================
+
+fn parse_nfo(content: &str) -> LookupResult {
+    let mut result = empty_result("sidecar:nfo");
+    let lines: Vec<&str> = content.lines().collect();
+    let mut description_lines: Vec<&str> = Vec::new();
+    let mut in_description = false;
+
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+
+        // Section headers are a line followed by an underline of '=' or '-'
+        let is_header = lines.get(i + 1).is_some_and(|next| is_underline(next));
+        if is_header {
+            in_description = trimmed.to_lowercase().contains("description");
+            continue;
+        }
+        if is_underline(line) {
+            continue;
+        }
+
+        if in_description {
+            description_lines.push(trimmed);
+            continue;
+        }
+
+        let Some((key, value)) = trimmed.split_once(':') else {
+            continue;
+        };
+        let key = key.trim().trim_end_matches('.').trim().to_lowercase();
+        let Some(value) = non_empty(value) else {
+            continue;
+        };
+
+        match key.as_str() {
+            "title" | "album" | "book title" if result.title.is_none() => {
+                result.title = Some(value)
+            }
+            "author" | "artist" | "written by" if result.author.is_none() => {
+                result.author = Some(value)
+            }
+            "read by" | "narrator" | "narrated by" if result.narrator.is_none() => {
+                result.narrator = Some(value)
+            }
+            "series" | "series name" if result.series.is_none() => result.series = Some(value),
+            "position in series" | "series position" | "book number"
+                if result.series_position.is_none() =>
+            {
+                result.series_position = parse_position(&value)
+            }
+            "copyright" | "year" | "release date" | "published" | "original release"
+                if result.year.is_none() =>
+            {
+                result.year = parse_year(&value)
+            }
+            "genre" if result.genre.is_none() => result.genre = Some(value),
+            "publisher" if result.publisher.is_none() => result.publisher = Some(value),
+            "isbn" if result.isbn.is_none() => result.isbn = Some(value),
+            "asin" if result.asin.is_none() => result.asin = Some(value),
+            _ => {}
+        }
+    }
+
+    // Collapse hard-wrapped lines into paragraphs
+    let mut paragraphs: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for line in description_lines {
+        if line.is_empty() {
+            if !current.is_empty() {
+                paragraphs.push(std::mem::take(&mut current));
+            }
+        } else {
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(line);
+        }
+    }
+    if !current.is_empty() {
+        paragraphs.push(current);
+    }
+    if !paragraphs.is_empty() {
+        result.description = Some(paragraphs.join("\n\n"));
+    }
+
+    result
+}
+
+fn is_underline(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.len() >= 3 && (trimmed.chars().all(|c| c == '=') || trimmed.chars().all(|c| c == '-'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_abs_json() {
+        let json = r#"{
+            "title": "The Final Empire",
+            "authors": ["Brandon Sanderson"],
+            "narrators": ["Michael Kramer"],
+            "series": ["Mistborn #1"],
+            "genres": ["Fantasy"],
+            "publishedYear": "2006",
+            "publisher": "Macmillan Audio",
+            "description": "<p>For a thousand years...</p>",
+            "isbn": null,
+            "asin": "B002UZMLXM"
+        }"#;
+
+        let result = parse_abs_json(json).unwrap();
+        assert_eq!(result.source, "sidecar:json");
+        assert_eq!(result.title.as_deref(), Some("The Final Empire"));
+        assert_eq!(result.author.as_deref(), Some("Brandon Sanderson"));
+        assert_eq!(result.narrator.as_deref(), Some("Michael Kramer"));
+        assert_eq!(result.series.as_deref(), Some("Mistborn"));
+        assert_eq!(result.series_position, Some(1));
+        assert_eq!(result.year, Some(2006));
+        assert_eq!(
+            result.description.as_deref(),
+            Some("For a thousand years...")
+        );
+        assert_eq!(result.isbn, None);
+        assert_eq!(result.asin.as_deref(), Some("B002UZMLXM"));
+    }
+
+    #[test]
+    fn test_parse_opf() {
+        let opf = r#"<?xml version="1.0" encoding="utf-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
+    <dc:title>The Martian</dc:title>
+    <dc:creator opf:role="aut" opf:file-as="Weir, Andy">Andy Weir</dc:creator>
+    <dc:creator opf:role="nrt">R. C. Bray</dc:creator>
+    <dc:publisher>Podium Publishing</dc:publisher>
+    <dc:date>2013-03-22T00:00:00+00:00</dc:date>
+    <dc:description>&lt;p&gt;Stranded on Mars &amp; alone.&lt;/p&gt;</dc:description>
+    <dc:subject>Science Fiction</dc:subject>
+    <dc:identifier opf:scheme="ISBN">9780553418026</dc:identifier>
+    <dc:identifier opf:scheme="ASIN">B00B5HZGUG</dc:identifier>
+    <meta name="calibre:series" content="Standalone"/>
+    <meta name="calibre:series_index" content="1.0"/>
+  </metadata>
+</package>"#;
+
+        let result = parse_opf(opf);
+        assert_eq!(result.source, "sidecar:opf");
+        assert_eq!(result.title.as_deref(), Some("The Martian"));
+        assert_eq!(result.author.as_deref(), Some("Andy Weir"));
+        assert_eq!(result.narrator.as_deref(), Some("R. C. Bray"));
+        assert_eq!(result.publisher.as_deref(), Some("Podium Publishing"));
+        assert_eq!(result.year, Some(2013));
+        assert_eq!(
+            result.description.as_deref(),
+            Some("Stranded on Mars & alone.")
+        );
+        assert_eq!(result.genre.as_deref(), Some("Science Fiction"));
+        assert_eq!(result.isbn.as_deref(), Some("9780553418026"));
+        assert_eq!(result.asin.as_deref(), Some("B00B5HZGUG"));
+        assert_eq!(result.series.as_deref(), Some("Standalone"));
+        assert_eq!(result.series_position, Some(1));
+    }
+
+    #[test]
+    fn test_parse_nfo() {
+        let nfo = "\
+General Information
+===================
+ Title:                  Red Rising
+ Author:                 Pierce Brown
+ Read By:                Tim Gerard Reynolds
+ Copyright:              2014
+ Genre:                  Science Fiction
+ Publisher:              Audible Studios
+
+Book Description
+================
+The Earth is dying. Darrow is a Red,
+a miner in the depths of Mars.
+
+He believes he is making the surface habitable.
+";
+
+        let result = parse_nfo(nfo);
+        assert_eq!(result.source, "sidecar:nfo");
+        assert_eq!(result.title.as_deref(), Some("Red Rising"));
+        assert_eq!(result.author.as_deref(), Some("Pierce Brown"));
+        assert_eq!(result.narrator.as_deref(), Some("Tim Gerard Reynolds"));
+        assert_eq!(result.year, Some(2014));
+        assert_eq!(result.genre.as_deref(), Some("Science Fiction"));
+        assert_eq!(result.publisher.as_deref(), Some("Audible Studios"));
+        assert_eq!(
+            result.description.as_deref(),
+            Some(
+                "The Earth is dying. Darrow is a Red, a miner in the depths of Mars.\n\n\
+                 He believes he is making the surface habitable."
+            )
+        );
+    }
+
+    #[test]
+    fn test_read_sidecars_txt_files() {
+        let dir = TempDir::new().unwrap();
+        let m4b = dir.path().join("book.m4b");
+        fs::write(&m4b, b"").unwrap();
+        fs::write(dir.path().join("desc.txt"), "  A description.\n").unwrap();
+        fs::write(dir.path().join("reader.txt"), "Ray Porter\n").unwrap();
+
+        let results = read_sidecars(&m4b);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].source, "sidecar:txt");
+        assert_eq!(results[0].description.as_deref(), Some("A description."));
+        assert_eq!(results[0].narrator.as_deref(), Some("Ray Porter"));
+    }
+
+    #[test]
+    fn test_read_sidecars_none_present() {
+        let dir = TempDir::new().unwrap();
+        let m4b = dir.path().join("book.m4b");
+        fs::write(&m4b, b"").unwrap();
+
+        assert!(read_sidecars(&m4b).is_empty());
+    }
+
+    #[test]
+    fn test_find_sidecar_prefers_same_stem() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("book.opf"), "").unwrap();
+        fs::write(dir.path().join("metadata.opf"), "").unwrap();
+
+        let found = find_sidecar(dir.path(), "book", "opf", &["metadata.opf"]);
+        assert_eq!(found, Some(dir.path().join("book.opf")));
+    }
+
+    #[test]
+    fn test_parse_year_and_position() {
+        assert_eq!(parse_year("2013-03-22"), Some(2013));
+        assert_eq!(parse_year("(c) 1999 Someone"), Some(1999));
+        assert_eq!(parse_year("12345"), None);
+        assert_eq!(parse_position("1.5"), Some(1));
+        assert_eq!(parse_position("Book 3"), Some(3));
+        assert_eq!(parse_position(""), None);
+    }
+}
@@ -19,6 +19,19 @@ impl TrustedSource {
             TrustedSource::Openlibrary => "openlibrary",
         }
     }
+
+    /// Check whether a result source label belongs to this provider.
+    ///
+    /// Labels may carry a qualifier, e.g. "audnexus (filename ASIN)".
+    pub fn matches(&self, source: &str) -> bool {
+        provider_name(source) == self.as_str()
+    }
+}
+
+/// Provider name of a result source label without any qualifier,
+/// e.g. "audnexus (filename ASIN)" -> "audnexus"
+pub fn provider_name(source: &str) -> &str {
+    source.split(" (").next().unwrap_or(source)
 }
 
 #[cfg(test)]
@@ -31,4 +44,12 @@ mod tests {
         assert_eq!(TrustedSource::Audnexus.as_str(), "audnexus");
         assert_eq!(TrustedSource::Openlibrary.as_str(), "openlibrary");
     }
+
+    #[test]
+    fn test_trusted_source_matches_qualified_label() {
+        assert!(TrustedSource::Audnexus.matches("audnexus"));
+        assert!(TrustedSource::Audnexus.matches("audnexus (filename ASIN)"));
+        assert!(!TrustedSource::Audnexus.matches("audible"));
+        assert!(!TrustedSource::Audible.matches("audiblex"));
+    }
 }
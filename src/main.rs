@@ -3,38 +3,59 @@ mod commands;
 mod config;
 mod database;
 mod editor;
+mod hardlink;
 mod hash;
+mod journal;
+mod lockfile;
 mod lookup;
 mod metadata;
+mod names;
 mod organize;
 mod safety;
 
 use anyhow::Result;
-use clap::Parser;
+use clap::{ArgMatches, CommandFactory, FromArgMatches};
 use cli::{Cli, Commands};
 
 fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let command = command_name(&matches);
+    journal::start(&command)?;
+    lockfile::configure(&command, cli.wait)?;
+    hardlink::configure(cli.break_links);
 
     match cli.command {
         Commands::Show { file, json, field } => {
             commands::show::run(&file, json, field.as_deref(), cli.quiet)?;
         }
         Commands::Edit {
-            file,
+            files,
             no_dry_run,
             yes,
             no_backup,
             commit,
             commit_all,
         } => {
-            commands::edit::run(
-                file.as_deref(),
+            commands::edit::run(&files, no_dry_run, yes, no_backup, commit, commit_all)?;
+        }
+        Commands::Set {
+            files,
+            fields,
+            from_json,
+            from_toml,
+            no_dry_run,
+            yes,
+            no_backup,
+        } => {
+            commands::set::run(
+                &files,
+                &fields,
+                from_json.as_deref(),
+                from_toml.as_deref(),
                 no_dry_run,
                 yes,
                 no_backup,
-                commit,
-                commit_all,
             )?;
         }
         Commands::Search {
@@ -68,8 +89,18 @@ fn main() -> Result<()> {
             yes,
             no_backup,
             trust_source,
+            fields,
+            only_missing,
         } => {
-            commands::lookup::run(&file, no_dry_run, yes, no_backup, trust_source)?;
+            commands::lookup::run(
+                &file,
+                no_dry_run,
+                yes,
+                no_backup,
+                trust_source,
+                &fields,
+                only_missing,
+            )?;
         }
         Commands::LookupAll {
             dir,
@@ -78,25 +109,81 @@ fn main() -> Result<()> {
             yes,
             no_backup,
             trust_source,
+            jobs,
+            resume,
+            report,
+            fields,
+            only_missing,
+        } => {
+            commands::lookup_all::run(
+                &dir,
+                auto_accept,
+                no_dry_run,
+                yes,
+                no_backup,
+                trust_source,
+                jobs,
+                resume,
+                report.as_deref(),
+                &fields,
+                only_missing,
+            )?;
+        }
+        Commands::LookupApply {
+            report,
+            no_dry_run,
+            yes,
+            no_backup,
         } => {
-            commands::lookup_all::run(&dir, auto_accept, no_dry_run, yes, no_backup, trust_source)?;
+            commands::lookup_apply::run(&report, no_dry_run, yes, no_backup)?;
+        }
+        Commands::ExportCsv { dir, output } => {
+            commands::spreadsheet::export(&dir, output.as_deref())?;
+        }
+        Commands::ImportCsv {
+            file,
+            no_dry_run,
+            yes,
+            no_backup,
+        } => {
+            commands::spreadsheet::import(&file, no_dry_run, yes, no_backup)?;
+        }
+        Commands::Lock {
+            file,
+            fields,
+            unlock,
+            no_dry_run,
+            no_backup,
+        } => {
+            commands::lock::run(&file, &fields, unlock, no_dry_run, no_backup)?;
         }
         Commands::Organize {
             source,
             dest,
             format,
             no_dry_run,
+            mode,
             allow_uncategorized,
             list,
+            resume,
+            rollback,
         } => {
-            commands::organize::run(
-                &source,
-                dest.as_ref(),
-                format.as_deref(),
-                no_dry_run,
-                allow_uncategorized,
-                list,
-            )?;
+            if resume {
+                commands::organize::resume(dest.as_ref(), no_dry_run)?;
+            } else if rollback {
+                commands::organize::rollback(dest.as_ref(), no_dry_run)?;
+            } else if let Some(source) = source {
+                // clap requires --source unless resuming or rolling back
+                commands::organize::run(
+                    &source,
+                    dest.as_ref(),
+                    format.as_deref(),
+                    no_dry_run,
+                    mode,
+                    allow_uncategorized,
+                    list,
+                )?;
+            }
         }
         Commands::Fix {
             dest,
@@ -133,8 +220,35 @@ fn main() -> Result<()> {
                 BackupsAction::Clean { dir, all, yes } => {
                     commands::backups::clean(&dir, all, yes)?;
                 }
+                BackupsAction::Restore {
+                    path,
+                    generation,
+                    force,
+                    no_dry_run,
+                    yes,
+                } => {
+                    commands::backups::restore(&path, generation, force, no_dry_run, yes)?;
+                }
+                BackupsAction::Verify { dir } => {
+                    commands::backups::verify(&dir)?;
+                }
             }
         }
+        Commands::History {
+            file,
+            limit,
+            details,
+        } => {
+            commands::history::run(file.as_deref(), limit, details)?;
+        }
+        Commands::Undo {
+            id,
+            run,
+            no_dry_run,
+            no_backup,
+        } => {
+            commands::undo::run(id, run, no_dry_run, no_backup)?;
+        }
         Commands::Pending { action } => {
             use cli::PendingAction;
             match action {
@@ -154,9 +268,46 @@ fn main() -> Result<()> {
                 PendingAction::Clear { file } => {
                     commands::pending::clear(file.as_deref())?;
                 }
+                PendingAction::Gc {
+                    library,
+                    no_dry_run,
+                } => {
+                    commands::pending::gc(&library, no_dry_run)?;
+                }
+            }
+        }
+        Commands::Genres { dir } => {
+            commands::genres::run(&dir)?;
+        }
+        Commands::Names { action } => {
+            use cli::NamesAction;
+            match action {
+                NamesAction::Scan { dir, accept } => {
+                    commands::names::scan(&dir, accept)?;
+                }
+                NamesAction::List => {
+                    commands::names::list()?;
+                }
+                NamesAction::Merge { canonical, aliases } => {
+                    commands::names::merge(&canonical, &aliases)?;
+                }
+                NamesAction::Remove { name } => {
+                    commands::names::remove(&name)?;
+                }
             }
         }
     }
 
     Ok(())
 }
+
+/// Full subcommand name (e.g. "pending apply") for the journal
+fn command_name(matches: &ArgMatches) -> String {
+    let mut parts = Vec::new();
+    let mut current = matches;
+    while let Some((name, sub)) = current.subcommand() {
+        parts.push(name);
+        current = sub;
+    }
+    parts.join(" ")
+}
@@ -1,7 +1,8 @@
+use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
 
 /// Comprehensive audiobook metadata from m4b files
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct AudiobookMetadata {
     pub title: Option<String>,
     pub author: Option<String>,
@@ -19,3 +20,121 @@ pub struct AudiobookMetadata {
     /// Cover art info (not the bytes - just format and dimensions if available)
     pub cover_info: Option<String>,
 }
+
+/// Names of the user-editable fields, in display order
+pub const EDITABLE_FIELDS: &[&str] = &[
+    "title",
+    "author",
+    "narrator",
+    "series",
+    "series_position",
+    "year",
+    "description",
+    "publisher",
+    "genre",
+    "isbn",
+    "asin",
+];
+
+/// Check that every name is an editable field
+pub fn validate_field_names(names: &[String]) -> Result<()> {
+    for name in names {
+        if !EDITABLE_FIELDS.contains(&name.as_str()) {
+            bail!(
+                "Unknown field \"{}\" (editable fields: {})",
+                name,
+                EDITABLE_FIELDS.join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+impl AudiobookMetadata {
+    /// Get an editable field's value as a string
+    pub fn get_field(&self, name: &str) -> Option<String> {
+        match name {
+            "title" => self.title.clone(),
+            "author" => self.author.clone(),
+            "narrator" => self.narrator.clone(),
+            "series" => self.series.clone(),
+            "series_position" => self.series_position.map(|v| v.to_string()),
+            "year" => self.year.map(|v| v.to_string()),
+            "description" => self.description.clone(),
+            "publisher" => self.publisher.clone(),
+            "genre" => self.genre.clone(),
+            "isbn" => self.isbn.clone(),
+            "asin" => self.asin.clone(),
+            _ => None,
+        }
+    }
+
+    /// Set an editable field from a string. `None` or an empty string clears it.
+    pub fn set_field(&mut self, name: &str, value: Option<&str>) -> Result<()> {
+        let value = value.map(str::trim).filter(|v| !v.is_empty());
+        let text = value.map(String::from);
+        let number = |field: &str| -> Result<Option<u32>> {
+            value
+                .map(|v| {
+                    v.parse::<u32>()
+                        .with_context(|| format!("{} must be a number, got \"{}\"", field, v))
+                })
+                .transpose()
+        };
+
+        match name {
+            "title" => self.title = text,
+            "author" => self.author = text,
+            "narrator" => self.narrator = text,
+            "series" => self.series = text,
+            "series_position" => self.series_position = number(name)?,
+            "year" => self.year = number(name)?,
+            "description" => self.description = text,
+            "publisher" => self.publisher = text,
+            "genre" => self.genre = text,
+            "isbn" => self.isbn = text,
+            "asin" => self.asin = text,
+            _ => bail!(
+                "Unknown field \"{}\" (editable fields: {})",
+                name,
+                EDITABLE_FIELDS.join(", ")
+            ),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_and_set_field() {
+        let mut meta = AudiobookMetadata::default();
+
+        meta.set_field("title", Some("The Martian")).unwrap();
+        meta.set_field("series_position", Some(" 3 ")).unwrap();
+        assert_eq!(meta.get_field("title"), Some("The Martian".to_string()));
+        assert_eq!(meta.series_position, Some(3));
+        assert_eq!(meta.get_field("series_position"), Some("3".to_string()));
+
+        meta.set_field("title", Some("")).unwrap();
+        assert_eq!(meta.title, None);
+    }
+
+    #[test]
+    fn test_set_field_errors() {
+        let mut meta = AudiobookMetadata::default();
+        assert!(meta.set_field("year", Some("soon")).is_err());
+        assert!(meta.set_field("duration_seconds", Some("1")).is_err());
+    }
+
+    #[test]
+    fn test_editable_fields_round_trip() {
+        let mut meta = AudiobookMetadata::default();
+        for field in EDITABLE_FIELDS {
+            meta.set_field(field, Some("7")).unwrap();
+            assert_eq!(meta.get_field(field), Some("7".to_string()));
+        }
+    }
+}
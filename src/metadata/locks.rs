@@ -0,0 +1,69 @@
+//! Per-file locked fields, stored in a freeform iTunes atom
+//!
+//! The atom holds a comma-separated list of field names that lookups must
+//! never change, e.g. `title,genre`.
+
+use crate::metadata::fields::validate_field_names;
+use crate::metadata::writer::update_tag;
+use anyhow::{Context, Result};
+use std::path::Path;
+
+fn locked_ident() -> mp4ameta::FreeformIdent<'static> {
+    mp4ameta::FreeformIdent::new("com.apple.iTunes", "AUDIOBOOKCTL_LOCKED")
+}
+
+/// Read the fields locked in an m4b file
+pub fn read_locked_fields(path: &Path) -> Result<Vec<String>> {
+    let tag = mp4ameta::Tag::read_from_path(path)
+        .with_context(|| format!("Failed to read m4b file: {}", path.display()))?;
+
+    let fields = tag
+        .strings_of(&locked_ident())
+        .next()
+        .map(parse_field_list)
+        .unwrap_or_default();
+    Ok(fields)
+}
+
+/// Replace the fields locked in an m4b file (an empty list removes the atom)
+pub fn write_locked_fields(path: &Path, fields: &[String]) -> Result<()> {
+    validate_field_names(fields)?;
+
+    update_tag(path, |tag| {
+        if fields.is_empty() {
+            tag.remove_data_of(&locked_ident());
+        } else {
+            tag.set_data(locked_ident(), mp4ameta::Data::Utf8(fields.join(",")));
+        }
+    })
+}
+
+fn parse_field_list(value: &str) -> Vec<String> {
+    let mut fields: Vec<String> = Vec::new();
+    for field in value.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+        if !fields.iter().any(|f| f == field) {
+            fields.push(field.to_string());
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_field_list() {
+        assert_eq!(
+            parse_field_list(" title, genre,,title "),
+            vec!["title".to_string(), "genre".to_string()]
+        );
+        assert!(parse_field_list("").is_empty());
+    }
+
+    #[test]
+    fn test_write_rejects_unknown_field() {
+        let result = write_locked_fields(Path::new("/nonexistent.m4b"), &["colour".to_string()]);
+        assert!(result.unwrap_err().to_string().contains("Unknown field"));
+    }
+}
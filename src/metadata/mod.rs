@@ -2,9 +2,11 @@
 #![allow(dead_code, unused_imports)]
 
 mod fields;
+mod locks;
 mod reader;
 mod writer;
 
-pub use fields::AudiobookMetadata;
+pub use fields::{validate_field_names, AudiobookMetadata, EDITABLE_FIELDS};
+pub use locks::{read_locked_fields, write_locked_fields};
 pub use reader::read_metadata;
 pub use writer::write_metadata;
@@ -1,12 +1,39 @@
-use crate::metadata::AudiobookMetadata;
+use crate::hardlink;
+use crate::journal;
+use crate::lockfile;
+use crate::metadata::{read_metadata, AudiobookMetadata};
 use anyhow::{Context, Result};
 use std::path::Path;
 
 /// Write metadata to an m4b file
 pub fn write_metadata(path: &Path, metadata: &AudiobookMetadata) -> Result<()> {
+    update_tag(path, |tag| apply_metadata(tag, metadata))
+}
+
+/// Read, change and write back an m4b file's tags. Every tag write goes
+/// through here so it holds the file lock, respects hardlinks and is journaled.
+pub fn update_tag(path: &Path, edit: impl FnOnce(&mut mp4ameta::Tag)) -> Result<()> {
+    let _lock = lockfile::lock_file(path)?;
+    hardlink::guard(path)?;
     let mut tag = mp4ameta::Tag::read_from_path(path)
         .with_context(|| format!("Failed to read m4b file for writing: {}", path.display()))?;
+    let before = journal::is_recording()
+        .then(|| read_metadata(path))
+        .transpose()?;
+
+    edit(&mut tag);
+
+    tag.write_to_path(path)
+        .with_context(|| format!("Failed to write metadata to: {}", path.display()))?;
+
+    if let Some(before) = before {
+        journal::record_metadata(path, &before, &read_metadata(path)?);
+    }
 
+    Ok(())
+}
+
+fn apply_metadata(tag: &mut mp4ameta::Tag, metadata: &AudiobookMetadata) {
     // Title
     if let Some(ref title) = metadata.title {
         tag.set_title(title);
@@ -82,11 +109,6 @@ pub fn write_metadata(path: &Path, metadata: &AudiobookMetadata) -> Result<()> {
 
     // Note: We don't write duration, chapter_count, or cover_info as they are read-only
     // Publisher is also not written as mp4ameta doesn't support it directly
-
-    tag.write_to_path(path)
-        .with_context(|| format!("Failed to write metadata to: {}", path.display()))?;
-
-    Ok(())
 }
 
 #[cfg(test)]
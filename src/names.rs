@@ -0,0 +1,540 @@
+//! Canonical author/narrator names
+//!
+//! The registry maps variant spellings ("King, Stephen", "RC Bray") to one
+//! canonical name. It lives in `~/.config/audiobookctl/names.toml`:
+//!
+//! ```toml
+//! [names]
+//! "Stephen King" = ["King, Stephen", "Richard Bachman"]
+//! "R.C. Bray" = ["RC Bray"]
+//! ```
+//!
+//! Lookups, `edit` and organize path generation all use the canonical name.
+
+use crate::metadata::AudiobookMetadata;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Registry of canonical names and their aliases
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NameRegistry {
+    /// Canonical name -> aliases
+    #[serde(default)]
+    pub names: BTreeMap<String, Vec<String>>,
+}
+
+impl NameRegistry {
+    /// Load the registry from the default path (empty if it doesn't exist)
+    pub fn load() -> Result<Self> {
+        Self::load_from(&Self::registry_path()?)
+    }
+
+    /// Load the registry from a specific path
+    pub fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content =
+            fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        toml::from_str(&content).with_context(|| format!("Failed to parse {:?}", path))
+    }
+
+    /// Save the registry to the default path
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&Self::registry_path()?)
+    }
+
+    /// Save the registry to a specific path
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {:?}", parent))?;
+        }
+        let content = toml::to_string_pretty(self).context("Failed to serialize names")?;
+        fs::write(path, content).with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    /// Get the default registry path
+    pub fn registry_path() -> Result<PathBuf> {
+        let config_dir = dirs::config_dir().context("Could not determine config directory")?;
+        Ok(config_dir.join("audiobookctl").join("names.toml"))
+    }
+
+    /// Canonical form of a name, if the registry knows it
+    pub fn canonical(&self, name: &str) -> Option<&str> {
+        let key = normalize_name(name);
+        if key.is_empty() {
+            return None;
+        }
+        self.names
+            .iter()
+            .find(|(canonical, aliases)| {
+                normalize_name(canonical) == key || aliases.iter().any(|a| normalize_name(a) == key)
+            })
+            .map(|(canonical, _)| canonical.as_str())
+    }
+
+    /// The canonical form of a name, or the name unchanged
+    pub fn canonicalize(&self, name: &str) -> String {
+        self.canonical(name).unwrap_or(name).to_string()
+    }
+
+    /// Replace author and narrator with their canonical names
+    pub fn apply(&self, metadata: &mut AudiobookMetadata) {
+        for field in [&mut metadata.author, &mut metadata.narrator] {
+            if let Some(value) = field.as_mut() {
+                *value = self.canonicalize(value);
+            }
+        }
+    }
+
+    /// Record aliases for a canonical name.
+    ///
+    /// An alias that was itself canonical brings its own aliases along, so
+    /// merging two groups keeps every spelling.
+    pub fn merge(&mut self, canonical: &str, aliases: &[String]) -> Result<()> {
+        let canonical = canonical.trim();
+        if canonical.is_empty() {
+            bail!("Canonical name cannot be empty");
+        }
+
+        let mut collected: Vec<String> = Vec::new();
+        for alias in aliases.iter().map(|a| a.trim()).filter(|a| !a.is_empty()) {
+            if let Some(folded) = self.names.remove(alias) {
+                collected.extend(folded);
+            }
+            collected.push(alias.to_string());
+        }
+
+        // The canonical name may previously have been someone else's alias
+        for existing in self.names.values_mut() {
+            existing.retain(|a| a != canonical && !collected.contains(a));
+        }
+        self.names.retain(|_, aliases| !aliases.is_empty());
+
+        let entry = self.names.entry(canonical.to_string()).or_default();
+        for alias in collected {
+            if alias != canonical && !entry.contains(&alias) {
+                entry.push(alias);
+            }
+        }
+        entry.sort();
+        Ok(())
+    }
+
+    /// Remove a canonical name (with its aliases) or a single alias
+    pub fn remove(&mut self, name: &str) -> bool {
+        if self.names.remove(name).is_some() {
+            return true;
+        }
+        let mut removed = false;
+        for aliases in self.names.values_mut() {
+            let before = aliases.len();
+            aliases.retain(|a| a != name);
+            removed |= aliases.len() != before;
+        }
+        removed
+    }
+}
+
+/// Normalize a name for comparison: "King, Stephen" and "stephen  king"
+/// both become "stephen king"; "R. C. Bray" and "RC Bray" become "rc bray".
+pub fn normalize_name(name: &str) -> String {
+    // "Last, First" -> "First Last"
+    let reordered = match split_inverted(name) {
+        Some((last, first)) => format!("{} {}", first, last),
+        None => name.to_string(),
+    };
+
+    let tokens: Vec<String> = reordered
+        .split(|c: char| c.is_whitespace() || c == '.')
+        .map(|t| {
+            t.chars()
+                .filter(|c| c.is_alphanumeric() || *c == '\'' || *c == '-')
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    // Join runs of initials: ["r", "c", "bray"] -> ["rc", "bray"]
+    let mut words: Vec<String> = Vec::new();
+    let mut initials = String::new();
+    for token in tokens {
+        if token.chars().count() == 1 {
+            initials.push_str(&token);
+        } else {
+            if !initials.is_empty() {
+                words.push(std::mem::take(&mut initials));
+            }
+            words.push(token);
+        }
+    }
+    if !initials.is_empty() {
+        words.push(initials);
+    }
+
+    words.join(" ")
+}
+
+/// Split a "Last, First" name into its surname and given names.
+/// Only a single comma counts; "A, B, C" is a list, not a name.
+pub fn split_inverted(name: &str) -> Option<(&str, &str)> {
+    let (last, first) = name.split_once(',')?;
+    let (last, first) = (last.trim(), first.trim());
+    (!first.contains(',') && !first.is_empty() && !last.is_empty()).then_some((last, first))
+}
+
+/// Split a field holding several people into names.
+///
+/// ";", "&" and " and " always separate people. Commas only do in a list of
+/// three or more ("A, B, C") or a serial list ("A, B, and C"); a single
+/// comma is read as "Last, First", so "Le Guin, Ursula K." stays one name.
+pub fn split_names(value: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    for group in value.split([';', '&']).flat_map(|g| g.split(" and ")) {
+        let group = group.trim();
+        let parts: Vec<&str> = group
+            .split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .collect();
+        let serial = group.ends_with(',') && parts.len() > 1;
+        if parts.len() > 2 || serial {
+            names.extend(parts.into_iter().map(str::to_string));
+        } else if !group.is_empty() {
+            names.push(group.trim_end_matches(',').trim().to_string());
+        }
+    }
+    names
+}
+
+/// Words that belong to the surname that follows them ("Le Guin", "van der Berg")
+const SURNAME_PARTICLES: &[&str] = &[
+    "de", "da", "di", "del", "della", "der", "den", "du", "la", "le", "st.", "ter", "van", "von",
+];
+
+/// "Andy Weir" -> "Weir, Andy"; "Ursula K. Le Guin" -> "Le Guin, Ursula K.".
+/// Names already in "Last, First" form, and single names, are left alone.
+pub fn last_first(name: &str) -> String {
+    let name = name.trim();
+    if split_inverted(name).is_some() {
+        return name.to_string();
+    }
+
+    let words: Vec<&str> = name.split_whitespace().collect();
+    if words.len() < 2 {
+        return name.to_string();
+    }
+    let mut surname_start = words.len() - 1;
+    while surname_start > 1
+        && SURNAME_PARTICLES.contains(&words[surname_start - 1].to_lowercase().as_str())
+    {
+        surname_start -= 1;
+    }
+    format!(
+        "{}, {}",
+        words[surname_start..].join(" "),
+        words[..surname_start].join(" ")
+    )
+}
+
+/// A name split for comparison: given names (lowercase, initials as single
+/// letters) and the surname with its particles and punctuation removed
+#[derive(Debug, PartialEq)]
+struct NameParts {
+    given: Vec<String>,
+    surname: String,
+}
+
+impl NameParts {
+    /// "Le Guin, Ursula K." -> given ["ursula", "k"], surname "leguin";
+    /// "RC Bray" -> given ["r", "c"], surname "bray"
+    fn parse(name: &str) -> Self {
+        let reordered = match split_inverted(name) {
+            Some((last, first)) => format!("{} {}", first, last),
+            None => name.to_string(),
+        };
+
+        let mut tokens: Vec<String> = Vec::new();
+        for token in reordered.split(|c: char| c.is_whitespace() || c == '.') {
+            let token: String = token
+                .chars()
+                .filter(|c| c.is_alphanumeric() || *c == '\'' || *c == '-')
+                .collect();
+            let len = token.chars().count();
+            // Run-together initials ("RC")
+            if (2..=3).contains(&len) && token.chars().all(|c| c.is_uppercase()) {
+                tokens.extend(token.chars().map(|c| c.to_lowercase().collect()));
+            } else if len > 0 {
+                tokens.push(token.to_lowercase());
+            }
+        }
+
+        let mut surname_start = tokens.len().saturating_sub(1);
+        while surname_start > 1
+            && SURNAME_PARTICLES
+                .iter()
+                .any(|p| p.trim_end_matches('.') == tokens[surname_start - 1])
+        {
+            surname_start -= 1;
+        }
+        let surname = tokens[surname_start..]
+            .concat()
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect();
+        tokens.truncate(surname_start);
+
+        Self {
+            given: tokens,
+            surname,
+        }
+    }
+
+    /// Whether two names could be the same person: the same surname, and
+    /// given names that agree where both have them ("Stephen" matches "S.",
+    /// but "Mary" doesn't match "Mark")
+    fn compatible(&self, other: &Self) -> bool {
+        if self.surname.is_empty() || self.surname != other.surname {
+            return false;
+        }
+        if self.given.is_empty() || other.given.is_empty() {
+            return self.given.is_empty() && other.given.is_empty();
+        }
+        let is_initial_of =
+            |initial: &str, name: &str| initial.chars().count() == 1 && name.starts_with(initial);
+        self.given
+            .iter()
+            .zip(&other.given)
+            .all(|(a, b)| a == b || is_initial_of(a, b) || is_initial_of(b, a))
+    }
+}
+
+/// A group of spellings that probably name the same person
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantGroup {
+    /// (spelling, number of books), most common first
+    pub spellings: Vec<(String, usize)>,
+}
+
+impl VariantGroup {
+    /// The most common spelling, suggested as canonical
+    pub fn suggested(&self) -> &str {
+        &self.spellings[0].0
+    }
+}
+
+/// Find probable variants among names (with book counts).
+///
+/// Spellings are grouped when they have the same surname and compatible
+/// given names; each joins the first group (most common spelling first) it
+/// is compatible with in full, so "J. Smith" can't pull "John Smith" and
+/// "Jane Smith" together. Names already resolved to the same canonical name
+/// by the registry are not reported again.
+pub fn find_variants(names: &[(String, usize)], registry: &NameRegistry) -> Vec<VariantGroup> {
+    let mut sorted: Vec<&(String, usize)> = names.iter().collect();
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let mut groups: Vec<Vec<(&(String, usize), NameParts)>> = Vec::new();
+    for name in sorted {
+        let parts = NameParts::parse(&name.0);
+        match groups
+            .iter_mut()
+            .find(|group| group.iter().all(|(_, p)| p.compatible(&parts)))
+        {
+            Some(group) => group.push((name, parts)),
+            None => groups.push(vec![(name, parts)]),
+        }
+    }
+
+    let mut result: Vec<VariantGroup> = groups
+        .into_iter()
+        .filter(|group| group.len() > 1)
+        .map(|group| VariantGroup {
+            spellings: group.into_iter().map(|(name, _)| name.clone()).collect(),
+        })
+        .filter(|group| {
+            // Skip groups the registry already maps to a single name
+            let canonical: Vec<Option<&str>> = group
+                .spellings
+                .iter()
+                .map(|(n, _)| registry.canonical(n))
+                .collect();
+            !(canonical[0].is_some() && canonical.iter().all(|c| *c == canonical[0]))
+        })
+        .collect();
+
+    result.sort_by(|a, b| a.suggested().cmp(b.suggested()));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn registry() -> NameRegistry {
+        let mut registry = NameRegistry::default();
+        registry
+            .merge(
+                "Stephen King",
+                &["King, Stephen".to_string(), "Richard Bachman".to_string()],
+            )
+            .unwrap();
+        registry
+    }
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name("King, Stephen"), "stephen king");
+        assert_eq!(normalize_name("Stephen  King"), "stephen king");
+        assert_eq!(normalize_name("R.C. Bray"), "rc bray");
+        assert_eq!(normalize_name("R. C. Bray"), "rc bray");
+        assert_eq!(normalize_name("RC Bray"), "rc bray");
+    }
+
+    #[test]
+    fn test_split_names() {
+        assert_eq!(split_names("A One; B Two"), vec!["A One", "B Two"]);
+        assert_eq!(split_names("A One & B Two"), vec!["A One", "B Two"]);
+        assert_eq!(split_names("A One and B Two"), vec!["A One", "B Two"]);
+        assert_eq!(
+            split_names("A One, B Two, C Three"),
+            vec!["A One", "B Two", "C Three"]
+        );
+        assert_eq!(
+            split_names("A One, B Two, and C Three"),
+            vec!["A One", "B Two", "C Three"]
+        );
+        assert_eq!(split_names("King, Stephen"), vec!["King, Stephen"]);
+        assert_eq!(
+            split_names("Le Guin, Ursula K."),
+            vec!["Le Guin, Ursula K."]
+        );
+        assert_eq!(
+            split_names("Le Guin, Ursula K. & Straub, Peter"),
+            vec!["Le Guin, Ursula K.", "Straub, Peter"]
+        );
+    }
+
+    #[test]
+    fn test_last_first() {
+        assert_eq!(last_first("Andy Weir"), "Weir, Andy");
+        assert_eq!(last_first("Ursula K. Le Guin"), "Le Guin, Ursula K.");
+        assert_eq!(last_first("Ludwig van der Berg"), "van der Berg, Ludwig");
+        assert_eq!(last_first("Le Guin, Ursula K."), "Le Guin, Ursula K.");
+        assert_eq!(last_first("Plato"), "Plato");
+        assert_eq!(normalize_name("Le Guin, Ursula K."), "ursula k le guin");
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let registry = registry();
+        assert_eq!(registry.canonicalize("king, stephen"), "Stephen King");
+        assert_eq!(registry.canonicalize("Richard Bachman"), "Stephen King");
+        assert_eq!(registry.canonicalize("Stephen King"), "Stephen King");
+        assert_eq!(registry.canonicalize("Andy Weir"), "Andy Weir");
+
+        let mut metadata = AudiobookMetadata {
+            author: Some("Richard Bachman".to_string()),
+            narrator: Some("R.C. Bray".to_string()),
+            ..Default::default()
+        };
+        registry.apply(&mut metadata);
+        assert_eq!(metadata.author.as_deref(), Some("Stephen King"));
+        assert_eq!(metadata.narrator.as_deref(), Some("R.C. Bray"));
+    }
+
+    #[test]
+    fn test_merge_folds_existing_group() {
+        let mut registry = registry();
+        registry
+            .merge("Steve King", &["Stephen King".to_string()])
+            .unwrap();
+
+        assert!(!registry.names.contains_key("Stephen King"));
+        assert_eq!(
+            registry.names["Steve King"],
+            vec!["King, Stephen", "Richard Bachman", "Stephen King"]
+        );
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut registry = registry();
+        assert!(registry.remove("Richard Bachman"));
+        assert_eq!(registry.canonicalize("Richard Bachman"), "Richard Bachman");
+        assert!(registry.remove("Stephen King"));
+        assert!(registry.names.is_empty());
+        assert!(!registry.remove("Stephen King"));
+    }
+
+    #[test]
+    fn test_find_variants() {
+        let names = vec![
+            ("R.C. Bray".to_string(), 12),
+            ("RC Bray".to_string(), 2),
+            ("Stephen King".to_string(), 5),
+            ("King, Stephen".to_string(), 1),
+            ("Andy Weir".to_string(), 3),
+        ];
+
+        let groups = find_variants(&names, &NameRegistry::default());
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].suggested(), "R.C. Bray");
+        assert_eq!(groups[1].suggested(), "Stephen King");
+
+        // Already registered groups are not reported again
+        let groups = find_variants(&names, &registry());
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].suggested(), "R.C. Bray");
+    }
+
+    #[test]
+    fn test_find_variants_keeps_different_people_apart() {
+        let names = vec![
+            ("Mark Smith".to_string(), 4),
+            ("Mary Smith".to_string(), 3),
+            ("Joan Smith".to_string(), 2),
+            ("John Smith".to_string(), 2),
+            ("J. Smith".to_string(), 1),
+            ("Mark Smyth".to_string(), 1),
+        ];
+        let groups = find_variants(&names, &NameRegistry::default());
+        // Only "J. Smith" joins a group, and only one
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].spellings.len(), 2);
+        assert_eq!(groups[0].spellings[1].0, "J. Smith");
+    }
+
+    #[test]
+    fn test_name_parts() {
+        let a = NameParts::parse("Le Guin, Ursula K.");
+        assert_eq!(a.surname, "leguin");
+        assert_eq!(a.given, vec!["ursula", "k"]);
+        assert!(a.compatible(&NameParts::parse("Ursula K. Le Guin")));
+        assert!(a.compatible(&NameParts::parse("U. K. LeGuin")));
+        assert!(NameParts::parse("RC Bray").compatible(&NameParts::parse("R. C. Bray")));
+        assert!(!NameParts::parse("Mark Smith").compatible(&NameParts::parse("Mary Smith")));
+        assert!(!NameParts::parse("Smith").compatible(&NameParts::parse("John Smith")));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("names.toml");
+
+        registry().save_to(&path).unwrap();
+        let loaded = NameRegistry::load_from(&path).unwrap();
+        assert_eq!(loaded, registry());
+        assert_eq!(
+            NameRegistry::load_from(&temp.path().join("missing.toml")).unwrap(),
+            NameRegistry::default()
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use crate::metadata::AudiobookMetadata;
+use crate::names::{last_first, split_names, NameRegistry};
 use anyhow::{bail, Result};
 use std::path::PathBuf;
 
@@ -6,6 +7,8 @@ use std::path::PathBuf;
 ///
 /// Use `{field}` for required placeholders (path generation fails if missing).
 /// Use `{field?}` for optional placeholders (path collapses if missing).
+/// Use `[...]` for text that is only included when every placeholder inside
+/// it has a value, e.g. `{title}[ - Book {series_position}]`.
 ///
 /// Example: `{author}/{series?}/{title}/{filename}`
 /// - With series: `Author/Series/Title/file.m4b`
@@ -31,10 +34,26 @@ pub const PLACEHOLDERS: &[(&str, &str)] = &[
     ("filename", "Original filename"),
 ];
 
+/// Available placeholder filters with descriptions.
+///
+/// Filters are applied left to right: `{author|first|last_first}`.
+pub const FILTERS: &[(&str, &str)] = &[
+    (
+        "last_first",
+        "\"Andy Weir\" -> \"Weir, Andy\" (each author)",
+    ),
+    ("first", "First of several authors/narrators"),
+    ("truncate:N", "At most N characters"),
+    ("default:\"text\"", "Use text when the field is missing"),
+    ("strip_leading_article", "\"The Martian\" -> \"Martian\""),
+    ("upper", "Upper case"),
+];
+
 /// A parsed format string with placeholder segments
 #[derive(Debug, Clone)]
 pub struct FormatTemplate {
     segments: Vec<Segment>,
+    names: NameRegistry,
 }
 
 #[derive(Debug, Clone)]
@@ -44,91 +63,265 @@ enum Segment {
         name: String,
         padding: Option<usize>,
         optional: bool,
+        filters: Vec<Filter>,
     },
+    /// `[...]`: dropped when any placeholder inside is missing
+    Group(Vec<Segment>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    LastFirst,
+    First,
+    Truncate(usize),
+    Default(String),
+    StripLeadingArticle,
+    Upper,
+}
+
+impl Filter {
+    /// Parse one filter, e.g. `truncate:60` or `default:"Standalone"`
+    fn parse(spec: &str) -> Result<Self> {
+        let (name, arg) = match spec.split_once(':') {
+            Some((name, arg)) => (name.trim(), Some(arg.trim())),
+            None => (spec.trim(), None),
+        };
+
+        let filter = match name {
+            "last_first" => Filter::LastFirst,
+            "first" => Filter::First,
+            "strip_leading_article" => Filter::StripLeadingArticle,
+            "upper" => Filter::Upper,
+            "truncate" => {
+                let len = arg.and_then(|a| a.parse::<usize>().ok()).filter(|&n| n > 0);
+                match len {
+                    Some(n) => return Ok(Filter::Truncate(n)),
+                    None => bail!("Filter 'truncate' needs a length, e.g. truncate:60"),
+                }
+            }
+            "default" => match arg {
+                Some(a) => return Ok(Filter::Default(unquote(a).to_string())),
+                None => bail!("Filter 'default' needs a value, e.g. default:\"Unknown\""),
+            },
+            _ => {
+                let valid: Vec<&str> = FILTERS
+                    .iter()
+                    .map(|(n, _)| n.split(':').next().unwrap_or(n))
+                    .collect();
+                bail!(
+                    "Unknown filter '{}'. Valid filters: {}",
+                    name,
+                    valid.join(", ")
+                );
+            }
+        };
+
+        if arg.is_some() {
+            bail!("Filter '{}' takes no argument", name);
+        }
+        Ok(filter)
+    }
+
+    /// Apply the filter to a field value (None when the field is missing)
+    fn apply(&self, value: Option<String>) -> Option<String> {
+        match self {
+            Filter::Default(default) => match value {
+                Some(v) if !v.trim().is_empty() => Some(v),
+                _ => Some(default.clone()),
+            },
+            _ => value.map(|v| self.apply_to(&v)),
+        }
+    }
+
+    fn apply_to(&self, value: &str) -> String {
+        match self {
+            Filter::LastFirst => split_names(value)
+                .iter()
+                .map(|name| last_first(name))
+                .collect::<Vec<_>>()
+                .join(" & "),
+            Filter::First => split_names(value).into_iter().next().unwrap_or_default(),
+            Filter::Truncate(len) => value
+                .chars()
+                .take(*len)
+                .collect::<String>()
+                .trim_end()
+                .to_string(),
+            Filter::StripLeadingArticle => strip_leading_article(value).to_string(),
+            Filter::Upper => value.to_uppercase(),
+            Filter::Default(_) => value.to_string(),
+        }
+    }
+}
+
+fn unquote(s: &str) -> &str {
+    s.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(s)
+}
+
+/// "The Martian" -> "Martian" (also "A " and "An ")
+fn strip_leading_article(title: &str) -> &str {
+    for article in ["the ", "a ", "an "] {
+        if let Some(head) = title.get(..article.len()) {
+            let rest = title[article.len()..].trim_start();
+            if head.eq_ignore_ascii_case(article) && !rest.is_empty() {
+                return rest;
+            }
+        }
+    }
+    title
 }
 
 impl FormatTemplate {
     /// Parse a format string like "{author}/{series}/{title}/{filename}"
+    ///
+    /// Text in `[...]` is a conditional group, dropped entirely when any
+    /// placeholder inside it is missing: `{title}[ - Book {series_position}]`.
+    /// Use `\[` and `\]` for literal brackets.
     pub fn parse(format: &str) -> Result<Self> {
-        let mut segments = Vec::new();
         let mut chars = format.chars().peekable();
+        let segments = Self::parse_segments(&mut chars, false)?;
+
+        Ok(Self {
+            segments,
+            names: NameRegistry::default(),
+        })
+    }
+
+    /// Parse segments up to the end of the string, or up to the `]` closing
+    /// the group when `in_group`
+    fn parse_segments(
+        chars: &mut std::iter::Peekable<std::str::Chars>,
+        in_group: bool,
+    ) -> Result<Vec<Segment>> {
+        let mut segments = Vec::new();
         let mut literal = String::new();
+        let mut closed = false;
 
         while let Some(c) = chars.next() {
-            if c == '{' {
-                // Save any accumulated literal
-                if !literal.is_empty() {
-                    segments.push(Segment::Literal(std::mem::take(&mut literal)));
+            match c {
+                '\\' if matches!(chars.peek(), Some('[') | Some(']')) => {
+                    literal.extend(chars.next());
                 }
-
-                // Parse placeholder name and optional padding
-                let mut placeholder = String::new();
-                let mut found_close = false;
-
-                for inner in chars.by_ref() {
-                    if inner == '}' {
-                        found_close = true;
-                        break;
+                '{' | '[' | ']' => {
+                    // Save any accumulated literal
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
                     }
-                    placeholder.push(inner);
-                }
-
-                if !found_close {
-                    bail!("Unclosed placeholder '{{' in format string");
-                }
-
-                // Parse optional padding (e.g., "series_position:02") and optional marker (?)
-                // Supports both {field?} and {field?:02} syntax
-                let (name, padding, optional) = {
-                    let work = placeholder.clone();
-
-                    // Check for optional marker - can be at end ({field?}) or before colon ({field?:02})
-                    let (base, optional) = if let Some(q_pos) = work.find('?') {
-                        // Remove the ? from the string
-                        let mut s = work.clone();
-                        s.remove(q_pos);
-                        (s, true)
-                    } else {
-                        (work, false)
-                    };
-
-                    // Check for padding
-                    if let Some(colon_pos) = base.find(':') {
-                        let name = base[..colon_pos].to_string();
-                        let pad_str = &base[colon_pos + 1..];
-                        let padding = pad_str.parse::<usize>().ok();
-                        (name, padding, optional)
-                    } else {
-                        (base, None, optional)
+                    match c {
+                        '{' => segments.push(Self::parse_placeholder(chars)?),
+                        '[' => {
+                            let start = chars.clone();
+                            let group = Self::parse_segments(chars, true)?;
+                            // The group as written, without its closing `]`
+                            let len = start.clone().count() - chars.clone().count() - 1;
+                            let source: String = start.take(len).collect();
+                            check_group(&group, &source)?;
+                            segments.push(Segment::Group(group));
+                        }
+                        _ if in_group => {
+                            closed = true;
+                            break;
+                        }
+                        _ => {
+                            bail!("Unmatched ']' in format string (use \\] for a literal bracket)")
+                        }
                     }
-                };
-
-                // Validate placeholder name
-                let valid_names: Vec<&str> = PLACEHOLDERS.iter().map(|(n, _)| *n).collect();
-                if !valid_names.contains(&name.as_str()) {
-                    bail!(
-                        "Unknown placeholder '{}'. Valid placeholders: {}",
-                        name,
-                        valid_names.join(", ")
-                    );
                 }
-
-                segments.push(Segment::Placeholder {
-                    name,
-                    padding,
-                    optional,
-                });
-            } else {
-                literal.push(c);
+                _ => literal.push(c),
             }
         }
 
+        if in_group && !closed {
+            bail!("Unclosed group '[' in format string (use \\[ for a literal bracket)");
+        }
+
         // Save any remaining literal
         if !literal.is_empty() {
             segments.push(Segment::Literal(literal));
         }
 
-        Ok(Self { segments })
+        Ok(segments)
+    }
+
+    /// Parse a placeholder after its opening `{`
+    fn parse_placeholder(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<Segment> {
+        // Parse placeholder name and optional padding
+        let mut placeholder = String::new();
+        let mut found_close = false;
+
+        // Braces inside quoted filter arguments don't close the placeholder
+        let mut in_quotes = false;
+        for inner in chars.by_ref() {
+            if inner == '"' {
+                in_quotes = !in_quotes;
+            } else if inner == '}' && !in_quotes {
+                found_close = true;
+                break;
+            }
+            placeholder.push(inner);
+        }
+
+        if !found_close {
+            bail!("Unclosed placeholder '{{' in format string");
+        }
+
+        // Split off filters ({title|truncate:60|upper})
+        let mut parts = split_filters(&placeholder).into_iter();
+        let placeholder = parts.next().unwrap_or_default();
+        let filters = parts
+            .map(|spec| Filter::parse(&spec))
+            .collect::<Result<Vec<_>>>()?;
+
+        // Parse optional padding (e.g., "series_position:02") and optional marker (?)
+        // Supports both {field?} and {field?:02} syntax
+        let (name, padding, optional) = {
+            let work = placeholder.clone();
+
+            // Check for optional marker - can be at end ({field?}) or before colon ({field?:02})
+            let (base, optional) = if let Some(q_pos) = work.find('?') {
+                // Remove the ? from the string
+                let mut s = work.clone();
+                s.remove(q_pos);
+                (s, true)
+            } else {
+                (work, false)
+            };
+
+            // Check for padding
+            if let Some(colon_pos) = base.find(':') {
+                let name = base[..colon_pos].to_string();
+                let pad_str = &base[colon_pos + 1..];
+                let padding = pad_str.parse::<usize>().ok();
+                (name, padding, optional)
+            } else {
+                (base, None, optional)
+            }
+        };
+
+        // Validate placeholder name
+        let valid_names: Vec<&str> = PLACEHOLDERS.iter().map(|(n, _)| *n).collect();
+        if !valid_names.contains(&name.as_str()) {
+            bail!(
+                "Unknown placeholder '{}'. Valid placeholders: {}",
+                name,
+                valid_names.join(", ")
+            );
+        }
+
+        Ok(Segment::Placeholder {
+            name,
+            padding,
+            optional,
+            filters,
+        })
+    }
+
+    /// Use canonical author/narrator names from a registry in generated paths
+    pub fn with_names(mut self, names: NameRegistry) -> Self {
+        self.names = names;
+        self
     }
 
     /// Generate a path from metadata and original filename
@@ -139,77 +332,98 @@ impl FormatTemplate {
         original_filename: &str,
     ) -> Result<PathBuf, Vec<String>> {
         let mut missing = Vec::new();
-        let mut path_parts = Vec::new();
-        let mut current_part = String::new();
+        let mut rendered = String::new();
+        self.render(
+            &self.segments,
+            metadata,
+            original_filename,
+            &mut rendered,
+            &mut missing,
+        );
+
+        if !missing.is_empty() {
+            return Err(missing);
+        }
 
-        for segment in &self.segments {
+        // Build the path, skipping empty components (e.g. a missing {field?})
+        let mut path = PathBuf::new();
+        for part in rendered
+            .split(['/', std::path::MAIN_SEPARATOR])
+            .filter(|part| !part.is_empty())
+        {
+            path.push(part);
+        }
+
+        Ok(path)
+    }
+
+    /// Render segments into `out`, recording missing required fields.
+    /// Returns false if any placeholder had no value.
+    fn render(
+        &self,
+        segments: &[Segment],
+        metadata: &AudiobookMetadata,
+        original_filename: &str,
+        out: &mut String,
+        missing: &mut Vec<String>,
+    ) -> bool {
+        let mut complete = true;
+
+        for segment in segments {
             match segment {
-                Segment::Literal(s) => {
-                    if s.contains('/') || s.contains(std::path::MAIN_SEPARATOR) {
-                        // Split on path separator
-                        for (i, part) in s.split(['/', std::path::MAIN_SEPARATOR]).enumerate() {
-                            if i > 0 {
-                                // Push the current accumulated part as a path component
-                                if !current_part.is_empty() {
-                                    path_parts.push(std::mem::take(&mut current_part));
-                                }
-                            }
-                            current_part.push_str(part);
-                        }
-                    } else {
-                        current_part.push_str(s);
+                Segment::Literal(s) => out.push_str(s),
+                Segment::Group(inner) => {
+                    // Missing fields inside a group drop the group instead of failing
+                    let mut group = String::new();
+                    if self.render(
+                        inner,
+                        metadata,
+                        original_filename,
+                        &mut group,
+                        &mut Vec::new(),
+                    ) {
+                        out.push_str(&group);
                     }
                 }
                 Segment::Placeholder {
                     name,
                     padding,
                     optional,
+                    filters,
                 } => {
-                    let value = self.get_field_value(metadata, name, original_filename);
-                    match value {
+                    let value = self
+                        .get_field_value(metadata, name, original_filename)
+                        .map(|v| match padding {
+                            Some(pad) => format!("{:0>width$}", v, width = *pad),
+                            None => v,
+                        });
+                    let value = filters.iter().fold(value, |v, f| f.apply(v));
+                    // Sanitize for filesystem
+                    match value.map(|v| sanitize_path_component(&v)) {
                         Some(v) => {
-                            let formatted = if let Some(pad) = padding {
-                                format!("{:0>width$}", v, width = *pad)
-                            } else {
-                                v
-                            };
-                            // Sanitize for filesystem
-                            let sanitized = sanitize_path_component(&formatted);
-                            current_part.push_str(&sanitized);
+                            // An empty value only counts as missing for groups
+                            complete &= !v.is_empty();
+                            out.push_str(&v);
                         }
                         None if *optional => {
-                            // Optional placeholder missing - mark current part as empty
-                            // so it gets filtered out
-                            // Don't add to missing list
+                            // Optional placeholder missing - leaves its path
+                            // component empty so it gets filtered out
+                            complete = false;
                         }
                         None => {
+                            complete = false;
                             if name != "filename" {
                                 missing.push(name.clone());
                             }
                             // Use placeholder text for now (will fail later if missing)
-                            current_part.push_str(&format!("{{{}}}", name));
+                            out.push_str(&format!("{{{}}}", name));
                         }
                     }
                 }
             }
         }
 
-        // Push the final part
-        if !current_part.is_empty() {
-            path_parts.push(current_part);
-        }
-
-        if !missing.is_empty() {
-            return Err(missing);
-        }
-
-        // Build the path
-        let mut path = PathBuf::new();
-        for part in path_parts {
-            path.push(part);
-        }
-
-        Ok(path)
+        complete
     }
 
     fn get_field_value(
@@ -219,7 +433,10 @@ impl FormatTemplate {
         original_filename: &str,
     ) -> Option<String> {
         match name {
-            "author" => metadata.author.clone(),
+            "author" => metadata
+                .author
+                .as_deref()
+                .map(|a| self.names.canonicalize(a)),
             "title" => metadata.title.clone(),
             "series" => metadata.series.clone(),
             "series_position" => metadata.series_position.map(|n| n.to_string()),
@@ -230,7 +447,10 @@ impl FormatTemplate {
                     None => Some(title.clone()),
                 }
             }
-            "narrator" => metadata.narrator.clone(),
+            "narrator" => metadata
+                .narrator
+                .as_deref()
+                .map(|n| self.names.canonicalize(n)),
             "year" => metadata.year.map(|n| n.to_string()),
             "genre" => metadata.genre.clone(),
             "publisher" => metadata.publisher.clone(),
@@ -242,6 +462,51 @@ impl FormatTemplate {
     }
 }
 
+/// Reject groups that only make sense as the literal brackets `[` and `]`
+/// used to be, like `[{asin}]` or `[Unabridged]`: as groups they would
+/// silently drop the brackets from every existing path
+fn check_group(group: &[Segment], source: &str) -> Result<()> {
+    let optional = match group {
+        [Segment::Placeholder { name, optional, .. }] => {
+            if *optional {
+                format!(", or '{}' for an optional field", source)
+            } else {
+                format!(
+                    ", or '{{{}?{}' for an optional field",
+                    name,
+                    &source[1 + name.len()..]
+                )
+            }
+        }
+        _ if group.iter().all(|s| matches!(s, Segment::Literal(_))) => String::new(),
+        _ => return Ok(()),
+    };
+    bail!(
+        "'[{source}]' in the format would lose its brackets: [...] now marks text left out when a \
+         field inside it is missing. Write '\\[{source}\\]' to keep the brackets in paths{optional}"
+    )
+}
+
+/// Split a placeholder on `|`, ignoring any inside quoted filter arguments
+fn split_filters(placeholder: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut in_quotes = false;
+    for c in placeholder.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '|' if !in_quotes => {
+                parts.push(String::new());
+                continue;
+            }
+            _ => {}
+        }
+        if let Some(part) = parts.last_mut() {
+            part.push(c);
+        }
+    }
+    parts
+}
+
 /// Sanitize a string for use as a path component
 /// Removes/replaces characters that are problematic on filesystems
 fn sanitize_path_component(s: &str) -> String {
@@ -278,6 +543,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_generate_path_uses_canonical_names() {
+        let mut names = NameRegistry::default();
+        names
+            .merge("Stephen King", &["King, Stephen".to_string()])
+            .unwrap();
+        let template = FormatTemplate::parse("{author}/{title}/{filename}")
+            .unwrap()
+            .with_names(names);
+        let metadata = AudiobookMetadata {
+            title: Some("It".to_string()),
+            author: Some("King, Stephen".to_string()),
+            ..Default::default()
+        };
+
+        let path = template.generate_path(&metadata, "book.m4b").unwrap();
+        assert_eq!(path, PathBuf::from("Stephen King/It/book.m4b"));
+    }
+
     #[test]
     fn test_parse_simple_format() {
         // Just verify parsing succeeds
@@ -315,6 +599,155 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_filters() {
+        let template = FormatTemplate::parse(
+            "{author|first|last_first}/{series|default:\"Standalone\"}/{title|strip_leading_article|truncate:10|upper}/{filename}",
+        )
+        .unwrap();
+        let metadata = AudiobookMetadata {
+            title: Some("The Way of Kings".to_string()),
+            author: Some("Brandon Sanderson & Someone Else".to_string()),
+            ..Default::default()
+        };
+        let path = template.generate_path(&metadata, "book.m4b").unwrap();
+        assert_eq!(
+            path,
+            PathBuf::from("Sanderson, Brandon/Standalone/WAY OF KIN/book.m4b")
+        );
+    }
+
+    #[test]
+    fn test_default_filter_fills_missing_field() {
+        let template =
+            FormatTemplate::parse("{author}/{year|default:\"Unknown\"} - {title}/{filename}")
+                .unwrap();
+        let metadata = sample_metadata(); // year is None
+        let path = template.generate_path(&metadata, "book.m4b").unwrap();
+        assert_eq!(
+            path,
+            PathBuf::from("Andy Weir/Unknown - Project Hail Mary/book.m4b")
+        );
+    }
+
+    #[test]
+    fn test_name_filters_keep_multi_word_surnames() {
+        let template =
+            FormatTemplate::parse("{author|first}/{narrator|last_first}/{filename}").unwrap();
+        let metadata = AudiobookMetadata {
+            author: Some("Le Guin, Ursula K.".to_string()),
+            narrator: Some("Ursula K. Le Guin".to_string()),
+            ..Default::default()
+        };
+        let path = template.generate_path(&metadata, "book.m4b").unwrap();
+        assert_eq!(
+            path,
+            PathBuf::from("Le Guin, Ursula K./Le Guin, Ursula K./book.m4b")
+        );
+    }
+
+    #[test]
+    fn test_strip_leading_article() {
+        assert_eq!(
+            strip_leading_article("An Absolutely Remarkable Thing"),
+            "Absolutely Remarkable Thing"
+        );
+        assert_eq!(strip_leading_article("Anathem"), "Anathem");
+        assert_eq!(strip_leading_article("A"), "A");
+    }
+
+    #[test]
+    fn test_invalid_filters_fail_at_parse_time() {
+        let err = FormatTemplate::parse("{title|lower}").unwrap_err();
+        assert!(err.to_string().contains("Unknown filter 'lower'"));
+        assert!(FormatTemplate::parse("{title|truncate}").is_err());
+        assert!(FormatTemplate::parse("{title|truncate:abc}").is_err());
+        assert!(FormatTemplate::parse("{title|upper:1}").is_err());
+        assert!(FormatTemplate::parse("{title|default}").is_err());
+        // Quoted arguments may contain | and }
+        FormatTemplate::parse("{series|default:\"A|B}\"}/{filename}").unwrap();
+    }
+
+    #[test]
+    fn test_conditional_group() {
+        let template =
+            FormatTemplate::parse("{author}/{title}[ - Book {series_position}]/{filename}")
+                .unwrap();
+
+        let metadata = sample_metadata();
+        let path = template.generate_path(&metadata, "book.m4b").unwrap();
+        assert_eq!(
+            path,
+            PathBuf::from("Andy Weir/Project Hail Mary - Book 1/book.m4b")
+        );
+
+        // A missing field drops the whole group instead of failing
+        let metadata = AudiobookMetadata {
+            series_position: None,
+            ..sample_metadata()
+        };
+        let path = template.generate_path(&metadata, "book.m4b").unwrap();
+        assert_eq!(path, PathBuf::from("Andy Weir/Project Hail Mary/book.m4b"));
+    }
+
+    #[test]
+    fn test_conditional_group_any_missing_drops_it() {
+        let template = FormatTemplate::parse(
+            "{author}/[{series} {series_position:02} - ]{title}[ ({narrator}[, {year}])]/{filename}",
+        )
+        .unwrap();
+        let metadata = AudiobookMetadata {
+            narrator: Some("Ray Porter".to_string()),
+            series_position: None,
+            ..sample_metadata()
+        };
+        let path = template.generate_path(&metadata, "book.m4b").unwrap();
+        assert_eq!(
+            path,
+            PathBuf::from("Andy Weir/Project Hail Mary (Ray Porter)/book.m4b")
+        );
+
+        // A group holding a whole path component collapses like {field?}
+        let template = FormatTemplate::parse("{author}/[{series}/]{title}/{filename}").unwrap();
+        let metadata = AudiobookMetadata {
+            series: None,
+            ..sample_metadata()
+        };
+        let path = template.generate_path(&metadata, "book.m4b").unwrap();
+        assert_eq!(path, PathBuf::from("Andy Weir/Project Hail Mary/book.m4b"));
+    }
+
+    #[test]
+    fn test_group_brackets() {
+        let template =
+            FormatTemplate::parse("{author}/{title} \\[Unabridged\\]/{filename}").unwrap();
+        let path = template
+            .generate_path(&sample_metadata(), "book.m4b")
+            .unwrap();
+        assert_eq!(
+            path,
+            PathBuf::from("Andy Weir/Project Hail Mary [Unabridged]/book.m4b")
+        );
+
+        assert!(FormatTemplate::parse("{author}/[{series}/{title}").is_err());
+
+        // Formats written when brackets were literal must be migrated, not
+        // silently produce different paths
+        let err = FormatTemplate::parse("{author}/{title} [{asin}]/{filename}").unwrap_err();
+        assert!(err.to_string().contains("'\\[{asin}\\]'"));
+        let err =
+            FormatTemplate::parse("{title} [{author|last_first|default:\"Anon\"}]").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("'\\[{author|last_first|default:\"Anon\"}\\]'"));
+        assert!(err
+            .to_string()
+            .contains("'{author?|last_first|default:\"Anon\"}'"));
+        assert!(FormatTemplate::parse("{author}/{title} [Unabridged]/{filename}").is_err());
+        FormatTemplate::parse("{author}/{title} \\[{asin}\\]/{filename}").unwrap();
+        assert!(FormatTemplate::parse("{author}/{series}]/{title}").is_err());
+    }
+
     #[test]
     fn test_sanitize_path_component() {
         // Colons with space become " - " (subtitle format)
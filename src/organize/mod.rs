@@ -1,9 +1,11 @@
 pub mod format;
 pub mod planner;
+pub mod run;
 pub mod scanner;
+pub mod transfer;
 pub mod tree;
 
-pub use format::{FormatTemplate, PLACEHOLDERS};
+pub use format::{FormatTemplate, FILTERS, PLACEHOLDERS};
 #[allow(unused_imports)]
 pub use planner::{
     AlreadyPresent, AuxiliaryOperation, Conflict, FixPlan, OrganizePlan, PlanProgress,
@@ -11,3 +13,4 @@ pub use planner::{
 };
 #[allow(unused_imports)]
 pub use scanner::{scan_directory, scan_directory_with_progress, AuxiliaryFile, ScannedFile};
+pub use transfer::TransferMode;
@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
@@ -22,7 +23,7 @@ pub struct PlannedOperation {
 }
 
 /// A file that already exists at destination with matching content
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(dead_code)] // Will be used by organize command display in upcoming tasks
 pub struct AlreadyPresent {
     pub source: PathBuf,
@@ -388,4 +389,36 @@ mod tests {
         assert_eq!(plan.uncategorized.len(), 1);
         assert_eq!(plan.uncategorized[0].missing_fields, vec!["author"]);
     }
+
+    #[test]
+    fn test_fix_plan_honours_conditional_groups() {
+        let mut in_series = make_scanned_file(
+            "/library/Author A/Title 1 - Book 2/book1.m4b",
+            "Author A",
+            "Title 1",
+        );
+        in_series.metadata.series_position = Some(2);
+        let files = vec![
+            in_series,
+            make_scanned_file("/library/Author B/Title 2/book2.m4b", "Author B", "Title 2"),
+            make_scanned_file(
+                "/library/Author C/Title 3 - Book /book3.m4b",
+                "Author C",
+                "Title 3",
+            ),
+        ];
+
+        let template =
+            FormatTemplate::parse("{author}/{title}[ - Book {series_position}]/{filename}")
+                .unwrap();
+        let plan = FixPlan::build(&files, &template, Path::new("/library"));
+
+        assert_eq!(plan.compliant.len(), 2);
+        assert!(plan.uncategorized.is_empty());
+        assert_eq!(plan.needs_fix.len(), 1);
+        assert_eq!(
+            plan.needs_fix[0].dest,
+            PathBuf::from("/library/Author C/Title 3/book3.m4b")
+        );
+    }
 }
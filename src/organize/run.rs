@@ -0,0 +1,311 @@
+//! Crash-safe organize runs
+//!
+//! Before `organize --no-dry-run` touches anything, its plan is written to
+//! `~/.cache/audiobookctl/organize/<dest-hash>.json`, and each step is marked
+//! as it starts and as it finishes. An interrupted run can be continued with
+//! `--resume` or undone with `--rollback`. The file is removed once the run,
+//! including its database update, completes.
+
+use crate::organize::{AlreadyPresent, OrganizePlan, TransferMode};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where a step stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Pending,
+    /// Begun but not known to have finished (the run was interrupted)
+    Started,
+    Done,
+    /// Left alone because the destination already existed
+    Skipped,
+}
+
+/// What a step puts into the library
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepKind {
+    Audiobook,
+    Auxiliary,
+    /// An audiobook with missing metadata, put in `__uncategorized__`
+    Uncategorized,
+}
+
+/// One file of the run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunStep {
+    pub kind: StepKind,
+    pub source: PathBuf,
+    pub dest: PathBuf,
+    pub status: StepStatus,
+    /// How the file got there (links fall back to copying)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub used: Option<TransferMode>,
+}
+
+impl RunStep {
+    /// Whether the step needs no more work
+    pub fn is_finished(&self) -> bool {
+        matches!(self.status, StepStatus::Done | StepStatus::Skipped)
+    }
+
+    /// Whether the step is an m4b (as opposed to an auxiliary file)
+    pub fn is_audiobook(&self) -> bool {
+        self.kind != StepKind::Auxiliary
+    }
+}
+
+/// An organize run into one library
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OrganizeRun {
+    pub source: PathBuf,
+    pub dest: PathBuf,
+    pub mode: TransferMode,
+    pub started_at: DateTime<Utc>,
+    pub steps: Vec<RunStep>,
+    pub already_present: Vec<AlreadyPresent>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl OrganizeRun {
+    /// Turn a plan into a run (not yet saved). Audiobooks are followed by
+    /// their auxiliary files, then uncategorized files if allowed.
+    pub fn new(
+        plan: &OrganizePlan,
+        source: &Path,
+        dest: &Path,
+        mode: TransferMode,
+        allow_uncategorized: bool,
+    ) -> Result<Self> {
+        fs::create_dir_all(dest).with_context(|| format!("Failed to create {:?}", dest))?;
+        let path = Self::locate(dest)?;
+        Self::from_plan(plan, source, dest, mode, allow_uncategorized, path)
+    }
+
+    fn from_plan(
+        plan: &OrganizePlan,
+        source: &Path,
+        dest: &Path,
+        mode: TransferMode,
+        allow_uncategorized: bool,
+        path: PathBuf,
+    ) -> Result<Self> {
+        let step = |kind, source: &Path, dest: &Path| RunStep {
+            kind,
+            source: absolute(source),
+            dest: absolute(dest),
+            status: StepStatus::Pending,
+            used: None,
+        };
+
+        let mut steps = Vec::new();
+        for op in &plan.operations {
+            steps.push(step(StepKind::Audiobook, &op.source, &op.dest));
+            for aux in &op.auxiliary {
+                steps.push(step(StepKind::Auxiliary, &aux.source, &aux.dest));
+            }
+        }
+        if allow_uncategorized {
+            let uncategorized_dir = dest.join("__uncategorized__");
+            for file in &plan.uncategorized {
+                let filename = file.source.file_name().context("File has no filename")?;
+                steps.push(step(
+                    StepKind::Uncategorized,
+                    &file.source,
+                    &uncategorized_dir.join(filename),
+                ));
+            }
+        }
+
+        Ok(Self {
+            source: absolute(source),
+            dest: absolute(dest),
+            mode,
+            started_at: Utc::now(),
+            steps,
+            already_present: plan.already_present.clone(),
+            path,
+        })
+    }
+
+    /// Load the interrupted run into a library, if any
+    pub fn load(dest: &Path) -> Result<Option<Self>> {
+        if !dest.exists() {
+            return Ok(None);
+        }
+        let path = Self::locate(dest)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read organize run: {}", path.display()))?;
+        let mut run: Self = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse organize run: {}", path.display()))?;
+        run.path = path;
+        Ok(Some(run))
+    }
+
+    /// Write the run to disk (via a temp file so a crash can't truncate it)
+    pub fn save(&self) -> Result<()> {
+        let content = serde_json::to_string(self).context("Failed to serialize organize run")?;
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, content)
+            .with_context(|| format!("Failed to write organize run: {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Failed to write organize run: {}", self.path.display()))?;
+        Ok(())
+    }
+
+    /// Delete the run file
+    pub fn remove(&self) -> Result<()> {
+        if self.path.exists() {
+            fs::remove_file(&self.path).with_context(|| {
+                format!("Failed to remove organize run: {}", self.path.display())
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Number of finished steps
+    pub fn finished_count(&self) -> usize {
+        self.steps.iter().filter(|s| s.is_finished()).count()
+    }
+
+    /// Run file path for a library directory
+    fn locate(dest: &Path) -> Result<PathBuf> {
+        let abs_dest = dest
+            .canonicalize()
+            .with_context(|| format!("Failed to get absolute path for: {}", dest.display()))?;
+
+        let runs_dir = dirs::cache_dir()
+            .context("Could not determine cache directory")?
+            .join("audiobookctl")
+            .join("organize");
+        fs::create_dir_all(&runs_dir).with_context(|| {
+            format!(
+                "Failed to create organize runs directory: {}",
+                runs_dir.display()
+            )
+        })?;
+
+        let mut hasher = Sha256::new();
+        hasher.update(abs_dest.to_string_lossy().as_bytes());
+        let hash = hex::encode(&hasher.finalize()[..8]);
+
+        Ok(runs_dir.join(format!("{}.json", hash)))
+    }
+}
+
+/// Steps are stored with absolute paths so `--resume` works from any directory
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::organize::{AuxiliaryOperation, PlannedOperation, UncategorizedFile};
+    use tempfile::TempDir;
+
+    fn plan(temp: &Path) -> OrganizePlan {
+        OrganizePlan {
+            operations: vec![PlannedOperation {
+                source: temp.join("inbox/book.m4b"),
+                dest: temp.join("library/Author/Book/book.m4b"),
+                auxiliary: vec![AuxiliaryOperation {
+                    source: temp.join("inbox/book.cue"),
+                    dest: temp.join("library/Author/Book/book.cue"),
+                }],
+            }],
+            already_present: Vec::new(),
+            uncategorized: vec![UncategorizedFile {
+                source: temp.join("inbox/untagged.m4b"),
+                missing_fields: vec!["author".to_string()],
+            }],
+            conflicts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_run_steps_from_plan() {
+        let temp = TempDir::new().unwrap();
+        let temp = temp.path();
+        let plan = plan(temp);
+
+        let run = OrganizeRun::from_plan(
+            &plan,
+            &temp.join("inbox"),
+            &temp.join("library"),
+            TransferMode::Copy,
+            true,
+            temp.join("run.json"),
+        )
+        .unwrap();
+        let kinds: Vec<StepKind> = run.steps.iter().map(|s| s.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                StepKind::Audiobook,
+                StepKind::Auxiliary,
+                StepKind::Uncategorized
+            ]
+        );
+        assert_eq!(
+            run.steps[2].dest,
+            temp.join("library/__uncategorized__/untagged.m4b")
+        );
+        assert!(run.steps.iter().all(|s| s.status == StepStatus::Pending));
+        assert_eq!(run.finished_count(), 0);
+
+        // Uncategorized files are only included when allowed
+        let run = OrganizeRun::from_plan(
+            &plan,
+            &temp.join("inbox"),
+            &temp.join("library"),
+            TransferMode::Copy,
+            false,
+            temp.join("run.json"),
+        )
+        .unwrap();
+        assert_eq!(run.steps.len(), 2);
+    }
+
+    #[test]
+    fn test_run_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let mut run = OrganizeRun {
+            source: temp.path().join("inbox"),
+            dest: temp.path().join("library"),
+            mode: TransferMode::Move,
+            started_at: Utc::now(),
+            steps: vec![RunStep {
+                kind: StepKind::Audiobook,
+                source: temp.path().join("inbox/book.m4b"),
+                dest: temp.path().join("library/book.m4b"),
+                status: StepStatus::Pending,
+                used: None,
+            }],
+            already_present: Vec::new(),
+            path: temp.path().join("run.json"),
+        };
+        run.steps[0].status = StepStatus::Done;
+        run.steps[0].used = Some(TransferMode::Move);
+        run.save().unwrap();
+
+        let content = fs::read_to_string(temp.path().join("run.json")).unwrap();
+        let loaded: OrganizeRun = serde_json::from_str(&content).unwrap();
+        assert_eq!(loaded.mode, TransferMode::Move);
+        assert_eq!(loaded.steps, run.steps);
+        assert_eq!(loaded.finished_count(), 1);
+
+        run.remove().unwrap();
+        assert!(!temp.path().join("run.json").exists());
+    }
+}
@@ -0,0 +1,302 @@
+//! Getting files into the library: copy, move, hardlink or reflink
+
+use crate::hash::sha256_file;
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+/// How organize puts files into the library
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferMode {
+    /// Copy, leaving the source in place
+    #[default]
+    Copy,
+    /// Move, removing the source once the destination is verified
+    Move,
+    /// Hardlink, so the source (e.g. a seeding download) and the library share
+    /// one file; copies when they are on different filesystems
+    Hardlink,
+    /// Reflink (copy-on-write clone) where the filesystem supports it;
+    /// copies otherwise
+    Reflink,
+}
+
+impl TransferMode {
+    /// The verb for prompts ("copy")
+    pub fn verb(&self) -> &'static str {
+        match self {
+            TransferMode::Copy => "copy",
+            TransferMode::Move => "move",
+            TransferMode::Hardlink => "hardlink",
+            TransferMode::Reflink => "reflink",
+        }
+    }
+
+    /// Present participle for progress headings ("Copying")
+    pub fn doing(&self) -> &'static str {
+        match self {
+            TransferMode::Copy => "Copying",
+            TransferMode::Move => "Moving",
+            TransferMode::Hardlink => "Hardlinking",
+            TransferMode::Reflink => "Reflinking",
+        }
+    }
+
+    /// Past tense for summaries ("copied")
+    pub fn done(&self) -> &'static str {
+        match self {
+            TransferMode::Copy => "copied",
+            TransferMode::Move => "moved",
+            TransferMode::Hardlink => "hardlinked",
+            TransferMode::Reflink => "reflinked",
+        }
+    }
+}
+
+/// Temp file a copy is written to before being renamed into place, so an
+/// interrupted copy never leaves a truncated file at `dest`
+pub fn part_path(dest: &Path) -> PathBuf {
+    let mut part = dest.as_os_str().to_owned();
+    part.push(".part");
+    PathBuf::from(part)
+}
+
+/// Copy a file and check the copy's SHA-256 against `source_hash`. The copy
+/// is written to a `.part` file and only renamed to `dest` once verified.
+pub fn copy_verified(source: &Path, dest: &Path, source_hash: &str) -> Result<()> {
+    let part = part_path(dest);
+    fs::copy(source, &part)
+        .with_context(|| format!("Failed to copy {:?} to {:?}", source, part))?;
+
+    let dest_hash =
+        sha256_file(&part).with_context(|| format!("Failed to hash destination {:?}", part))?;
+    if dest_hash != source_hash {
+        let _ = fs::remove_file(&part);
+        bail!(
+            "Copy verification failed: {:?} -> {:?}\n  Source hash: {}\n  Dest hash:   {}",
+            source,
+            dest,
+            source_hash,
+            dest_hash
+        );
+    }
+    fs::rename(&part, dest).with_context(|| format!("Failed to rename {:?} to {:?}", part, dest))
+}
+
+/// Copy a file with verification, hashing the source if `source_hash` is
+/// not already known. Returns the verified hash.
+pub fn copy_file(source: &Path, dest: &Path, source_hash: Option<&str>) -> Result<String> {
+    let source_hash = match source_hash {
+        Some(hash) => hash.to_string(),
+        None => sha256_file(source)?,
+    };
+    copy_verified(source, dest, &source_hash)?;
+    Ok(source_hash)
+}
+
+/// Move a file. A rename when both paths are on the same filesystem;
+/// otherwise copy, verify the copy's hash, and only then delete the source.
+///
+/// `source_hash` is the source's SHA-256 if already known; a rename never
+/// reads the file. Returns the verified hash when the file had to be copied.
+pub fn move_file(source: &Path, dest: &Path, source_hash: Option<&str>) -> Result<Option<String>> {
+    match fs::rename(source, dest) {
+        Ok(()) => return Ok(None),
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {}
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to move {:?} to {:?}", source, dest))
+        }
+    }
+
+    let hash = copy_file(source, dest, source_hash)?;
+    fs::remove_file(source)
+        .with_context(|| format!("Copied {:?} but failed to remove the source", source))?;
+    Ok(Some(hash))
+}
+
+/// Hardlink `dest` to `source`, falling back to a verified copy when they are
+/// on different filesystems. Returns the mode actually used.
+pub fn hardlink_file(
+    source: &Path,
+    dest: &Path,
+    source_hash: Option<&str>,
+) -> Result<TransferMode> {
+    match fs::hard_link(source, dest) {
+        Ok(()) => Ok(TransferMode::Hardlink),
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            copy_file(source, dest, source_hash)?;
+            Ok(TransferMode::Copy)
+        }
+        Err(e) => Err(e).with_context(|| format!("Failed to hardlink {:?} to {:?}", dest, source)),
+    }
+}
+
+/// Whether two paths are names for the same file (hardlinked)
+#[cfg(unix)]
+pub fn same_file(a: &Path, b: &Path) -> Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+    let (a, b) = (
+        fs::metadata(a).with_context(|| format!("Failed to stat {:?}", a))?,
+        fs::metadata(b).with_context(|| format!("Failed to stat {:?}", b))?,
+    );
+    Ok(a.dev() == b.dev() && a.ino() == b.ino())
+}
+
+#[cfg(not(unix))]
+pub fn same_file(_a: &Path, _b: &Path) -> Result<bool> {
+    Ok(false)
+}
+
+/// Clone `source` to `dest` without copying its data, falling back to a
+/// verified copy when the filesystem can't. Returns the mode actually used.
+pub fn reflink_file(source: &Path, dest: &Path, source_hash: Option<&str>) -> Result<TransferMode> {
+    let part = part_path(dest);
+    match clone_file(source, &part) {
+        Ok(()) => {
+            fs::rename(&part, dest)
+                .with_context(|| format!("Failed to rename {:?} to {:?}", part, dest))?;
+            Ok(TransferMode::Reflink)
+        }
+        Err(_) => {
+            copy_file(source, dest, source_hash)?;
+            Ok(TransferMode::Copy)
+        }
+    }
+}
+
+/// Clone a file with the FICLONE ioctl (btrfs, XFS, bcachefs, ...)
+#[cfg(target_os = "linux")]
+fn clone_file(source: &Path, dest: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let src = fs::File::open(source)?;
+    let dst = fs::File::create_new(dest)?;
+    // SAFETY: both descriptors are open for the duration of the call, and
+    // FICLONE takes the source descriptor as its argument
+    let result = unsafe { libc::ioctl(dst.as_raw_fd(), libc::FICLONE, src.as_raw_fd()) };
+    if result == -1 {
+        let err = io::Error::last_os_error();
+        drop(dst);
+        let _ = fs::remove_file(dest);
+        return Err(err);
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn clone_file(_source: &Path, _dest: &Path) -> io::Result<()> {
+    Err(io::Error::from(ErrorKind::Unsupported))
+}
+
+/// Remove directories left empty after moving a file out of them, walking up
+/// from the file's directory but never removing `root` or anything above it
+pub fn cleanup_empty_dirs(file_path: &Path, root: &Path) {
+    let mut current = file_path.parent();
+
+    while let Some(dir) = current {
+        if !dir.starts_with(root) || dir == root {
+            break;
+        }
+        // Try to remove the directory (will fail if not empty)
+        if fs::remove_dir(dir).is_err() {
+            break;
+        }
+        current = dir.parent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_move_file_same_filesystem() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("inbox").join("book.m4b");
+        let dest = temp.path().join("library").join("book.m4b");
+        fs::create_dir_all(source.parent().unwrap()).unwrap();
+        fs::create_dir_all(dest.parent().unwrap()).unwrap();
+        fs::write(&source, "audio").unwrap();
+
+        move_file(&source, &dest, None).unwrap();
+        assert!(!source.exists());
+        assert_eq!(fs::read(&dest).unwrap(), b"audio");
+    }
+
+    #[test]
+    fn test_copy_verified_rejects_wrong_hash() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("book.m4b");
+        let dest = temp.path().join("copy.m4b");
+        fs::write(&source, "audio").unwrap();
+
+        let hash = sha256_file(&source).unwrap();
+        copy_verified(&source, &dest, &hash).unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), b"audio");
+
+        // A failed copy leaves neither the destination nor its .part behind
+        let other = temp.path().join("other.m4b");
+        assert!(copy_verified(&source, &other, "0000").is_err());
+        assert!(!other.exists());
+        assert!(!part_path(&other).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlink_file_shares_data() {
+        use std::os::unix::fs::MetadataExt;
+
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("seed.m4b");
+        let dest = temp.path().join("book.m4b");
+        fs::write(&source, "audio").unwrap();
+
+        assert_eq!(
+            hardlink_file(&source, &dest, None).unwrap(),
+            TransferMode::Hardlink
+        );
+        assert_eq!(fs::metadata(&source).unwrap().nlink(), 2);
+        assert_eq!(
+            fs::metadata(&source).unwrap().ino(),
+            fs::metadata(&dest).unwrap().ino()
+        );
+    }
+
+    #[test]
+    fn test_reflink_file_copies_when_unsupported() {
+        let temp = TempDir::new().unwrap();
+        let source = temp.path().join("seed.m4b");
+        let dest = temp.path().join("book.m4b");
+        fs::write(&source, "audio").unwrap();
+
+        // Reflinks only work on some filesystems; either way the content arrives
+        let mode = reflink_file(&source, &dest, None).unwrap();
+        assert!(matches!(mode, TransferMode::Reflink | TransferMode::Copy));
+        assert_eq!(fs::read(&dest).unwrap(), b"audio");
+        assert!(source.exists());
+    }
+
+    #[test]
+    fn test_cleanup_empty_dirs_stops_at_root() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path().join("inbox");
+        let book_dir = root.join("Author").join("Book");
+        fs::create_dir_all(&book_dir).unwrap();
+        fs::write(root.join("notes.txt"), "keep").unwrap();
+
+        cleanup_empty_dirs(&book_dir.join("book.m4b"), &root);
+        assert!(!root.join("Author").exists());
+        assert!(root.exists());
+
+        // The root itself is never removed, even when empty
+        fs::remove_file(root.join("notes.txt")).unwrap();
+        fs::create_dir_all(&book_dir).unwrap();
+        cleanup_empty_dirs(&book_dir.join("book.m4b"), &root);
+        assert!(root.exists());
+    }
+}
@@ -1,47 +1,170 @@
-use anyhow::{Context, Result};
+use crate::config::{BackupLocation, BackupMode, BackupsConfig, Config};
+use crate::hash::{audio_sha256, file_and_audio_sha256, sha256_file};
+use crate::journal;
+use crate::lockfile;
+use crate::metadata::{read_metadata, AudiobookMetadata};
+use crate::safety::absolute_path;
+use crate::safety::store::{BackupStore, StoredBackup};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use mp4ameta::{Data, FreeformIdent, Tag};
+use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
+use tracing::info;
 use walkdir::WalkDir;
 
-/// Create a backup of a file before modifying it
+/// Freeform atom in a metadata backup holding the backed-up file's hash
+const SOURCE_HASH_IDENT: FreeformIdent<'static> =
+    FreeformIdent::new("org.audiobookctl", "SOURCE_SHA256");
+
+/// Freeform atom in a metadata backup holding the hash of the file's audio data
+const AUDIO_HASH_IDENT: FreeformIdent<'static> =
+    FreeformIdent::new("org.audiobookctl", "AUDIO_SHA256");
+
+/// Create a backup of a file before modifying it, as set in `[backups]`
 pub fn create_backup(file_path: &Path) -> Result<PathBuf> {
-    let backup_path = backup_path_for(file_path);
+    create_backup_with(file_path, &Config::load()?.backups)
+}
+
+/// Create a backup of a file with explicit settings
+pub fn create_backup_with(file_path: &Path, config: &BackupsConfig) -> Result<PathBuf> {
+    let kind = BackupKind::from(config.mode);
+
+    match config.location {
+        BackupLocation::Alongside => {
+            let backup_path = match kind {
+                BackupKind::Full => backup_path_for(file_path),
+                BackupKind::Metadata => metadata_backup_path_for(file_path),
+            };
+            write_backup(file_path, kind, &backup_path)?;
+            Ok(backup_path)
+        }
+        BackupLocation::Store => {
+            let store = BackupStore::open(config)?;
+            let entry = store.add(file_path, kind)?;
+            let pruned = store.prune(config)?;
+            if !pruned.is_empty() {
+                info!("pruned {} old backup(s) from the store", pruned.len());
+            }
+            Ok(store.object_path(&entry))
+        }
+    }
+}
 
-    fs::copy(file_path, &backup_path).with_context(|| {
-        format!(
-            "Failed to create backup: {} -> {}",
-            file_path.display(),
-            backup_path.display()
-        )
-    })?;
+/// Write a backup of a file to `backup_path`
+pub(super) fn write_backup(file_path: &Path, kind: BackupKind, backup_path: &Path) -> Result<()> {
+    match kind {
+        BackupKind::Full => {
+            fs::copy(file_path, backup_path).with_context(|| {
+                format!(
+                    "Failed to create backup: {} -> {}",
+                    file_path.display(),
+                    backup_path.display()
+                )
+            })?;
+        }
+        // The file's tag atoms (the `moov/udta/meta` item list) plus its file
+        // and audio hashes, as a minimal MPEG-4 file
+        BackupKind::Metadata => {
+            let mut tag = Tag::read_from_path(file_path)
+                .with_context(|| format!("Failed to read tags from {}", file_path.display()))?;
+            let (file_hash, audio_hash) = file_and_audio_sha256(file_path)?;
+            tag.set_data(SOURCE_HASH_IDENT, Data::Utf8(file_hash));
+            tag.set_data(AUDIO_HASH_IDENT, Data::Utf8(audio_hash));
 
-    Ok(backup_path)
+            tag.dump_to_path(backup_path).with_context(|| {
+                format!(
+                    "Failed to create backup: {} -> {}",
+                    file_path.display(),
+                    backup_path.display()
+                )
+            })?;
+        }
+    }
+    Ok(())
 }
 
 /// Get the backup path for a file
 pub fn backup_path_for(file_path: &Path) -> PathBuf {
+    with_suffix(file_path, ".bak")
+}
+
+/// Get the metadata-only backup path for a file
+pub fn metadata_backup_path_for(file_path: &Path) -> PathBuf {
+    with_suffix(file_path, ".meta.bak")
+}
+
+fn with_suffix(file_path: &Path, suffix: &str) -> PathBuf {
     let mut backup = file_path.to_path_buf();
     let mut name = backup.file_name().unwrap_or_default().to_os_string();
-    name.push(".bak");
+    name.push(suffix);
     backup.set_file_name(name);
     backup
 }
 
-/// Check if a backup exists for a file
+/// Check if a backup (of either kind) exists for a file
 pub fn has_backup(file_path: &Path) -> bool {
-    backup_path_for(file_path).exists()
+    backup_path_for(file_path).exists() || metadata_backup_path_for(file_path).exists()
 }
 
-/// Delete the backup for a specific file
+/// Delete the backups (every generation) for a specific file
 pub fn delete_backup(file_path: &Path) -> Result<bool> {
-    let backup_path = backup_path_for(file_path);
+    let backups = BackupInfo::generations(file_path)?;
+    for backup in &backups {
+        remove_backup(backup)?;
+    }
+    Ok(!backups.is_empty())
+}
 
-    if backup_path.exists() {
-        fs::remove_file(&backup_path)
-            .with_context(|| format!("Failed to delete backup: {}", backup_path.display()))?;
-        Ok(true)
-    } else {
-        Ok(false)
+/// Delete one backup
+pub fn remove_backup(backup: &BackupInfo) -> Result<()> {
+    match &backup.stored {
+        Some(entry) => {
+            BackupStore::open(&Config::load()?.backups)?.remove(std::slice::from_ref(entry))
+        }
+        None => fs::remove_file(&backup.backup_path)
+            .with_context(|| format!("Failed to delete backup: {}", backup.backup_path.display())),
+    }
+}
+
+/// Backup files next to a file (at most one of each kind)
+fn alongside_backups(file_path: &Path) -> Vec<BackupInfo> {
+    [
+        (backup_path_for(file_path), BackupKind::Full),
+        (metadata_backup_path_for(file_path), BackupKind::Metadata),
+    ]
+    .into_iter()
+    .filter_map(|(backup_path, kind)| {
+        let meta = fs::metadata(&backup_path).ok()?;
+        Some(BackupInfo {
+            backup_path,
+            original_path: file_path.to_path_buf(),
+            size_bytes: meta.len(),
+            kind,
+            created_at: meta.modified().ok().map(DateTime::from),
+            stored: None,
+        })
+    })
+    .collect()
+}
+
+/// What a backup file contains
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupKind {
+    /// A copy of the whole file
+    Full,
+    /// Only the tag atoms and the file's hash
+    Metadata,
+}
+
+impl From<BackupMode> for BackupKind {
+    fn from(mode: BackupMode) -> Self {
+        match mode {
+            BackupMode::Full => Self::Full,
+            BackupMode::Metadata => Self::Metadata,
+        }
     }
 }
 
@@ -51,10 +174,184 @@ pub struct BackupInfo {
     pub backup_path: PathBuf,
     pub original_path: PathBuf,
     pub size_bytes: u64,
+    pub kind: BackupKind,
+    /// When the backup was taken (file modification time for `.bak` files)
+    pub created_at: Option<DateTime<Utc>>,
+    /// The store entry, for backups in the central store
+    pub stored: Option<StoredBackup>,
 }
 
-/// Find all backup files in a directory recursively
+impl BackupInfo {
+    /// The most recent backup of a file, if any
+    pub fn for_file(file_path: &Path) -> Result<Option<Self>> {
+        Ok(Self::generations(file_path)?.into_iter().next())
+    }
+
+    /// Every backup of a file, alongside it and in the store, newest first
+    pub fn generations(file_path: &Path) -> Result<Vec<Self>> {
+        let mut backups = alongside_backups(file_path);
+
+        let config = Config::load()?.backups;
+        let store = BackupStore::open(&config)?;
+        for entry in store.generations(file_path)? {
+            backups.push(Self::from_stored(&store, entry, file_path.to_path_buf()));
+        }
+
+        backups.sort_by_key(|b| std::cmp::Reverse(b.created_at));
+        Ok(backups)
+    }
+
+    fn from_stored(store: &BackupStore, entry: StoredBackup, original_path: PathBuf) -> Self {
+        Self {
+            backup_path: store.object_path(&entry),
+            original_path,
+            size_bytes: entry.size_bytes,
+            kind: entry.kind,
+            created_at: Some(entry.created_at),
+            stored: Some(entry),
+        }
+    }
+
+    /// Hash of the original file when the backup was taken
+    pub fn source_hash(&self) -> Result<String> {
+        match self.kind {
+            BackupKind::Full => sha256_file(&self.backup_path),
+            BackupKind::Metadata => self.stored_hash(&SOURCE_HASH_IDENT, "source"),
+        }
+    }
+
+    /// Hash of the original file's audio data (ignoring tags) when the backup
+    /// was taken; fails if the backup is unreadable or truncated
+    pub fn audio_hash(&self) -> Result<String> {
+        match self.kind {
+            BackupKind::Full => {
+                read_backup_tag(&self.backup_path)?;
+                audio_sha256(&self.backup_path)
+            }
+            BackupKind::Metadata => self.stored_hash(&AUDIO_HASH_IDENT, "audio"),
+        }
+    }
+
+    /// The tags stored in the backup
+    pub fn metadata(&self) -> Result<AudiobookMetadata> {
+        read_metadata(&self.backup_path)
+    }
+
+    fn stored_hash(&self, ident: &FreeformIdent, what: &str) -> Result<String> {
+        let tag = read_backup_tag(&self.backup_path)?;
+        let hash = tag
+            .strings_of(ident)
+            .next()
+            .with_context(|| format!("{} has no {} hash", self.backup_path.display(), what))?;
+        Ok(hash.to_string())
+    }
+}
+
+fn read_backup_tag(backup_path: &Path) -> Result<Tag> {
+    Tag::read_from_path(backup_path)
+        .with_context(|| format!("Failed to read backup: {}", backup_path.display()))
+}
+
+/// Put a backup's contents back into the original file.
+///
+/// A full backup replaces the file. A metadata backup replaces every tag atom
+/// in the file with the saved ones, and is refused if the file's audio is not
+/// the audio the backup was taken from, or if that can't be checked because
+/// the backup's audio hash is missing or unreadable (unless `force`). Either
+/// way the result is written to a temporary file next to the original and
+/// renamed over it, so an interrupted restore leaves the original untouched.
+pub fn restore_backup(backup: &BackupInfo, force: bool) -> Result<()> {
+    let file_path = &backup.original_path;
+    let _lock = lockfile::lock_file(file_path)?;
+    let before = journal::is_recording()
+        .then(|| read_metadata(file_path))
+        .transpose()?;
+
+    let source = match backup.kind {
+        BackupKind::Full => &backup.backup_path,
+        BackupKind::Metadata => {
+            match backup.audio_hash() {
+                Ok(expected) => {
+                    if audio_sha256(file_path)? != expected {
+                        bail!(
+                            "{} holds different audio than when {} was taken",
+                            file_path.display(),
+                            backup.backup_path.display()
+                        );
+                    }
+                }
+                Err(_) if force => {}
+                Err(e) => {
+                    return Err(e.context(
+                        "Can't check the backup matches the file's audio (use --force to restore anyway)",
+                    ))
+                }
+            }
+            file_path
+        }
+    };
+
+    let temp = with_suffix(file_path, ".restore.tmp");
+    let result = (|| {
+        fs::copy(source, &temp).with_context(|| {
+            format!("Failed to copy {} -> {}", source.display(), temp.display())
+        })?;
+
+        if backup.kind == BackupKind::Metadata {
+            let saved = read_backup_tag(&backup.backup_path)?;
+            let mut tag = Tag::read_from_path(&temp).with_context(|| {
+                format!("Failed to read m4b file for writing: {}", temp.display())
+            })?;
+            restore_tag(&mut tag, &saved);
+            tag.write_to_path(&temp)
+                .with_context(|| format!("Failed to write metadata to: {}", temp.display()))?;
+        }
+
+        fs::rename(&temp, file_path)
+            .with_context(|| format!("Failed to replace {}", file_path.display()))
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result?;
+
+    if let Some(before) = before {
+        journal::record_metadata(file_path, &before, &read_metadata(file_path)?);
+    }
+
+    Ok(())
+}
+
+/// Replace all atoms in `tag` with those of a metadata backup
+fn restore_tag(tag: &mut Tag, saved: &Tag) {
+    tag.clear();
+    for (ident, data) in saved.data() {
+        if SOURCE_HASH_IDENT != *ident && AUDIO_HASH_IDENT != *ident {
+            tag.add_data(ident.clone(), data.clone());
+        }
+    }
+}
+
+/// Find all backups of files under a directory: `.bak` files in it
+/// (recursively) and entries in the central store
 pub fn find_all_backups(dir: &Path) -> Result<Vec<BackupInfo>> {
+    let mut backups = find_backup_files(dir);
+
+    let config = Config::load()?.backups;
+    let store = BackupStore::open(&config)?;
+    let root = absolute_path(dir);
+    for entry in store.entries()? {
+        if entry.original.starts_with(&root) {
+            let original = entry.original.clone();
+            backups.push(BackupInfo::from_stored(&store, entry, original));
+        }
+    }
+
+    Ok(backups)
+}
+
+/// Backup files in a directory, recursively
+fn find_backup_files(dir: &Path) -> Vec<BackupInfo> {
     let mut backups = Vec::new();
 
     for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
@@ -63,20 +360,26 @@ pub fn find_all_backups(dir: &Path) -> Result<Vec<BackupInfo>> {
         if path.extension().is_some_and(|e| e == "bak") {
             // Check if it's an m4b backup
             let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
-            if stem.ends_with(".m4b") {
-                let original = path.with_file_name(stem);
-                let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+            let (original, kind) = match stem.strip_suffix(".meta") {
+                Some(original) => (original, BackupKind::Metadata),
+                None => (stem, BackupKind::Full),
+            };
+            if original.ends_with(".m4b") {
+                let meta = fs::metadata(path).ok();
 
                 backups.push(BackupInfo {
                     backup_path: path.to_path_buf(),
-                    original_path: original,
-                    size_bytes: size,
+                    original_path: path.with_file_name(original),
+                    size_bytes: meta.as_ref().map_or(0, |m| m.len()),
+                    kind,
+                    created_at: meta.and_then(|m| m.modified().ok()).map(DateTime::from),
+                    stored: None,
                 });
             }
         }
     }
 
-    Ok(backups)
+    backups
 }
 
 /// Format bytes as human-readable size
@@ -105,6 +408,92 @@ mod tests {
         let path = Path::new("/home/user/book.m4b");
         let backup = backup_path_for(path);
         assert_eq!(backup, PathBuf::from("/home/user/book.m4b.bak"));
+        assert_eq!(
+            metadata_backup_path_for(path),
+            PathBuf::from("/home/user/book.m4b.meta.bak")
+        );
+    }
+
+    #[test]
+    fn test_metadata_backup_round_trip() {
+        let temp = tempfile::tempdir().unwrap();
+        let backup_path = temp.path().join("book.m4b.meta.bak");
+        let narrator = FreeformIdent::new("com.apple.iTunes", "NARRATOR");
+
+        let mut original = Tag::default();
+        original.set_title("Leviathan Wakes");
+        original.set_data(narrator.clone(), Data::Utf8("Jefferson Mays".to_string()));
+        original.set_data(SOURCE_HASH_IDENT, Data::Utf8("abc123".to_string()));
+        original.set_data(AUDIO_HASH_IDENT, Data::Utf8("def456".to_string()));
+        original.dump_to_path(&backup_path).unwrap();
+
+        let backup = BackupInfo {
+            backup_path: backup_path.clone(),
+            original_path: temp.path().join("book.m4b"),
+            size_bytes: 0,
+            kind: BackupKind::Metadata,
+            created_at: None,
+            stored: None,
+        };
+        assert_eq!(backup.source_hash().unwrap(), "abc123");
+        assert_eq!(backup.audio_hash().unwrap(), "def456");
+        assert_eq!(
+            backup.metadata().unwrap().title.as_deref(),
+            Some("Leviathan Wakes")
+        );
+
+        // Tags written since the backup are replaced, not merged
+        let mut current = Tag::default();
+        current.set_title("Wrong Title");
+        current.set_genre("Science Fiction");
+        restore_tag(&mut current, &read_backup_tag(&backup_path).unwrap());
+
+        assert_eq!(current.title(), Some("Leviathan Wakes"));
+        assert_eq!(current.genre(), None);
+        assert_eq!(current.strings_of(&narrator).next(), Some("Jefferson Mays"));
+        assert_eq!(current.strings_of(&SOURCE_HASH_IDENT).next(), None);
+        assert_eq!(current.strings_of(&AUDIO_HASH_IDENT).next(), None);
+    }
+
+    #[test]
+    fn test_restore_refuses_backup_without_audio_hash() {
+        let temp = tempfile::tempdir().unwrap();
+        let file = temp.path().join("book.m4b");
+        let backup_path = temp.path().join("book.m4b.meta.bak");
+        fs::write(&file, "audio").unwrap();
+
+        let mut saved = Tag::default();
+        saved.set_title("Leviathan Wakes");
+        saved.dump_to_path(&backup_path).unwrap();
+
+        let backup = BackupInfo {
+            backup_path,
+            original_path: file.clone(),
+            size_bytes: 0,
+            kind: BackupKind::Metadata,
+            created_at: None,
+            stored: None,
+        };
+        let err = restore_backup(&backup, false).unwrap_err();
+        assert!(format!("{:#}", err).contains("use --force"));
+        assert_eq!(fs::read(&file).unwrap(), b"audio");
+    }
+
+    #[test]
+    fn test_find_all_backups_both_kinds() {
+        let temp = tempfile::tempdir().unwrap();
+        fs::write(temp.path().join("a.m4b.bak"), "full").unwrap();
+        fs::write(temp.path().join("b.m4b.meta.bak"), "meta").unwrap();
+        fs::write(temp.path().join("notes.txt.bak"), "other").unwrap();
+
+        let mut backups = find_backup_files(temp.path());
+        backups.sort_by(|a, b| a.backup_path.cmp(&b.backup_path));
+
+        assert_eq!(backups.len(), 2);
+        assert_eq!(backups[0].original_path, temp.path().join("a.m4b"));
+        assert_eq!(backups[0].kind, BackupKind::Full);
+        assert_eq!(backups[1].original_path, temp.path().join("b.m4b"));
+        assert_eq!(backups[1].kind, BackupKind::Metadata);
     }
 
     #[test]
@@ -2,9 +2,12 @@
 
 pub mod backup;
 pub mod pending;
+pub mod store;
 
 pub use backup::{
-    backup_path_for, create_backup, delete_backup, find_all_backups, format_size, has_backup,
-    BackupInfo,
+    backup_path_for, create_backup, create_backup_with, delete_backup, find_all_backups,
+    format_size, has_backup, metadata_backup_path_for, remove_backup, restore_backup, BackupInfo,
+    BackupKind,
 };
-pub use pending::{PendingEdit, PendingEditsCache};
+pub use pending::{absolute_path, PendingEdit, PendingEditsCache};
+pub use store::{BackupStore, StoredBackup};
@@ -1,17 +1,100 @@
+use crate::hash::sha256_file;
+use crate::metadata::{read_metadata, AudiobookMetadata};
 use anyhow::{Context, Result};
 use sha2::{Digest, Sha256};
 use std::fs;
 use std::path::{Path, PathBuf};
 
 /// Represents a pending edit waiting to be applied
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PendingEdit {
     pub original_path: PathBuf,
     pub toml_content: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    /// SHA256 of the file when the edit was saved (None for older edits)
+    pub content_hash: Option<String>,
+    /// Metadata the edit started from (None for older edits)
+    pub original: Option<AudiobookMetadata>,
+    /// Cache file holding this edit
+    pub cache_file: PathBuf,
+}
+
+impl PendingEdit {
+    /// Parse a cache file: header comments followed by the edit's TOML
+    fn parse(content: &str, fallback_path: PathBuf, cache_file: PathBuf) -> Self {
+        let mut original_path = fallback_path;
+        let mut created_at = chrono::Utc::now();
+        let mut content_hash = None;
+        let mut original = None;
+        let mut toml_start = content.len();
+
+        for (i, line) in content.lines().enumerate() {
+            if let Some(path_str) = line.strip_prefix("# Pending edit for: ") {
+                original_path = PathBuf::from(path_str);
+            } else if let Some(ts_str) = line.strip_prefix("# Created: ") {
+                if let Ok(ts) = chrono::DateTime::parse_from_rfc3339(ts_str) {
+                    created_at = ts.with_timezone(&chrono::Utc);
+                }
+            } else if let Some(hash) = line.strip_prefix("# Content hash: ") {
+                content_hash = Some(hash.trim().to_string());
+            } else if let Some(json) = line.strip_prefix("# Original: ") {
+                original = serde_json::from_str(json).ok();
+            } else if !line.starts_with('#') && !line.is_empty() {
+                toml_start = content.lines().take(i).map(|l| l.len() + 1).sum();
+                break;
+            }
+        }
+
+        Self {
+            original_path,
+            toml_content: content[toml_start..].to_string(),
+            created_at,
+            content_hash,
+            original,
+            cache_file,
+        }
+    }
+
+    /// Render the cache file: header comments followed by the edit's TOML
+    fn render(&self) -> Result<String> {
+        let mut header = format!(
+            "# Pending edit for: {}\n# Created: {}\n",
+            self.original_path.display(),
+            self.created_at.to_rfc3339()
+        );
+        if let Some(hash) = &self.content_hash {
+            header.push_str(&format!("# Content hash: {}\n", hash));
+        }
+        if let Some(original) = &self.original {
+            header.push_str(&format!(
+                "# Original: {}\n",
+                serde_json::to_string(original)?
+            ));
+        }
+        header.push_str(&format!(
+            "# Run: audiobookctl edit \"{}\" --no-dry-run\n\n",
+            self.original_path.display()
+        ));
+
+        Ok(format!("{}{}", header, self.toml_content))
+    }
+
+    /// Whether the file's content changed since the edit was saved
+    pub fn has_drifted(&self, file_path: &Path) -> Result<bool> {
+        match &self.content_hash {
+            Some(hash) => Ok(sha256_file(file_path)? != *hash),
+            None => Ok(false),
+        }
+    }
 }
 
 /// Manages the pending edits cache directory
+///
+/// Edits are stored by a hash of the path of the file they were made for and
+/// record the file's content hash in their header. `load` finds them by
+/// path; `adopt_orphan` hands an edit whose file is gone to a file with the
+/// same content, so an edit follows a file that was moved. Files with
+/// identical content (a book and its copy) still get separate edits.
 pub struct PendingEditsCache {
     cache_dir: PathBuf,
 }
@@ -24,6 +107,11 @@ impl PendingEditsCache {
             .join("audiobookctl")
             .join("pending");
 
+        Self::in_dir(cache_dir)
+    }
+
+    /// Create a cache in a specific directory
+    fn in_dir(cache_dir: PathBuf) -> Result<Self> {
         fs::create_dir_all(&cache_dir).with_context(|| {
             format!("Failed to create cache directory: {}", cache_dir.display())
         })?;
@@ -31,103 +119,147 @@ impl PendingEditsCache {
         Ok(Self { cache_dir })
     }
 
-    /// Get the cache file path for a given m4b file
-    pub fn cache_path_for(&self, file_path: &Path) -> Result<PathBuf> {
-        let abs_path = file_path
-            .canonicalize()
-            .with_context(|| format!("Failed to get absolute path for: {}", file_path.display()))?;
-
-        let hash = Self::hash_path(&abs_path);
-        Ok(self.cache_dir.join(format!("{}.toml", hash)))
-    }
-
-    /// Hash a path to a 16-char hex string
-    fn hash_path(path: &Path) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(path.to_string_lossy().as_bytes());
-        let result = hasher.finalize();
-        hex::encode(&result[..8]) // First 8 bytes = 16 hex chars
+    /// Cache file name for a file's canonical path (first 16 hex chars of its hash)
+    fn cache_file_for(&self, abs_path: &Path) -> PathBuf {
+        let hash = hex::encode(Sha256::digest(abs_path.as_os_str().as_encoded_bytes()));
+        self.cache_dir.join(format!("{}.toml", &hash[..16]))
     }
 
     /// Check if a pending edit exists for a file
     pub fn has_pending(&self, file_path: &Path) -> Result<bool> {
-        let cache_path = self.cache_path_for(file_path)?;
-        Ok(cache_path.exists())
+        Ok(self.load(file_path)?.is_some())
     }
 
-    /// Load a pending edit for a file
+    /// Load the pending edit saved for a file's path
     pub fn load(&self, file_path: &Path) -> Result<Option<PendingEdit>> {
-        let cache_path = self.cache_path_for(file_path)?;
+        let abs_path = canonical_path(file_path)?;
+        self.find_by_path(&abs_path)
+    }
 
-        if !cache_path.exists() {
-            return Ok(None);
+    /// Move an edit whose file is gone over to `file_path` if its content
+    /// hash matches (the file was moved outside audiobookctl)
+    ///
+    /// Does nothing if the file already has an edit. Returns whether an edit
+    /// was adopted.
+    pub fn adopt_orphan(&self, file_path: &Path) -> Result<bool> {
+        let abs_path = canonical_path(file_path)?;
+        let entries = self.entries()?;
+        if entries.iter().any(|e| e.original_path == abs_path) {
+            return Ok(false);
         }
 
-        let content = fs::read_to_string(&cache_path)
-            .with_context(|| format!("Failed to read pending edit: {}", cache_path.display()))?;
-
-        // Parse header comments for metadata
-        let mut original_path = file_path.to_path_buf();
-        let mut created_at = chrono::Utc::now();
-        let mut toml_start = 0;
+        // Only hash the file if there are edits that lost their file
+        let orphans: Vec<PendingEdit> = entries
+            .into_iter()
+            .filter(|e| e.content_hash.is_some() && !e.original_path.exists())
+            .collect();
+        if orphans.is_empty() {
+            return Ok(false);
+        }
 
-        for (i, line) in content.lines().enumerate() {
-            if line.starts_with("# Pending edit for: ") {
-                let path_str = line.trim_start_matches("# Pending edit for: ");
-                original_path = PathBuf::from(path_str);
-            } else if line.starts_with("# Created: ") {
-                let ts_str = line.trim_start_matches("# Created: ");
-                if let Ok(ts) = chrono::DateTime::parse_from_rfc3339(ts_str) {
-                    created_at = ts.with_timezone(&chrono::Utc);
-                }
-            } else if !line.starts_with('#') && !line.is_empty() {
-                toml_start = content.lines().take(i).map(|l| l.len() + 1).sum();
-                break;
+        let hash = sha256_file(&abs_path)?;
+        match orphans
+            .into_iter()
+            .find(|e| e.content_hash.as_deref() == Some(hash.as_str()))
+        {
+            Some(edit) => {
+                self.retarget(edit, &abs_path)?;
+                Ok(true)
             }
+            None => Ok(false),
         }
-
-        let toml_content = content[toml_start..].to_string();
-
-        Ok(Some(PendingEdit {
-            original_path,
-            toml_content,
-            created_at,
-        }))
     }
 
     /// Save a pending edit for a file
+    ///
+    /// The file's content hash and current metadata are recorded so that
+    /// applying the edit later can detect (and merge) newer changes.
     pub fn save(&self, file_path: &Path, toml_content: &str) -> Result<PathBuf> {
-        let cache_path = self.cache_path_for(file_path)?;
-        let abs_path = file_path.canonicalize()?;
-        let now = chrono::Utc::now();
-
-        let header = format!(
-            "# Pending edit for: {}\n# Created: {}\n# Run: audiobookctl edit \"{}\" --no-dry-run\n\n",
-            abs_path.display(),
-            now.to_rfc3339(),
-            abs_path.display()
-        );
-
-        let full_content = format!("{}{}", header, toml_content);
+        let abs_path = canonical_path(file_path)?;
+        let content_hash = sha256_file(&abs_path)?;
+        let original = read_metadata(&abs_path)?;
+        self.store(&abs_path, toml_content, content_hash, original)
+    }
 
-        fs::write(&cache_path, full_content)
-            .with_context(|| format!("Failed to write pending edit: {}", cache_path.display()))?;
+    fn store(
+        &self,
+        abs_path: &Path,
+        toml_content: &str,
+        content_hash: String,
+        original: AudiobookMetadata,
+    ) -> Result<PathBuf> {
+        let edit = PendingEdit {
+            original_path: abs_path.to_path_buf(),
+            toml_content: toml_content.to_string(),
+            created_at: chrono::Utc::now(),
+            original: Some(original),
+            cache_file: self.cache_file_for(abs_path),
+            content_hash: Some(content_hash),
+        };
+
+        // Replace an earlier edit for this path (saved under another key)
+        if let Some(previous) = self.find_by_path(abs_path)? {
+            if previous.cache_file != edit.cache_file {
+                self.remove(&previous)?;
+            }
+        }
 
-        Ok(cache_path)
+        self.write(&edit)?;
+        Ok(edit.cache_file)
     }
 
     /// Clear pending edit for a specific file
     pub fn clear(&self, file_path: &Path) -> Result<bool> {
-        let cache_path = self.cache_path_for(file_path)?;
+        let abs_path = canonical_path(file_path)?;
 
-        if cache_path.exists() {
-            fs::remove_file(&cache_path).with_context(|| {
-                format!("Failed to remove pending edit: {}", cache_path.display())
+        match self.find_by_path(&abs_path)? {
+            Some(edit) => {
+                self.remove(&edit)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Point edits for a file that was moved or copied at its new path.
+    ///
+    /// Call after the file exists at `to`; `from` may no longer exist.
+    pub fn relocate(&self, from: &Path, to: &Path) -> Result<usize> {
+        let from = absolute_path(from);
+        let to = canonical_path(to)?;
+        let mut count = 0;
+
+        for edit in self.entries()? {
+            if edit.original_path == from {
+                self.retarget(edit, &to)?;
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Point an edit at a new path
+    pub fn retarget(&self, mut edit: PendingEdit, new_path: &Path) -> Result<PendingEdit> {
+        let old_file = std::mem::replace(&mut edit.cache_file, self.cache_file_for(new_path));
+        edit.original_path = new_path.to_path_buf();
+        self.write(&edit)?;
+        if old_file != edit.cache_file {
+            fs::remove_file(&old_file).with_context(|| {
+                format!("Failed to remove pending edit: {}", old_file.display())
             })?;
-            Ok(true)
-        } else {
-            Ok(false)
         }
+        Ok(edit)
+    }
+
+    /// Delete an edit's cache file
+    pub fn remove(&self, edit: &PendingEdit) -> Result<()> {
+        fs::remove_file(&edit.cache_file).with_context(|| {
+            format!(
+                "Failed to remove pending edit: {}",
+                edit.cache_file.display()
+            )
+        })
     }
 
     /// Clear all pending edits
@@ -150,6 +282,20 @@ impl PendingEditsCache {
 
     /// List all pending edits
     pub fn list_all(&self) -> Result<Vec<PendingEdit>> {
+        let mut edits: Vec<PendingEdit> = self
+            .entries()?
+            .into_iter()
+            .filter(|e| !e.original_path.as_os_str().is_empty())
+            .collect();
+
+        // Sort by created_at (oldest first)
+        edits.sort_by_key(|a| a.created_at);
+
+        Ok(edits)
+    }
+
+    /// Every cache file, including ones without a recorded path
+    pub fn entries(&self) -> Result<Vec<PendingEdit>> {
         let mut edits = Vec::new();
 
         if !self.cache_dir.exists() {
@@ -162,60 +308,191 @@ impl PendingEditsCache {
             if path.extension().is_some_and(|e| e == "toml") {
                 // Read the file to get the original path from header
                 let content = fs::read_to_string(&path)?;
-
-                let mut original_path = PathBuf::new();
-                let mut created_at = chrono::Utc::now();
-                let mut toml_start = 0;
-
-                for (i, line) in content.lines().enumerate() {
-                    if line.starts_with("# Pending edit for: ") {
-                        let path_str = line.trim_start_matches("# Pending edit for: ");
-                        original_path = PathBuf::from(path_str);
-                    } else if line.starts_with("# Created: ") {
-                        let ts_str = line.trim_start_matches("# Created: ");
-                        if let Ok(ts) = chrono::DateTime::parse_from_rfc3339(ts_str) {
-                            created_at = ts.with_timezone(&chrono::Utc);
-                        }
-                    } else if !line.starts_with('#') && !line.is_empty() {
-                        toml_start = content.lines().take(i).map(|l| l.len() + 1).sum();
-                        break;
-                    }
-                }
-
-                if !original_path.as_os_str().is_empty() {
-                    let toml_content = content[toml_start..].to_string();
-                    edits.push(PendingEdit {
-                        original_path,
-                        toml_content,
-                        created_at,
-                    });
-                }
+                edits.push(PendingEdit::parse(&content, PathBuf::new(), path));
             }
         }
 
-        // Sort by created_at (oldest first)
-        edits.sort_by(|a, b| a.created_at.cmp(&b.created_at));
-
         Ok(edits)
     }
+
+    fn find_by_path(&self, abs_path: &Path) -> Result<Option<PendingEdit>> {
+        Ok(self
+            .entries()?
+            .into_iter()
+            .find(|e| e.original_path == abs_path))
+    }
+
+    fn write(&self, edit: &PendingEdit) -> Result<()> {
+        fs::write(&edit.cache_file, edit.render()?).with_context(|| {
+            format!(
+                "Failed to write pending edit: {}",
+                edit.cache_file.display()
+            )
+        })
+    }
+}
+
+fn canonical_path(file_path: &Path) -> Result<PathBuf> {
+    file_path
+        .canonicalize()
+        .with_context(|| format!("Failed to get absolute path for: {}", file_path.display()))
+}
+
+/// Canonical path of a file that may no longer exist (via its parent directory)
+pub fn absolute_path(file_path: &Path) -> PathBuf {
+    if let Ok(path) = file_path.canonicalize() {
+        return path;
+    }
+    match (file_path.parent(), file_path.file_name()) {
+        (Some(parent), Some(name)) => {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            parent
+                .canonicalize()
+                .map(|p| p.join(name))
+                .unwrap_or_else(|_| file_path.to_path_buf())
+        }
+        _ => file_path.to_path_buf(),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tempfile::TempDir;
+
+    /// Write an edit for `file` as if it had been saved when its content was `hash`
+    fn add_edit(cache: &PendingEditsCache, file: &Path, hash: &str) -> PendingEdit {
+        let edit = PendingEdit {
+            original_path: file.to_path_buf(),
+            toml_content: "title = \"Edited\"\n".to_string(),
+            created_at: chrono::Utc::now(),
+            content_hash: Some(hash.to_string()),
+            original: Some(AudiobookMetadata::default()),
+            cache_file: cache.cache_file_for(file),
+        };
+        cache.write(&edit).unwrap();
+        edit
+    }
 
     #[test]
-    fn test_hash_path_consistent() {
-        let hash1 = PendingEditsCache::hash_path(Path::new("/home/user/book.m4b"));
-        let hash2 = PendingEditsCache::hash_path(Path::new("/home/user/book.m4b"));
-        assert_eq!(hash1, hash2);
-        assert_eq!(hash1.len(), 16);
+    fn test_parse_header() {
+        let original = AudiobookMetadata {
+            title: Some("Leviathan Wakes".to_string()),
+            ..Default::default()
+        };
+        let content = format!(
+            "# Pending edit for: /lib/book.m4b\n# Created: 2024-01-02T03:04:05+00:00\n# Content hash: abc123\n# Original: {}\n# Run: audiobookctl edit \"/lib/book.m4b\" --no-dry-run\n\n# Audiobook Metadata\ntitle = \"Leviathan Wakes\"\n",
+            serde_json::to_string(&original).unwrap()
+        );
+
+        let edit = PendingEdit::parse(&content, PathBuf::new(), PathBuf::new());
+        assert_eq!(edit.original_path, PathBuf::from("/lib/book.m4b"));
+        assert_eq!(edit.content_hash.as_deref(), Some("abc123"));
+        assert_eq!(edit.original, Some(original));
+        assert_eq!(edit.toml_content, "title = \"Leviathan Wakes\"\n");
+
+        // Rendering round-trips the header
+        let reparsed = PendingEdit::parse(&edit.render().unwrap(), PathBuf::new(), PathBuf::new());
+        assert_eq!(reparsed.original_path, edit.original_path);
+        assert_eq!(reparsed.content_hash, edit.content_hash);
+        assert_eq!(reparsed.original, edit.original);
+        assert_eq!(reparsed.toml_content, edit.toml_content);
+    }
+
+    #[test]
+    fn test_parse_header_without_hash() {
+        let content = "# Pending edit for: /lib/book.m4b\n# Created: 2024-01-02T03:04:05+00:00\n\ntitle = \"X\"\n";
+        let edit = PendingEdit::parse(content, PathBuf::new(), PathBuf::new());
+        assert_eq!(edit.content_hash, None);
+        assert_eq!(edit.original, None);
+        assert!(!edit
+            .has_drifted(Path::new("/nonexistent/book.m4b"))
+            .unwrap());
+    }
+
+    #[test]
+    fn test_relocate_follows_move() {
+        let temp = TempDir::new().unwrap();
+        let cache = PendingEditsCache::in_dir(temp.path().join("pending")).unwrap();
+        let library = temp.path().canonicalize().unwrap();
+        let old_path = library.join("old.m4b");
+        let new_path = library.join("new.m4b");
+        fs::write(&old_path, b"audio").unwrap();
+        add_edit(&cache, &old_path, &sha256_file(&old_path).unwrap());
+
+        fs::rename(&old_path, &new_path).unwrap();
+        assert_eq!(cache.relocate(&old_path, &new_path).unwrap(), 1);
+
+        let edit = cache.load(&new_path).unwrap().unwrap();
+        assert_eq!(edit.original_path, new_path);
+        assert!(cache.clear(&new_path).unwrap());
+        assert!(cache.list_all().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_adopt_orphan_finds_moved_file_by_content_hash() {
+        let temp = TempDir::new().unwrap();
+        let cache = PendingEditsCache::in_dir(temp.path().join("pending")).unwrap();
+        let library = temp.path().canonicalize().unwrap();
+        let moved = library.join("moved.m4b");
+        fs::write(&moved, b"audio").unwrap();
+        add_edit(
+            &cache,
+            &library.join("gone.m4b"),
+            &sha256_file(&moved).unwrap(),
+        );
+
+        let other = library.join("other.m4b");
+        fs::write(&other, b"different audio").unwrap();
+        assert!(!cache.adopt_orphan(&other).unwrap());
+
+        // Loading is a plain lookup by path
+        assert!(cache.load(&moved).unwrap().is_none());
+        assert_eq!(
+            cache.list_all().unwrap()[0].original_path,
+            library.join("gone.m4b")
+        );
+
+        assert!(cache.adopt_orphan(&moved).unwrap());
+        let edit = cache.load(&moved).unwrap().unwrap();
+        assert_eq!(edit.original_path, moved);
+        // The edit now records the new path
+        assert_eq!(cache.list_all().unwrap()[0].original_path, moved);
     }
 
     #[test]
-    fn test_hash_path_different() {
-        let hash1 = PendingEditsCache::hash_path(Path::new("/home/user/book1.m4b"));
-        let hash2 = PendingEditsCache::hash_path(Path::new("/home/user/book2.m4b"));
-        assert_ne!(hash1, hash2);
+    fn test_identical_files_keep_separate_edits() {
+        let temp = TempDir::new().unwrap();
+        let cache = PendingEditsCache::in_dir(temp.path().join("pending")).unwrap();
+        let library = temp.path().canonicalize().unwrap();
+        let book = library.join("book.m4b");
+        let copy = library.join("copy.m4b");
+        fs::write(&book, b"audio").unwrap();
+        fs::write(&copy, b"audio").unwrap();
+        let hash = sha256_file(&book).unwrap();
+
+        let first = cache
+            .store(
+                &book,
+                "title = \"Book\"\n",
+                hash.clone(),
+                Default::default(),
+            )
+            .unwrap();
+        let second = cache
+            .store(&copy, "title = \"Copy\"\n", hash, Default::default())
+            .unwrap();
+        assert_ne!(first, second);
+
+        let edit = cache.load(&book).unwrap().unwrap();
+        assert_eq!(edit.original_path, book);
+        assert_eq!(edit.toml_content, "title = \"Book\"\n");
+        let edit = cache.load(&copy).unwrap().unwrap();
+        assert_eq!(edit.original_path, copy);
+        assert_eq!(edit.toml_content, "title = \"Copy\"\n");
     }
 }
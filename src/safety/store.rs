@@ -0,0 +1,453 @@
+//! Central backup store
+//!
+//! Keeps backups out of the library, under `~/.local/share/audiobookctl/backups`
+//! (or `[backups] store_dir`). Each backup is stored once under the SHA-256 of
+//! its content (`objects/ab/abcd...bak`), and `index.json` records which file
+//! it was taken from and when, so a file can have several generations.
+//!
+//! The store is shared by every library, so changes to the index hold
+//! `index.lock` and temp files are named per process.
+
+use crate::config::BackupsConfig;
+use crate::hash::sha256_file;
+use crate::lockfile::{self, Lock};
+use crate::safety::absolute_path;
+use crate::safety::backup::{write_backup, BackupKind};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// One generation of a file's backup
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredBackup {
+    /// The file the backup was taken from
+    pub original: PathBuf,
+    /// SHA-256 of the backup's content (its name in the store)
+    pub object: String,
+    pub kind: BackupKind,
+    pub created_at: DateTime<Utc>,
+    pub size_bytes: u64,
+}
+
+/// The central backup store
+pub struct BackupStore {
+    dir: PathBuf,
+}
+
+impl BackupStore {
+    /// The store configured in `[backups]`
+    pub fn open(config: &BackupsConfig) -> Result<Self> {
+        let dir = match &config.store_dir {
+            Some(dir) => dir.clone(),
+            None => dirs::data_dir()
+                .context("Could not determine data directory")?
+                .join("audiobookctl")
+                .join("backups"),
+        };
+        Ok(Self::at(dir))
+    }
+
+    /// A store at a specific directory
+    pub fn at(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.dir.join("index.json")
+    }
+
+    /// Lock the index against other runs for a read-modify-write
+    fn lock(&self) -> Result<Lock> {
+        lockfile::lock_shared(&self.dir.join("index.lock"))
+    }
+
+    /// A temp file name no other run (or thread) uses
+    fn temp_path(&self, name: &str) -> PathBuf {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        self.dir.join(format!(
+            "{}.{}.{}.tmp",
+            name,
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ))
+    }
+
+    /// All stored backups, oldest first
+    pub fn entries(&self) -> Result<Vec<StoredBackup>> {
+        let path = self.index_path();
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let content =
+            fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?;
+        let mut entries: Vec<StoredBackup> = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse {:?}", path))?;
+        entries.sort_by_key(|e| e.created_at);
+        Ok(entries)
+    }
+
+    /// Generations of one file's backup, newest first
+    pub fn generations(&self, file_path: &Path) -> Result<Vec<StoredBackup>> {
+        let original = absolute_path(file_path);
+        let mut entries: Vec<StoredBackup> = self
+            .entries()?
+            .into_iter()
+            .filter(|e| e.original == original)
+            .collect();
+        entries.reverse();
+        Ok(entries)
+    }
+
+    /// Where a backup's content lives
+    pub fn object_path(&self, entry: &StoredBackup) -> PathBuf {
+        let extension = match entry.kind {
+            BackupKind::Full => "bak",
+            BackupKind::Metadata => "meta.bak",
+        };
+        self.dir
+            .join("objects")
+            .join(&entry.object[..2])
+            .join(format!("{}.{}", entry.object, extension))
+    }
+
+    /// Back up a file into the store as a new generation
+    pub fn add(&self, file_path: &Path, kind: BackupKind) -> Result<StoredBackup> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create directory {:?}", self.dir))?;
+
+        let temp = self.temp_path("incoming");
+        let result =
+            write_backup(file_path, kind, &temp).and_then(|()| self.store(file_path, kind, &temp));
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result
+    }
+
+    /// Move a written backup into the store and index it
+    fn store(&self, file_path: &Path, kind: BackupKind, temp: &Path) -> Result<StoredBackup> {
+        let object = sha256_file(temp)?;
+        let size_bytes = fs::metadata(temp)?.len();
+
+        let entry = StoredBackup {
+            original: absolute_path(file_path),
+            object,
+            kind,
+            created_at: Utc::now(),
+            size_bytes,
+        };
+
+        // Held until the entry is indexed, so a concurrent prune can't delete
+        // the object in between
+        let _lock = self.lock()?;
+
+        // Identical content is stored once
+        let object_path = self.object_path(&entry);
+        if object_path.exists() {
+            fs::remove_file(temp)?;
+        } else {
+            if let Some(parent) = object_path.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create directory {:?}", parent))?;
+            }
+            fs::rename(temp, &object_path)
+                .with_context(|| format!("Failed to store backup {:?}", object_path))?;
+        }
+
+        let mut entries = self.entries()?;
+        entries.push(entry.clone());
+        self.save(&entries)?;
+
+        Ok(entry)
+    }
+
+    /// Drop entries from the index, deleting objects nothing refers to any more
+    pub fn remove(&self, removed: &[StoredBackup]) -> Result<()> {
+        let _lock = self.lock()?;
+        self.remove_locked(removed)
+    }
+
+    fn remove_locked(&self, removed: &[StoredBackup]) -> Result<()> {
+        let (gone, kept): (Vec<StoredBackup>, Vec<StoredBackup>) = self
+            .entries()?
+            .into_iter()
+            .partition(|e| removed.contains(e));
+        self.save(&kept)?;
+
+        for entry in gone {
+            if !kept.iter().any(|e| e.object == entry.object) {
+                let path = self.object_path(&entry);
+                if path.exists() {
+                    fs::remove_file(&path)
+                        .with_context(|| format!("Failed to delete backup {:?}", path))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Point a moved file's backups at its new path, returning how many moved
+    pub fn relocate(&self, from: &Path, to: &Path) -> Result<usize> {
+        let from = absolute_path(from);
+        let _lock = self.lock()?;
+        let mut entries = self.entries()?;
+        let mut moved = 0;
+        for entry in entries.iter_mut().filter(|e| e.original == from) {
+            entry.original = absolute_path(to);
+            moved += 1;
+        }
+        if moved > 0 {
+            self.save(&entries)?;
+        }
+        Ok(moved)
+    }
+
+    /// Apply the retention policy, returning the entries removed
+    pub fn prune(&self, config: &BackupsConfig) -> Result<Vec<StoredBackup>> {
+        let _lock = self.lock()?;
+        let entries = self.entries()?;
+        let pruned = select_for_pruning(
+            &entries,
+            config.keep_generations,
+            config.max_age_days.map(|days| Duration::days(days as i64)),
+            config.max_storage_bytes,
+            Utc::now(),
+        );
+        let removed: Vec<StoredBackup> = pruned.into_iter().map(|i| entries[i].clone()).collect();
+        if !removed.is_empty() {
+            self.remove_locked(&removed)?;
+        }
+        Ok(removed)
+    }
+
+    /// Write the index (callers hold the lock)
+    fn save(&self, entries: &[StoredBackup]) -> Result<()> {
+        let path = self.index_path();
+        let temp = self.temp_path("index.json");
+        fs::write(&temp, serde_json::to_string_pretty(entries)?)
+            .with_context(|| format!("Failed to write {:?}", temp))?;
+        fs::rename(&temp, &path).with_context(|| format!("Failed to write {:?}", path))?;
+        Ok(())
+    }
+}
+
+/// Indices of entries (sorted oldest first) the retention policy removes.
+///
+/// Generations beyond `keep_generations` per file (0 keeps all) and entries
+/// older than `max_age` (except each file's newest) go first; then the oldest remaining entries until
+/// the store fits in `max_bytes`. Objects shared by several entries count once.
+/// The newest entry (the backup just taken) is never removed for size.
+pub fn select_for_pruning(
+    entries: &[StoredBackup],
+    keep_generations: usize,
+    max_age: Option<Duration>,
+    max_bytes: u64,
+    now: DateTime<Utc>,
+) -> Vec<usize> {
+    let mut pruned = vec![false; entries.len()];
+
+    let mut by_file: BTreeMap<&Path, Vec<usize>> = BTreeMap::new();
+    for (i, entry) in entries.iter().enumerate() {
+        by_file.entry(&entry.original).or_default().push(i);
+    }
+
+    if keep_generations > 0 {
+        for indices in by_file.values() {
+            let excess = indices.len().saturating_sub(keep_generations);
+            for &i in &indices[..excess] {
+                pruned[i] = true;
+            }
+        }
+    }
+
+    if let Some(max_age) = max_age {
+        // A file's newest backup is kept however old it is
+        let newest: HashSet<usize> = by_file.values().filter_map(|v| v.last().copied()).collect();
+        for (i, entry) in entries.iter().enumerate() {
+            if now - entry.created_at > max_age && !newest.contains(&i) {
+                pruned[i] = true;
+            }
+        }
+    }
+
+    let mut refs: HashMap<&str, usize> = HashMap::new();
+    let mut total = 0;
+    for (i, entry) in entries.iter().enumerate() {
+        if !pruned[i] {
+            let count = refs.entry(&entry.object).or_default();
+            if *count == 0 {
+                total += entry.size_bytes;
+            }
+            *count += 1;
+        }
+    }
+    for (i, entry) in entries
+        .iter()
+        .enumerate()
+        .take(entries.len().saturating_sub(1))
+    {
+        if total <= max_bytes {
+            break;
+        }
+        if pruned[i] {
+            continue;
+        }
+        pruned[i] = true;
+        let count = refs.get_mut(entry.object.as_str()).unwrap();
+        *count -= 1;
+        if *count == 0 {
+            total -= entry.size_bytes;
+        }
+    }
+
+    (0..entries.len()).filter(|&i| pruned[i]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn stored(original: &str, object: &str, days_ago: i64, size_bytes: u64) -> StoredBackup {
+        StoredBackup {
+            original: PathBuf::from(original),
+            object: object.to_string(),
+            kind: BackupKind::Metadata,
+            created_at: Utc::now() - Duration::days(days_ago),
+            size_bytes,
+        }
+    }
+
+    #[test]
+    fn test_prune_by_generation_count() {
+        let entries = vec![
+            stored("/lib/a.m4b", "a1", 4, 10),
+            stored("/lib/b.m4b", "b1", 3, 10),
+            stored("/lib/a.m4b", "a2", 2, 10),
+            stored("/lib/a.m4b", "a3", 1, 10),
+        ];
+        assert_eq!(
+            select_for_pruning(&entries, 2, None, u64::MAX, Utc::now()),
+            vec![0]
+        );
+        assert!(select_for_pruning(&entries, 0, None, u64::MAX, Utc::now()).is_empty());
+    }
+
+    #[test]
+    fn test_prune_by_age() {
+        let entries = vec![
+            stored("/lib/a.m4b", "a1", 100, 10),
+            stored("/lib/b.m4b", "b1", 40, 10),
+            stored("/lib/b.m4b", "b2", 35, 10),
+            stored("/lib/a.m4b", "a2", 1, 10),
+        ];
+        // b2 is past the limit too, but it's b's newest backup
+        assert_eq!(
+            select_for_pruning(&entries, 0, Some(Duration::days(30)), u64::MAX, Utc::now()),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    fn test_prune_by_age_keeps_only_backup() {
+        let entries = vec![stored("/lib/a.m4b", "a1", 100, 10)];
+        assert!(
+            select_for_pruning(&entries, 0, Some(Duration::days(0)), u64::MAX, Utc::now())
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_prune_by_size_oldest_first_counting_shared_objects_once() {
+        let entries = vec![
+            stored("/lib/a.m4b", "shared", 3, 50),
+            stored("/lib/b.m4b", "b1", 2, 30),
+            stored("/lib/c.m4b", "shared", 1, 50),
+        ];
+        // Total is 80 (the shared object counts once). Dropping the oldest
+        // entry frees nothing while the newest still refers to its object.
+        assert_eq!(
+            select_for_pruning(&entries, 0, None, 80, Utc::now()),
+            Vec::<usize>::new()
+        );
+        assert_eq!(
+            select_for_pruning(&entries, 0, None, 60, Utc::now()),
+            vec![0, 1]
+        );
+        // Even over the limit, the newest backup stays
+        assert_eq!(
+            select_for_pruning(&entries, 0, None, 0, Utc::now()),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    fn test_add_relocate_and_remove() {
+        let temp = TempDir::new().unwrap();
+        let store = BackupStore::at(temp.path().join("store"));
+        let file = temp.path().join("book.m4b");
+        fs::write(&file, "audio").unwrap();
+
+        let first = store.add(&file, BackupKind::Full).unwrap();
+        let second = store.add(&file, BackupKind::Full).unwrap();
+        assert_eq!(first.object, second.object);
+        assert_eq!(fs::read(store.object_path(&first)).unwrap(), b"audio");
+        assert_eq!(
+            store.generations(&file).unwrap(),
+            vec![second.clone(), first.clone()]
+        );
+
+        let moved = temp.path().join("moved.m4b");
+        fs::rename(&file, &moved).unwrap();
+        assert_eq!(store.relocate(&file, &moved).unwrap(), 2);
+        assert_eq!(store.generations(&moved).unwrap().len(), 2);
+
+        // The object stays while another generation refers to it
+        let entries = store.entries().unwrap();
+        store.remove(&entries[..1]).unwrap();
+        assert!(store.object_path(&first).exists());
+        store.remove(&entries[1..]).unwrap();
+        assert!(!store.object_path(&first).exists());
+        assert!(store.entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_concurrent_adds_keep_every_entry() {
+        let temp = TempDir::new().unwrap();
+        let store_dir = temp.path().join("store");
+
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let file = temp.path().join(format!("book{}.m4b", i));
+                fs::write(&file, format!("audio {}", i)).unwrap();
+                let store = BackupStore::at(store_dir.clone());
+                std::thread::spawn(move || {
+                    for _ in 0..5 {
+                        store.add(&file, BackupKind::Full).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let store = BackupStore::at(store_dir.clone());
+        assert_eq!(store.entries().unwrap().len(), 20);
+        let leftovers = fs::read_dir(&store_dir)
+            .unwrap()
+            .filter(|e| {
+                e.as_ref()
+                    .unwrap()
+                    .path()
+                    .extension()
+                    .is_some_and(|x| x == "tmp")
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+}
@@ -1,9 +1,9 @@
-use assert_cmd::Command;
+use assert_cmd::cargo::cargo_bin_cmd;
 use predicates::prelude::*;
 
 #[test]
 fn test_show_missing_file_returns_error() {
-    let mut cmd = Command::cargo_bin("audiobookctl").unwrap();
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
     cmd.args(["show", "/nonexistent/file.m4b"]);
     cmd.assert()
         .failure()
@@ -12,7 +12,7 @@ fn test_show_missing_file_returns_error() {
 
 #[test]
 fn test_show_help() {
-    let mut cmd = Command::cargo_bin("audiobookctl").unwrap();
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
     cmd.args(["show", "--help"]);
     cmd.assert()
         .success()
@@ -21,7 +21,7 @@ fn test_show_help() {
 
 #[test]
 fn test_unknown_field_returns_error() {
-    let mut cmd = Command::cargo_bin("audiobookctl").unwrap();
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
     cmd.args(["show", "--field", "invalid_field", "/nonexistent/file.m4b"]);
     // File error comes first, but if we had a file, field error would show
     cmd.assert().failure();
@@ -29,7 +29,7 @@ fn test_unknown_field_returns_error() {
 
 #[test]
 fn test_version() {
-    let mut cmd = Command::cargo_bin("audiobookctl").unwrap();
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
     cmd.arg("--version");
     cmd.assert()
         .success()
@@ -38,7 +38,7 @@ fn test_version() {
 
 #[test]
 fn test_edit_help() {
-    let mut cmd = Command::cargo_bin("audiobookctl").unwrap();
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
     cmd.args(["edit", "--help"]);
     cmd.assert()
         .success()
@@ -49,23 +49,57 @@ fn test_edit_help() {
 
 #[test]
 fn test_edit_missing_file() {
-    let mut cmd = Command::cargo_bin("audiobookctl").unwrap();
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
     cmd.args(["edit", "/nonexistent/file.m4b"]);
     cmd.assert().failure();
 }
 
+#[test]
+fn test_edit_commit_requires_single_file() {
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
+    cmd.args(["edit", "--commit", "a.m4b", "b.m4b"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--commit takes a single file"));
+}
+
 #[test]
 fn test_edit_commit_all_no_backups() {
-    let mut cmd = Command::cargo_bin("audiobookctl").unwrap();
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
     cmd.args(["edit", "--commit-all"]);
     cmd.assert()
         .success()
         .stdout(predicate::str::contains("No backup files found"));
 }
 
+#[test]
+fn test_set_help() {
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
+    cmd.args(["set", "--help"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("--field"))
+        .stdout(predicate::str::contains("--from-json"))
+        .stdout(predicate::str::contains("--from-toml"));
+}
+
+#[test]
+fn test_set_rejects_unknown_field() {
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
+    cmd.args([
+        "set",
+        "/nonexistent/file.m4b",
+        "--field",
+        "narator=Ray Porter",
+    ]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown field \"narator\""));
+}
+
 #[test]
 fn test_lookup_help() {
-    let mut cmd = Command::cargo_bin("audiobookctl").unwrap();
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
     cmd.args(["lookup", "--help"]);
     cmd.assert()
         .success()
@@ -73,12 +107,292 @@ fn test_lookup_help() {
         .stdout(predicate::str::contains("Audnexus"))
         .stdout(predicate::str::contains("Open Library"))
         .stdout(predicate::str::contains("--no-dry-run"))
-        .stdout(predicate::str::contains("--no-backup-i-void-my-warranty"));
+        .stdout(predicate::str::contains("--no-backup-i-void-my-warranty"))
+        .stdout(predicate::str::contains("--fields"))
+        .stdout(predicate::str::contains("--only-missing"));
 }
 
 #[test]
 fn test_lookup_missing_file() {
-    let mut cmd = Command::cargo_bin("audiobookctl").unwrap();
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
     cmd.args(["lookup", "/nonexistent/file.m4b"]);
     cmd.assert().failure();
 }
+
+#[test]
+fn test_names_help() {
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
+    cmd.args(["names", "--help"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("scan"))
+        .stdout(predicate::str::contains("merge"))
+        .stdout(predicate::str::contains("remove"));
+}
+
+#[test]
+fn test_genres_without_database() {
+    let temp = tempfile::tempdir().unwrap();
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
+    cmd.args(["genres"]).arg(temp.path());
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("No database found"));
+}
+
+#[test]
+fn test_export_csv_empty_directory() {
+    let temp = tempfile::tempdir().unwrap();
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
+    cmd.args(["export-csv", temp.path().to_str().unwrap()]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("path,sha256,title,author,"));
+}
+
+#[test]
+fn test_pending_gc_help() {
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
+    cmd.args(["pending", "gc", "--help"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("--no-dry-run"));
+}
+
+#[test]
+fn test_history_and_undo_with_empty_journal() {
+    let temp = tempfile::tempdir().unwrap();
+
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
+    cmd.env("XDG_DATA_HOME", temp.path()).arg("history");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("No history."));
+
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
+    cmd.env("XDG_DATA_HOME", temp.path()).args(["undo", "1"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("No journal entry #1"));
+}
+
+#[test]
+fn test_organize_resume_and_rollback_without_interrupted_run() {
+    let library = tempfile::tempdir().unwrap();
+    let cache = tempfile::tempdir().unwrap();
+
+    for flag in ["--resume", "--rollback"] {
+        let mut cmd = cargo_bin_cmd!("audiobookctl");
+        cmd.env("XDG_CACHE_HOME", cache.path())
+            .args(["organize", "--dest"])
+            .arg(library.path())
+            .arg(flag);
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains("No interrupted organize run"));
+    }
+
+    // --source is only optional when resuming or rolling back
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
+    cmd.args(["organize", "--dest"]).arg(library.path());
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--source"));
+}
+
+#[test]
+fn test_index_refuses_locked_library() {
+    use sha2::{Digest, Sha256};
+    use std::io::Write;
+
+    let library = tempfile::tempdir().unwrap();
+    let cache = tempfile::tempdir().unwrap();
+    let root = library.path().canonicalize().unwrap();
+
+    // Hold the library lock the way another run would
+    let hash = hex::encode(Sha256::digest(root.as_os_str().as_encoded_bytes()));
+    let locks = cache.path().join("audiobookctl").join("locks");
+    std::fs::create_dir_all(&locks).unwrap();
+    let mut held =
+        std::fs::File::create(locks.join(format!("library-{}.lock", &hash[..16]))).unwrap();
+    held.lock().unwrap();
+    writeln!(held, "4242\nfix").unwrap();
+
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
+    cmd.env("XDG_CACHE_HOME", cache.path())
+        .args(["index", root.to_str().unwrap()]);
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "is locked by PID 4242 running `audiobookctl fix`",
+    ));
+}
+
+/// A minimal m4b: tags followed by a fake audio atom
+fn make_m4b(path: &std::path::Path, title: &str) {
+    use std::io::Write;
+
+    let mut tag = mp4ameta::Tag::default();
+    tag.set_title(title);
+    tag.set_artist("Author");
+    tag.dump_to_path(path).unwrap();
+    let mut file = std::fs::OpenOptions::new().append(true).open(path).unwrap();
+    file.write_all(&((8 + title.len()) as u32).to_be_bytes())
+        .unwrap();
+    file.write_all(b"mdat").unwrap();
+    file.write_all(title.as_bytes()).unwrap();
+}
+
+/// Save an organize run into `library` that was interrupted with every step started
+fn write_interrupted_run(
+    cache: &std::path::Path,
+    source: &std::path::Path,
+    library: &std::path::Path,
+    mode: &str,
+    steps: &[(&std::path::Path, &std::path::Path)],
+) {
+    use sha2::{Digest, Sha256};
+
+    let library = library.canonicalize().unwrap();
+    let hash = Sha256::digest(library.to_string_lossy().as_bytes());
+    let runs = cache.join("audiobookctl").join("organize");
+    std::fs::create_dir_all(&runs).unwrap();
+    let run = serde_json::json!({
+        "source": source,
+        "dest": library,
+        "mode": mode,
+        "started_at": "2026-01-01T00:00:00Z",
+        "steps": steps
+            .iter()
+            .map(|(source, dest)| serde_json::json!({
+                "kind": "audiobook",
+                "source": source,
+                "dest": dest,
+                "status": "started",
+            }))
+            .collect::<Vec<_>>(),
+        "already_present": [],
+    });
+    std::fs::write(
+        runs.join(format!("{}.json", hex::encode(&hash[..8]))),
+        run.to_string(),
+    )
+    .unwrap();
+}
+
+fn resume_cmd(home: &std::path::Path, library: &std::path::Path) -> assert_cmd::Command {
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
+    cmd.env("HOME", home)
+        .env("XDG_CACHE_HOME", home.join("cache"))
+        .env("XDG_CONFIG_HOME", home.join("config"))
+        .env("XDG_DATA_HOME", home.join("data"))
+        .args(["organize", "--resume", "--no-dry-run", "--dest"])
+        .arg(library);
+    cmd
+}
+
+#[test]
+fn test_organize_resume_finishes_landed_move() {
+    use sha2::{Digest, Sha256};
+
+    let home = tempfile::tempdir().unwrap();
+    let source = home.path().canonicalize().unwrap().join("downloads");
+    let library = home.path().canonicalize().unwrap().join("library");
+    let book = source.join("Book").join("book.m4b");
+    let dest = library.join("Author").join("Book").join("book.m4b");
+    std::fs::create_dir_all(book.parent().unwrap()).unwrap();
+    std::fs::create_dir_all(dest.parent().unwrap()).unwrap();
+    make_m4b(&book, "Book");
+    // Copied across filesystems and verified, but the source wasn't deleted yet
+    std::fs::copy(&book, &dest).unwrap();
+
+    // A pending edit for the source
+    let pending = home
+        .path()
+        .join("cache")
+        .join("audiobookctl")
+        .join("pending");
+    std::fs::create_dir_all(&pending).unwrap();
+    let key = hex::encode(Sha256::digest(book.as_os_str().as_encoded_bytes()));
+    std::fs::write(
+        pending.join(format!("{}.toml", &key[..16])),
+        format!(
+            "# Pending edit for: {}\n# Created: 2026-01-01T00:00:00Z\n\ntitle = \"New\"\n",
+            book.display()
+        ),
+    )
+    .unwrap();
+
+    write_interrupted_run(
+        &home.path().join("cache"),
+        &source,
+        &library,
+        "move",
+        &[(&book, &dest)],
+    );
+    resume_cmd(home.path(), &library).assert().success();
+
+    assert!(!book.exists());
+    assert!(!source.join("Book").exists());
+    assert!(library.join("Author/Book/book.m4b.sha256").exists());
+
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
+    cmd.env("XDG_DATA_HOME", home.path().join("data"))
+        .arg("history");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "move {} -> {}",
+            book.display(),
+            dest.display()
+        )));
+
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
+    cmd.env("XDG_CACHE_HOME", home.path().join("cache"))
+        .args(["pending", "list"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(dest.display().to_string()));
+}
+
+#[test]
+fn test_organize_resume_records_mode_actually_used() {
+    let home = tempfile::tempdir().unwrap();
+    let source = home.path().canonicalize().unwrap().join("downloads");
+    let library = home.path().canonicalize().unwrap().join("library");
+    std::fs::create_dir_all(&source).unwrap();
+    let linked = source.join("linked.m4b");
+    let copied = source.join("copied.m4b");
+    make_m4b(&linked, "Linked");
+    make_m4b(&copied, "Copied");
+    let linked_dest = library.join("Author").join("Linked").join("linked.m4b");
+    let copied_dest = library.join("Author").join("Copied").join("copied.m4b");
+    std::fs::create_dir_all(linked_dest.parent().unwrap()).unwrap();
+    std::fs::create_dir_all(copied_dest.parent().unwrap()).unwrap();
+    std::fs::hard_link(&linked, &linked_dest).unwrap();
+    // A hardlink that fell back to copying
+    std::fs::copy(&copied, &copied_dest).unwrap();
+
+    write_interrupted_run(
+        &home.path().join("cache"),
+        &source,
+        &library,
+        "hardlink",
+        &[(&linked, &linked_dest), (&copied, &copied_dest)],
+    );
+    resume_cmd(home.path(), &library).assert().success();
+
+    let db = rusqlite::Connection::open(library.join(".audiobookctl.db")).unwrap();
+    let links: Vec<(String, String)> = db
+        .prepare("SELECT file_path, kind FROM links")
+        .unwrap()
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(
+        links,
+        vec![(
+            "Author/Linked/linked.m4b".to_string(),
+            "hardlink".to_string()
+        )]
+    );
+}
@@ -1,9 +1,9 @@
-use assert_cmd::Command;
+use assert_cmd::cargo::cargo_bin_cmd;
 use predicates::prelude::*;
 
 #[test]
 fn test_lookup_all_help() {
-    let mut cmd = Command::cargo_bin("audiobookctl").unwrap();
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
     cmd.args(["lookup-all", "--help"])
         .assert()
         .success()
@@ -13,14 +13,19 @@ fn test_lookup_all_help() {
         .stdout(predicate::str::contains("--auto-accept"))
         .stdout(predicate::str::contains("--no-dry-run"))
         .stdout(predicate::str::contains("--yes"))
-        .stdout(predicate::str::contains("--no-backup-i-void-my-warranty"));
+        .stdout(predicate::str::contains("--no-backup-i-void-my-warranty"))
+        .stdout(predicate::str::contains("--jobs"))
+        .stdout(predicate::str::contains("--resume"))
+        .stdout(predicate::str::contains("--report"))
+        .stdout(predicate::str::contains("--fields"))
+        .stdout(predicate::str::contains("--only-missing"));
 }
 
 #[test]
 fn test_lookup_all_empty_directory() {
     let temp = tempfile::tempdir().unwrap();
 
-    let mut cmd = Command::cargo_bin("audiobookctl").unwrap();
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
     cmd.args(["lookup-all", temp.path().to_str().unwrap()])
         .assert()
         .success()
@@ -29,16 +34,86 @@ fn test_lookup_all_empty_directory() {
 
 #[test]
 fn test_lookup_all_nonexistent_directory() {
-    let mut cmd = Command::cargo_bin("audiobookctl").unwrap();
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
     cmd.args(["lookup-all", "/nonexistent/directory/path"])
         .assert()
         .success()
         .stdout(predicate::str::contains("No .m4b files found"));
 }
 
+#[test]
+fn test_lookup_all_rejects_unknown_field() {
+    let temp = tempfile::tempdir().unwrap();
+    std::fs::write(temp.path().join("book.m4b"), b"not really an m4b").unwrap();
+
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
+    cmd.args(["lookup-all"])
+        .arg(temp.path())
+        .args(["--fields", "narrator,colour"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown field \"colour\""));
+}
+
+#[test]
+fn test_lock_help() {
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
+    cmd.args(["lock", "--help"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Lock fields so lookups never change them",
+        ))
+        .stdout(predicate::str::contains("--unlock"));
+}
+
+#[test]
+fn test_lookup_apply_help() {
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
+    cmd.args(["lookup-apply", "--help"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Apply the decisions recorded"))
+        .stdout(predicate::str::contains("--no-dry-run"));
+}
+
+#[test]
+fn test_lookup_apply_rejects_missing_file() {
+    let temp = tempfile::tempdir().unwrap();
+    let report = temp.path().join("report.json");
+    std::fs::write(
+        &report,
+        r#"{
+            "generated_at": "2026-01-01T00:00:00Z",
+            "dir": "/nonexistent",
+            "files": [{
+                "path": "/nonexistent/book.m4b",
+                "status": "updates_available",
+                "sources": ["audible"],
+                "fields": [{
+                    "field": "title",
+                    "state": "agreed",
+                    "current": null,
+                    "proposed": "The Martian",
+                    "apply": true
+                }]
+            }]
+        }"#,
+    )
+    .unwrap();
+
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
+    cmd.args(["lookup-apply", report.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "0 files to update, 0 without changes, 1 rejected",
+        ));
+}
+
 #[test]
 fn test_backups_list_help() {
-    let mut cmd = Command::cargo_bin("audiobookctl").unwrap();
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
     cmd.args(["backups", "list", "--help"])
         .assert()
         .success()
@@ -47,7 +122,7 @@ fn test_backups_list_help() {
 
 #[test]
 fn test_backups_clean_help() {
-    let mut cmd = Command::cargo_bin("audiobookctl").unwrap();
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
     cmd.args(["backups", "clean", "--help"])
         .assert()
         .success()
@@ -57,7 +132,7 @@ fn test_backups_clean_help() {
 #[test]
 fn test_backups_list_empty_directory() {
     let temp = tempfile::tempdir().unwrap();
-    let mut cmd = Command::cargo_bin("audiobookctl").unwrap();
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
     cmd.args(["backups", "list", temp.path().to_str().unwrap()])
         .assert()
         .success()
@@ -67,7 +142,7 @@ fn test_backups_list_empty_directory() {
 #[test]
 fn test_backups_clean_empty_directory() {
     let temp = tempfile::tempdir().unwrap();
-    let mut cmd = Command::cargo_bin("audiobookctl").unwrap();
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
     cmd.args([
         "backups",
         "clean",
@@ -79,3 +154,29 @@ fn test_backups_clean_empty_directory() {
     .success()
     .stdout(predicate::str::contains("No backup files to clean"));
 }
+
+#[test]
+fn test_backups_verify_flags_unreadable_backup() {
+    let temp = tempfile::tempdir().unwrap();
+    std::fs::write(temp.path().join("book.m4b"), "not an m4b").unwrap();
+    std::fs::write(temp.path().join("book.m4b.bak"), "not an m4b either").unwrap();
+
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
+    cmd.args(["backups", "verify", temp.path().to_str().unwrap()])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("OK: 0, Untrusted: 1"));
+}
+
+#[test]
+fn test_backups_restore_without_backup() {
+    let temp = tempfile::tempdir().unwrap();
+    let file = temp.path().join("book.m4b");
+    std::fs::write(&file, "not an m4b").unwrap();
+
+    let mut cmd = cargo_bin_cmd!("audiobookctl");
+    cmd.args(["backups", "restore", file.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No backup found"));
+}